@@ -0,0 +1,168 @@
+use syn::{spanned::Spanned, Attribute, LitStr};
+
+/// Casing applied to every field name by `#[kvdb(rename_all = "...")]`.
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    Camel,
+    Pascal,
+}
+
+impl RenameRule {
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        Ok(match lit.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "camelCase" => Self::Camel,
+            "PascalCase" => Self::Pascal,
+            _ => return Err(syn::Error::new(lit.span(), "unknown rename_all rule")),
+        })
+    }
+
+    /// Renames a PascalCase enum variant name.
+    pub fn apply_to_variant(&self, name: &str) -> String {
+        match self {
+            Self::Lower => return name.to_lowercase(),
+            Self::Upper => return name.to_uppercase(),
+            _ => {}
+        }
+        let mut snake = String::new();
+        for (i, c) in name.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        }
+        self.apply(&snake)
+    }
+
+    /// Renames a snake_case Rust field name.
+    pub fn apply(&self, name: &str) -> String {
+        let words = name.split('_').filter(|w| !w.is_empty());
+        let capitalize = |w: &str| {
+            let mut chars = w.chars();
+            chars.next().map_or_else(String::new, |c| {
+                c.to_uppercase().chain(chars).collect::<String>()
+            })
+        };
+        match self {
+            Self::Lower => name.to_lowercase(),
+            Self::Upper => name.to_uppercase(),
+            Self::Snake => name.to_owned(),
+            Self::ScreamingSnake => name.to_uppercase(),
+            Self::Kebab => words.collect::<Vec<_>>().join("-"),
+            Self::Camel => words
+                .enumerate()
+                .map(|(i, w)| if i == 0 { w.to_owned() } else { capitalize(w) })
+                .collect(),
+            Self::Pascal => words.map(capitalize).collect(),
+        }
+    }
+}
+
+/// Options from `#[kvdb(...)]` on the struct.
+#[derive(Default)]
+pub struct ContainerAttrs {
+    pub prefix: Option<LitStr>,
+    pub id: Option<LitStr>,
+    pub rename_all: Option<RenameRule>,
+    pub tag: Option<LitStr>,
+    pub version: Option<u32>,
+}
+
+impl ContainerAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("kvdb")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("prefix") {
+                    parsed.prefix = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("id") {
+                    parsed.id = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("rename_all") {
+                    parsed.rename_all = Some(RenameRule::parse(&meta.value()?.parse()?)?);
+                } else if meta.path.is_ident("tag") {
+                    parsed.tag = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("version") {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    let version = lit.base10_parse()?;
+                    if version == 0 || version > i32::MAX as u32 {
+                        return Err(syn::Error::new(lit.span(), "kvdb version must be positive"));
+                    }
+                    parsed.version = Some(version);
+                } else {
+                    return Err(meta.error("unsupported kvdb container attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// Value used for a field that is skipped or missing from the stored object.
+pub enum FieldDefault {
+    Trait,
+    Path(syn::ExprPath),
+}
+
+/// Options from `#[kvdb(...)]` on a field.
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub rename: Option<LitStr>,
+    pub skip: bool,
+    pub default: Option<FieldDefault>,
+    pub with: Option<syn::Path>,
+    pub flatten: bool,
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("kvdb")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("skip") {
+                    parsed.skip = true;
+                } else if meta.path.is_ident("default") {
+                    parsed.default = Some(if meta.input.peek(syn::Token![=]) {
+                        let lit: LitStr = meta.value()?.parse()?;
+                        FieldDefault::Path(lit.parse()?)
+                    } else {
+                        FieldDefault::Trait
+                    });
+                } else if meta.path.is_ident("flatten") {
+                    parsed.flatten = true;
+                } else if meta.path.is_ident("with") {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    parsed.with = Some(lit.parse()?);
+                } else {
+                    return Err(meta.error("unsupported kvdb field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        let attr = || attrs.iter().find(|a| a.path().is_ident("kvdb")).unwrap();
+        if parsed.skip && (parsed.rename.is_some() || parsed.with.is_some() || parsed.flatten) {
+            return Err(syn::Error::new(
+                attr().span(),
+                "skipped fields cannot be renamed, flattened or use `with`",
+            ));
+        }
+        if parsed.flatten && parsed.with.is_some() {
+            return Err(syn::Error::new(
+                attr().span(),
+                "flattened fields cannot use `with`",
+            ));
+        }
+        Ok(parsed)
+    }
+}
@@ -1,59 +1,811 @@
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::ext::IdentExt;
 
 extern crate proc_macro;
 extern crate syn;
 #[macro_use]
 extern crate quote;
 
-#[proc_macro_derive(KVDBEntity)]
+mod attrs;
+
+use attrs::{ContainerAttrs, FieldAttrs, FieldDefault};
+
+/// Derives conversions between a struct and `KVDBObject`.
+///
+/// Unit-only enums are stored as strings, enums with struct variants as objects with a
+/// `type` discriminator (`#[kvdb(tag = "...")]`), newtypes as the inner value and tuple
+/// structs as lists.
+///
+/// Container attributes: `#[kvdb(rename_all = "camelCase")]`,
+/// `#[kvdb(prefix = "user", id = "id")]` which generates `<Name>Repository` and
+/// `#[kvdb(version = 2)]` which stores the version in the `_version` field so that older
+/// objects can be migrated.
+///
+/// Field attributes: `rename = "name"`, `skip`, `default` / `default = "path"` used
+/// when the field is missing, and `with = "module"` where the module provides
+/// `into_value(T) -> KVDBValue` and `from_value(&KVDBValue) -> Result<T>`.
+/// `flatten` stores the fields of a nested entity in the parent object, prefixed
+/// with the field name (`address.city`); other nested entities are stored as maps.
+///
+/// `KVDBEntity::fields` lists the stored fields with their types, `Option` and `default`
+/// fields are not required. It is used to derive a `MapSchema`.
+#[proc_macro_derive(KVDBEntity, attributes(kvdb))]
 pub fn kvdb_entity(i: TokenStream) -> TokenStream {
-    let s = i.to_string();
-    let ast = syn::parse_str(&s).unwrap();
-    gen_into_hashmap(&ast)
+    let ast = syn::parse_macro_input!(i as syn::DeriveInput);
+    let mut gen = gen_into_hashmap(&ast).unwrap_or_else(|e| e.to_compile_error().into());
+    gen.extend(gen_repository(&ast).unwrap_or_else(|e| e.to_compile_error().into()));
+    gen
 }
 
-fn gen_into_hashmap(ast: &syn::DeriveInput) -> TokenStream {
+/// Generates `<Name>Repository` when the struct has a `prefix` and an `id` field.
+fn gen_repository(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let attrs = ContainerAttrs::parse(&ast.attrs)?;
+    let (prefix, id) = match (attrs.prefix, attrs.id) {
+        (Some(prefix), Some(id)) => (prefix, id),
+        (None, None) => return Ok(TokenStream::new()),
+        (Some(prefix), None) => {
+            return Err(syn::Error::new(
+                prefix.span(),
+                "kvdb prefix requires an id field",
+            ))
+        }
+        (None, Some(id)) => return Err(syn::Error::new(id.span(), "kvdb id requires a prefix")),
+    };
+
+    if !ast.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ast.generics,
+            "kvdb repository cannot be generated for generic types",
+        ));
+    }
+    let fields = match &ast.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ast,
+                "kvdb repository requires a struct",
+            ))
+        }
+    };
+    let id_field = fields
+        .iter()
+        .filter_map(|f| f.ident.as_ref())
+        .find(|f| *f == &id.value())
+        .ok_or_else(|| syn::Error::new(id.span(), format!("no field named `{}`", id.value())))?;
+
     let name = &ast.ident;
+    let vis = &ast.vis;
+    let repo = format_ident!("{}Repository", name);
+    let key = quote!(minikvdb::minikvdb::kvdb_key::Key);
+    let result = quote!(minikvdb::error::Result);
+
+    let gen = quote! {
+        #[derive(Debug, Clone)]
+        #vis struct #repo {
+            db: minikvdb::prelude::MiniKVDB,
+        }
+
+        #[automatically_derived]
+        impl #repo {
+            pub const PREFIX: &'static str = #prefix;
+
+            pub fn new(db: &minikvdb::prelude::MiniKVDB) -> Self {
+                Self { db: db.clone() }
+            }
+
+            pub fn key(id: impl std::fmt::Display) -> #key {
+                minikvdb::minikvdb::namespaced_key::NamespacedKey::new(Self::PREFIX)
+                    .ns(id)
+                    .compose()
+            }
+
+            /// Ids containing `:` are rejected, they would collide with nested keys.
+            pub fn save(&self, entity: #name) -> #result<()> {
+                let id = entity.#id_field.to_string();
+                if id.contains(':') {
+                    return Err(minikvdb::error::MiniKVDBError::InvalidId(id));
+                }
+                self.db.hash_set(Self::key(id), entity)?;
+                Ok(())
+            }
+
+            pub fn find_by_id(&self, id: impl std::fmt::Display) -> #result<Option<#name>> {
+                self.db.hash_get_object(Self::key(id))
+            }
+
+            pub fn delete(&self, id: impl std::fmt::Display) -> #result<bool> {
+                Ok(self.db.hash_delete(Self::key(id))?.is_some())
+            }
+
+            pub fn exists(&self, id: impl std::fmt::Display) -> #result<bool> {
+                self.db.hash_contains_key(Self::key(id))
+            }
+
+            /// Entities stored directly under the prefix, nested keys such as
+            /// `prefix:id:settings` are not included.
+            pub fn list_all(&self) -> #result<Vec<#name>> {
+                let mut all = Vec::new();
+                for key in self.keys()? {
+                    all.extend(self.db.hash_get_object(key)?);
+                }
+                Ok(all)
+            }
+
+            pub fn count(&self) -> #result<usize> {
+                Ok(self.keys()?.len())
+            }
+
+            /// Sorted keys of the entities.
+            fn keys(&self) -> #result<Vec<#key>> {
+                let prefix = format!("{}:", Self::PREFIX);
+                Ok(self
+                    .db
+                    .hash_keys(prefix.as_str())?
+                    .into_iter()
+                    .filter(|key| !key.0[prefix.len()..].contains(':'))
+                    .collect())
+            }
+        }
+    };
+
+    Ok(gen.into())
+}
+
+fn gen_into_hashmap(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let container = ContainerAttrs::parse(&ast.attrs)?;
+    let named =
+        matches!(&ast.data, syn::Data::Struct(d) if matches!(d.fields, syn::Fields::Named(_)));
+    if container.version.is_some() && !named {
+        return Err(syn::Error::new_spanned(
+            &ast.ident,
+            "kvdb version is supported only on structs with named fields",
+        ));
+    }
+    match &ast.data {
+        syn::Data::Struct(data) => match &data.fields {
+            syn::Fields::Named(fields) => gen_struct(ast, &container, fields),
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => gen_newtype(ast, fields),
+            syn::Fields::Unnamed(fields) => gen_tuple(ast, fields),
+            syn::Fields::Unit => Err(syn::Error::new_spanned(
+                &ast.ident,
+                "KVDBEntity cannot be derived for unit structs",
+            )),
+        },
+        syn::Data::Enum(data) if data.variants.is_empty() => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "KVDBEntity cannot be derived for empty enums",
+        )),
+        syn::Data::Enum(data) => {
+            if let Some(v) = data
+                .variants
+                .iter()
+                .find(|v| matches!(v.fields, syn::Fields::Unnamed(_)))
+            {
+                return Err(syn::Error::new_spanned(
+                    v,
+                    "KVDBEntity supports only unit and struct enum variants",
+                ));
+            }
+            if data.variants.iter().all(|v| v.fields.is_empty()) && container.tag.is_none() {
+                gen_unit_enum(ast, &container, data)
+            } else {
+                gen_tagged_enum(ast, &container, data)
+            }
+        }
+        syn::Data::Union(data) => Err(syn::Error::new_spanned(
+            data.union_token,
+            "KVDBEntity cannot be derived for unions",
+        )),
+    }
+}
 
-    let data = if let syn::Data::Struct(data) = &ast.data {
-        data
+/// Conversions of named fields from bindings into object entries and back.
+#[derive(Default)]
+struct NamedFields {
+    names: Vec<syn::Ident>,
+    /// Stored name of every field that is neither skipped nor flattened.
+    keys: Vec<(String, proc_macro2::Span)>,
+    insert: Vec<TokenStream2>,
+    get: Vec<TokenStream2>,
+    into_bounds: Vec<TokenStream2>,
+    from_bounds: Vec<TokenStream2>,
+    fields: Vec<TokenStream2>,
+    field_bounds: Vec<TokenStream2>,
+}
+
+impl NamedFields {
+    fn new(container: &ContainerAttrs, fields: &syn::FieldsNamed) -> syn::Result<Self> {
+        let mut out = Self::default();
+        for f in fields.named.iter() {
+            let name = f.ident.clone().unwrap();
+            let ty = &f.ty;
+            let attrs = FieldAttrs::parse(&f.attrs)?;
+            let has_default = attrs.default.is_some();
+            let default = match &attrs.default {
+                Some(FieldDefault::Path(path)) => quote!(#path()),
+                _ => quote!(Default::default()),
+            };
+            if (attrs.default.is_some() || attrs.skip)
+                && !matches!(attrs.default, Some(FieldDefault::Path(_)))
+            {
+                out.from_bounds.push(quote!(#ty: Default));
+            }
+            if attrs.skip {
+                out.get.push(quote!(#name: #default));
+                continue;
+            }
+            let field = name.unraw().to_string();
+            let key = match (&attrs.rename, container.rename_all) {
+                (Some(rename), _) => rename.value(),
+                (None, Some(rule)) => rule.apply(&field),
+                (None, None) => field,
+            };
+            let missing = if attrs.default.is_some() {
+                default
+            } else if is_option(ty) {
+                quote!(None)
+            } else {
+                quote!(return Err(minikvdb::error::MiniKVDBError::MissingField(#key.to_string())))
+            };
+
+            let required = !has_default && !is_option(ty);
+            if attrs.flatten && is_option(ty) {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "optional fields cannot be flattened, store them as nested maps instead",
+                ));
+            }
+            if attrs.flatten {
+                let prefix = format!("{key}.");
+                out.fields.push(quote! {
+                    fields.extend(<#ty as minikvdb::prelude::KVDBEntity>::fields().into_iter().map(|f| {
+                        minikvdb::prelude::EntityField {
+                            name: format!("{}{}", #prefix, f.name.0).into(),
+                            required: f.required && #required,
+                            ..f
+                        }
+                    }));
+                });
+                out.field_bounds
+                    .push(quote!(#ty: minikvdb::prelude::KVDBEntity));
+                out.insert.push(quote! {
+                    for (k, v) in minikvdb::prelude::KVDBObject::from(#name) {
+                        obj.insert(format!("{}{}", #prefix, k.0).into(), v);
+                    }
+                });
+                out.into_bounds
+                    .push(quote!(#ty: Into<minikvdb::prelude::KVDBObject>));
+                out.from_bounds.push(quote! {
+                    #ty: TryFrom<minikvdb::prelude::KVDBObject, Error = minikvdb::error::MiniKVDBError>
+                });
+                let convert = quote! {
+                    nested
+                        .try_into()
+                        .map_err(|e: minikvdb::error::MiniKVDBError| e.prefixed(#prefix))?
+                };
+                let convert = if has_default {
+                    quote!(if nested.is_empty() { #missing } else { #convert })
+                } else {
+                    convert
+                };
+                out.get.push(quote! {
+                    #name: {
+                        let nested: minikvdb::prelude::KVDBObject = v
+                            .iter()
+                            .filter_map(|(k, v)| k.0.strip_prefix(#prefix).map(|k| (k.into(), v.clone())))
+                            .collect();
+                        #convert
+                    }
+                });
+                out.names.push(name);
+                continue;
+            }
+
+            out.keys.push((key.clone(), name.span()));
+            out.fields.push(match &attrs.with {
+                Some(_) => quote! {
+                    fields.push(minikvdb::prelude::EntityField {
+                        name: #key.into(),
+                        kvdb_type: None,
+                        required: #required,
+                    });
+                },
+                None => quote! {
+                    fields.push({
+                        #[allow(unused_imports)]
+                        use minikvdb::minikvdb::kvdb_type::{AnyFieldType as _, KnownFieldType as _};
+                        let (kvdb_type, optional) =
+                            (&minikvdb::minikvdb::kvdb_type::FieldTypeProbe::<#ty>(std::marker::PhantomData))
+                                .field_type();
+                        minikvdb::prelude::EntityField {
+                            name: #key.into(),
+                            kvdb_type,
+                            required: #required && !optional,
+                        }
+                    });
+                },
+            });
+            out.insert.push(match &attrs.with {
+                Some(with) => quote!(obj.insert(#key.into(), #with::into_value(#name));),
+                None => quote!(obj.insert(#key.into(), #name.into());),
+            });
+            let convert = match &attrs.with {
+                Some(with) => quote! {
+                    #with::from_value(v)
+                        .map_err(|e| minikvdb::error::MiniKVDBError::field(#key, e))?
+                },
+                None => quote! {
+                    <#ty as TryFrom<&minikvdb::prelude::KVDBValue>>::try_from(v)
+                        .map_err(|e| minikvdb::error::MiniKVDBError::field(#key, e))?
+                },
+            };
+            if attrs.with.is_none() {
+                out.into_bounds
+                    .push(quote!(#ty: Into<minikvdb::prelude::KVDBValue>));
+                out.from_bounds.push(quote! {
+                    #ty: for<'a> TryFrom<&'a minikvdb::prelude::KVDBValue, Error = minikvdb::error::MiniKVDBError>
+                });
+            }
+
+            out.get.push(quote! {
+                #name: match v.get(#key) {
+                    Some(v) => #convert,
+                    None => #missing,
+                }
+            });
+            out.names.push(name);
+        }
+        Ok(out)
+    }
+}
+
+/// Where clause of the generics extended with `bounds` when the type is generic.
+fn where_clause(generics: &syn::Generics, bounds: &[TokenStream2]) -> TokenStream2 {
+    let predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|w| w.predicates.iter());
+    if generics.params.is_empty() {
+        quote!(where #(#predicates,)*)
     } else {
-        unimplemented!();
+        quote!(where #(#predicates,)* #(#bounds,)*)
+    }
+}
+
+/// `KVDBEntity` and `KVDBValue` conversions of types stored as map objects, `fields`
+/// push the `EntityField`s of the object.
+fn gen_object_value(
+    ast: &syn::DeriveInput,
+    version: Option<u32>,
+    fields: &[TokenStream2],
+    field_bounds: &[TokenStream2],
+) -> TokenStream2 {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, _) = ast.generics.split_for_impl();
+    let into = where_clause(
+        &ast.generics,
+        &[quote!(#name #ty_generics: Into<minikvdb::prelude::KVDBObject>)],
+    );
+    let from = where_clause(
+        &ast.generics,
+        &[
+            quote!(#name #ty_generics: TryFrom<minikvdb::prelude::KVDBObject, Error = minikvdb::error::MiniKVDBError>),
+        ],
+    );
+    let mut entity_bounds = vec![
+        quote!(#name #ty_generics: Into<minikvdb::prelude::KVDBObject>),
+        quote!(#name #ty_generics: TryFrom<minikvdb::prelude::KVDBObject, Error = minikvdb::error::MiniKVDBError>),
+    ];
+    entity_bounds.extend_from_slice(field_bounds);
+    let entity = where_clause(&ast.generics, &entity_bounds);
+    let field_type = where_clause(&ast.generics, &[]);
+    let version = version.map(|v| quote!(const VERSION: u32 = #v;));
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics minikvdb::prelude::KVDBEntity for #name #ty_generics #entity {
+            #version
+            #[allow(unused_mut)]
+            fn fields() -> Vec<minikvdb::prelude::EntityField> {
+                let mut fields = Vec::new();
+                #(#fields)*
+                fields
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics minikvdb::prelude::KVDBFieldType for #name #ty_generics #field_type {
+            fn kvdb_type() -> Option<minikvdb::prelude::KVDBType> {
+                Some(minikvdb::prelude::KVDBType::Map)
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics From<#name #ty_generics> for minikvdb::prelude::KVDBValue #into {
+            fn from(v: #name #ty_generics) -> minikvdb::prelude::KVDBValue {
+                minikvdb::prelude::KVDBValue::Map(v.into())
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<minikvdb::prelude::KVDBValue> for #name #ty_generics #from {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                minikvdb::prelude::KVDBObject::try_from(v)?.try_into()
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<&minikvdb::prelude::KVDBValue> for #name #ty_generics #from {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: &minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                minikvdb::prelude::KVDBObject::try_from(v)?.try_into()
+            }
+        }
+    }
+}
+
+/// Structs with named fields are stored as objects.
+fn gen_struct(
+    ast: &syn::DeriveInput,
+    container: &ContainerAttrs,
+    fields: &syn::FieldsNamed,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, _) = ast.generics.split_for_impl();
+    let NamedFields {
+        names,
+        mut insert,
+        get,
+        into_bounds,
+        from_bounds,
+        mut fields,
+        field_bounds,
+        ..
+    } = NamedFields::new(container, fields)?;
+    let into = where_clause(&ast.generics, &into_bounds);
+    let from = where_clause(&ast.generics, &from_bounds);
+    if let Some(version) = container.version {
+        let version = version as i32;
+        insert.push(quote! {
+            obj.insert(
+                minikvdb::minikvdb::kvdb_entity::VERSION_FIELD.into(),
+                minikvdb::prelude::KVDBValue::Int(#version),
+            );
+        });
+        fields.push(quote! {
+            fields.push(minikvdb::prelude::EntityField {
+                name: minikvdb::minikvdb::kvdb_entity::VERSION_FIELD.into(),
+                kvdb_type: Some(minikvdb::prelude::KVDBType::Int),
+                required: false,
+            });
+        });
+    }
+    let value = gen_object_value(ast, container.version, &fields, &field_bounds);
+
+    let gen = quote! {
+        #[automatically_derived]
+        impl #impl_generics From<#name #ty_generics> for minikvdb::prelude::KVDBObject #into {
+            #[allow(unused_mut)]
+            fn from(v: #name #ty_generics) -> minikvdb::prelude::KVDBObject {
+                let #name { #(#names,)* .. } = v;
+                let mut obj = minikvdb::prelude::KVDBObject::new();
+                #(#insert)*
+                obj
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<minikvdb::prelude::KVDBObject> for #name #ty_generics #from {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: minikvdb::prelude::KVDBObject) -> Result<Self, Self::Error> {
+                Ok(Self {
+                    #(#get,)*
+                })
+            }
+        }
+        #value
     };
 
-    let fields = data.fields.iter().map(|f| {
-        let name = &f.ident;
-        quote! {
-            (stringify!(#name).to_string().into(), v.#name.into())
+    Ok(gen.into())
+}
+
+/// Newtype structs are stored as the inner value.
+fn gen_newtype(ast: &syn::DeriveInput, fields: &syn::FieldsUnnamed) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, _) = ast.generics.split_for_impl();
+    let ty = &fields.unnamed[0].ty;
+    let into = where_clause(
+        &ast.generics,
+        &[quote!(#ty: Into<minikvdb::prelude::KVDBValue>)],
+    );
+    let from = where_clause(
+        &ast.generics,
+        &[
+            quote!(#ty: TryFrom<minikvdb::prelude::KVDBValue, Error = minikvdb::error::MiniKVDBError>),
+        ],
+    );
+    let from_ref = where_clause(
+        &ast.generics,
+        &[
+            quote!(#ty: for<'a> TryFrom<&'a minikvdb::prelude::KVDBValue, Error = minikvdb::error::MiniKVDBError>),
+        ],
+    );
+    let field_type = where_clause(&ast.generics, &[]);
+
+    let gen = quote! {
+        #[automatically_derived]
+        impl #impl_generics From<#name #ty_generics> for minikvdb::prelude::KVDBValue #into {
+            fn from(v: #name #ty_generics) -> minikvdb::prelude::KVDBValue {
+                v.0.into()
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<minikvdb::prelude::KVDBValue> for #name #ty_generics #from {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                Ok(Self(v.try_into()?))
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<&minikvdb::prelude::KVDBValue> for #name #ty_generics #from_ref {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: &minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                Ok(Self(v.try_into()?))
+            }
         }
-    });
+        #[automatically_derived]
+        impl #impl_generics minikvdb::prelude::KVDBFieldType for #name #ty_generics #field_type {
+            fn kvdb_type() -> Option<minikvdb::prelude::KVDBType> {
+                #[allow(unused_imports)]
+                use minikvdb::minikvdb::kvdb_type::{AnyFieldType as _, KnownFieldType as _};
+                (&minikvdb::minikvdb::kvdb_type::FieldTypeProbe::<#ty>(std::marker::PhantomData))
+                    .field_type()
+                    .0
+            }
+        }
+    };
 
-    let fields_get = data.fields.iter().map(|f| {
-        let name = &f.ident;
-        quote!{
-            #name: v.get(stringify!(#name)).ok_or(minikvdb::error::MiniKVDBError::MissingField(stringify!(#name).to_string()))?.try_into()?
+    Ok(gen.into())
+}
+
+/// Tuple structs are stored as lists of their fields.
+fn gen_tuple(ast: &syn::DeriveInput, fields: &syn::FieldsUnnamed) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, _) = ast.generics.split_for_impl();
+    let field_type = where_clause(&ast.generics, &[]);
+    let len = fields.unnamed.len();
+    let index = (0..len).map(syn::Index::from).collect::<Vec<_>>();
+    let types = fields.unnamed.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let into = where_clause(
+        &ast.generics,
+        &types
+            .iter()
+            .map(|ty| quote!(#ty: Into<minikvdb::prelude::KVDBValue>))
+            .collect::<Vec<_>>(),
+    );
+    let from = where_clause(
+        &ast.generics,
+        &types
+            .iter()
+            .map(|ty| quote!(#ty: for<'a> TryFrom<&'a minikvdb::prelude::KVDBValue, Error = minikvdb::error::MiniKVDBError>))
+            .collect::<Vec<_>>(),
+    );
+
+    let gen = quote! {
+        #[automatically_derived]
+        impl #impl_generics From<#name #ty_generics> for minikvdb::prelude::KVDBValue #into {
+            fn from(v: #name #ty_generics) -> minikvdb::prelude::KVDBValue {
+                minikvdb::prelude::KVDBValue::List(vec![#(v.#index.into(),)*])
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<&minikvdb::prelude::KVDBValue> for #name #ty_generics #from {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: &minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                match v {
+                    minikvdb::prelude::KVDBValue::List(v) if v.len() == #len => {
+                        Ok(Self(#((&v[#index]).try_into().map_err(|e| {
+                            minikvdb::error::MiniKVDBError::field(stringify!(#index), e)
+                        })?,)*))
+                    }
+                    minikvdb::prelude::KVDBValue::List(v) => {
+                        Err(minikvdb::error::MiniKVDBError::IndexOutOfBounds(v.len().min(#len)))
+                    }
+                    v => Err(minikvdb::error::MiniKVDBError::wrong_type(
+                        minikvdb::prelude::KVDBType::List,
+                        v,
+                    )),
+                }
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<minikvdb::prelude::KVDBValue> for #name #ty_generics #from {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                Self::try_from(&v)
+            }
         }
-    });
+        #[automatically_derived]
+        impl #impl_generics minikvdb::prelude::KVDBFieldType for #name #ty_generics #field_type {
+            fn kvdb_type() -> Option<minikvdb::prelude::KVDBType> {
+                Some(minikvdb::prelude::KVDBType::List)
+            }
+        }
+    };
+
+    Ok(gen.into())
+}
+
+/// Stored name of every enum variant.
+fn variant_names(container: &ContainerAttrs, data: &syn::DataEnum) -> syn::Result<Vec<String>> {
+    data.variants
+        .iter()
+        .map(|v| {
+            let attrs = FieldAttrs::parse(&v.attrs)?;
+            if attrs.skip || attrs.default.is_some() || attrs.with.is_some() {
+                return Err(syn::Error::new_spanned(
+                    &v.ident,
+                    "enum variants support only the rename attribute",
+                ));
+            }
+            let name = v.ident.to_string();
+            Ok(match (attrs.rename, container.rename_all) {
+                (Some(rename), _) => rename.value(),
+                (None, Some(rule)) => rule.apply_to_variant(&name),
+                (None, None) => name,
+            })
+        })
+        .collect()
+}
+
+/// Enums with only unit variants are stored as strings.
+fn gen_unit_enum(
+    ast: &syn::DeriveInput,
+    container: &ContainerAttrs,
+    data: &syn::DataEnum,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let variants = data.variants.iter().map(|v| &v.ident).collect::<Vec<_>>();
+    let names = variant_names(container, data)?;
 
     let gen = quote! {
         #[automatically_derived]
-        impl From<#name> for minikvdb::prelude::KVDBObject {
-            fn from(v: #name) -> minikvdb::prelude::KVDBObject {
-                [
-                    #(#fields,)*
-                ].into()
+        impl #impl_generics From<#name #ty_generics> for minikvdb::prelude::KVDBValue #where_clause {
+            fn from(v: #name #ty_generics) -> minikvdb::prelude::KVDBValue {
+                match v {
+                    #(#name::#variants => #names.into(),)*
+                }
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<&minikvdb::prelude::KVDBValue> for #name #ty_generics #where_clause {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: &minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                match v {
+                    #(minikvdb::prelude::KVDBValue::String(s) if s == #names => Ok(#name::#variants),)*
+                    minikvdb::prelude::KVDBValue::String(s) => {
+                        Err(minikvdb::error::MiniKVDBError::UnknownVariant(s.clone()))
+                    }
+                    v => Err(minikvdb::error::MiniKVDBError::wrong_type(
+                        minikvdb::prelude::KVDBType::String,
+                        v,
+                    )),
+                }
             }
         }
         #[automatically_derived]
-        impl TryFrom<minikvdb::prelude::KVDBObject> for #name {
+        impl #impl_generics TryFrom<minikvdb::prelude::KVDBValue> for #name #ty_generics #where_clause {
+            type Error = minikvdb::error::MiniKVDBError;
+            fn try_from(v: minikvdb::prelude::KVDBValue) -> Result<Self, Self::Error> {
+                Self::try_from(&v)
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics minikvdb::prelude::KVDBFieldType for #name #ty_generics #where_clause {
+            fn kvdb_type() -> Option<minikvdb::prelude::KVDBType> {
+                Some(minikvdb::prelude::KVDBType::String)
+            }
+        }
+    };
+
+    Ok(gen.into())
+}
+
+/// Enums with struct variants are stored as objects with a discriminator field,
+/// `type` unless set with `#[kvdb(tag = "...")]`.
+fn gen_tagged_enum(
+    ast: &syn::DeriveInput,
+    container: &ContainerAttrs,
+    data: &syn::DataEnum,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, _) = ast.generics.split_for_impl();
+    let tag = container
+        .tag
+        .as_ref()
+        .map_or_else(|| "type".to_owned(), |t| t.value());
+    let names = variant_names(container, data)?;
+
+    let mut into_arms = vec![];
+    let mut from_arms = vec![];
+    let mut into_bounds = vec![];
+    let mut from_bounds = vec![];
+    for (variant, stored) in data.variants.iter().zip(names.iter()) {
+        let ident = &variant.ident;
+        let fields = match &variant.fields {
+            syn::Fields::Named(fields) => NamedFields::new(container, fields)?,
+            _ => NamedFields::default(),
+        };
+        if let Some((_, span)) = fields.keys.iter().find(|(key, _)| *key == tag) {
+            return Err(syn::Error::new(
+                *span,
+                format!("field `{tag}` collides with the enum tag, rename it or set `#[kvdb(tag = \"...\")]`"),
+            ));
+        }
+        let NamedFields {
+            names, insert, get, ..
+        } = &fields;
+        into_arms.push(quote! {
+            #name::#ident { #(#names,)* .. } => {
+                let mut obj = minikvdb::prelude::KVDBObject::new();
+                obj.insert(#tag.into(), #stored.into());
+                #(#insert)*
+                obj
+            }
+        });
+        from_arms.push(quote! {
+            Some(minikvdb::prelude::KVDBValue::String(s)) if s == #stored => Ok(#name::#ident {
+                #(#get,)*
+            })
+        });
+        into_bounds.extend(fields.into_bounds);
+        from_bounds.extend(fields.from_bounds);
+    }
+    let into = where_clause(&ast.generics, &into_bounds);
+    let from = where_clause(&ast.generics, &from_bounds);
+    let tag_field = quote! {
+        fields.push(minikvdb::prelude::EntityField {
+            name: #tag.into(),
+            kvdb_type: Some(minikvdb::prelude::KVDBType::String),
+            required: true,
+        });
+    };
+    let value = gen_object_value(ast, None, &[tag_field], &[]);
+
+    let gen = quote! {
+        #[automatically_derived]
+        impl #impl_generics From<#name #ty_generics> for minikvdb::prelude::KVDBObject #into {
+            fn from(v: #name #ty_generics) -> minikvdb::prelude::KVDBObject {
+                match v {
+                    #(#into_arms,)*
+                }
+            }
+        }
+        #[automatically_derived]
+        impl #impl_generics TryFrom<minikvdb::prelude::KVDBObject> for #name #ty_generics #from {
             type Error = minikvdb::error::MiniKVDBError;
             fn try_from(v: minikvdb::prelude::KVDBObject) -> Result<Self, Self::Error> {
-                    Ok(Self {
-                        #(#fields_get,)*
-                    })
+                match v.get(#tag) {
+                    #(#from_arms,)*
+                    Some(minikvdb::prelude::KVDBValue::String(s)) => {
+                        Err(minikvdb::error::MiniKVDBError::UnknownVariant(s.clone()))
+                    }
+                    Some(v) => Err(minikvdb::error::MiniKVDBError::field(
+                        #tag,
+                        minikvdb::error::MiniKVDBError::wrong_type(minikvdb::prelude::KVDBType::String, v),
+                    )),
+                    None => Err(minikvdb::error::MiniKVDBError::MissingField(#tag.to_string())),
+                }
             }
         }
+        #value
     };
 
-    gen.into()
+    Ok(gen.into())
+}
+
+/// Option fields are optional, a missing field is read as `None`.
+fn is_option(ty: &syn::Type) -> bool {
+    if let syn::Type::Path(p) = ty {
+        p.qself.is_none() && p.path.segments.last().is_some_and(|s| s.ident == "Option")
+    } else {
+        false
+    }
 }
@@ -0,0 +1,68 @@
+//! Compares the throughput of single-lock and sharded stores.
+//!
+//! cargo run --release --example shard_bench -- [threads] [ops per thread] [shards]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use minikvdb::prelude::*;
+
+const KEYS: u64 = 10_000;
+
+fn run(db: &MiniKVDB, threads: usize, ops: usize) -> Duration {
+    let start = Instant::now();
+    thread::scope(|s| {
+        for t in 0..threads {
+            s.spawn(move || {
+                let mut rng = 0x9e3779b97f4a7c15u64 ^ t as u64;
+                for i in 0..ops {
+                    // xorshift64
+                    rng ^= rng << 13;
+                    rng ^= rng >> 7;
+                    rng ^= rng << 17;
+                    let key = format!("key:{}", rng % KEYS);
+                    match i % 4 {
+                        0 => {
+                            db.set(key, i as i32).unwrap();
+                        }
+                        1 => {
+                            db.push_back(key, values![i as i32]).unwrap();
+                        }
+                        2 => {
+                            let obj = KVDBObject::from([("n".into(), KVDBValue::Int(i as i32))]);
+                            db.hash_set(key, obj).unwrap();
+                        }
+                        _ => {
+                            db.get(key).unwrap();
+                        }
+                    }
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let mut args = std::env::args()
+        .skip(1)
+        .map(|a| a.parse::<usize>().unwrap());
+    let threads = args.next().unwrap_or(32);
+    let ops = args.next().unwrap_or(100_000);
+    let shards = args.next().unwrap_or(64);
+
+    for (name, db) in [
+        ("single lock", MiniKVDB::default()),
+        ("sharded", MiniKVDB::with_shards(shards)),
+    ] {
+        let elapsed = run(&db, threads, ops);
+        let total = (threads * ops) as f64;
+        println!(
+            "{name:>12}: {threads} threads, {:.0} ops in {elapsed:.2?}, {:.0} ops/s",
+            total,
+            total / elapsed.as_secs_f64()
+        );
+    }
+}
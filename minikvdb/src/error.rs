@@ -1,6 +1,12 @@
-use std::sync::{PoisonError, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    sync::{PoisonError, RwLockReadGuard, RwLockWriteGuard},
+};
 
-use crate::minikvdb::KVDBStore;
+#[cfg(feature = "schema")]
+use crate::map_store::map_schema::Violation;
+use crate::minikvdb::{kvdb_type::KVDBType, kvdb_value::KVDBValue, KVDBStore};
 
 pub type Result<T> = std::result::Result<T, MiniKVDBError>;
 
@@ -9,9 +15,166 @@ pub enum MiniKVDBError {
     RWLockWritePoison,
     RWLockReadPoison,
     CannotIncrement,
-    WrongFieldType,
-    InvalidObject,
+    /// The value has a different type than requested, `expected` is `None` when several
+    /// types are accepted.
+    WrongFieldType {
+        expected: Option<KVDBType>,
+        actual: KVDBType,
+    },
+    InvalidUtf8,
+    /// The object stored under `key` could not be converted.
+    InvalidObject {
+        key: String,
+        source: Box<MiniKVDBError>,
+    },
+    /// The value stored under `key` could not be converted.
+    InvalidValue {
+        key: String,
+        source: Box<MiniKVDBError>,
+    },
+    /// A field of an object could not be converted.
+    Field {
+        field: String,
+        source: Box<MiniKVDBError>,
+    },
     MissingField(String),
+    UnknownVariant(String),
+    InvalidPath(String),
+    IndexOutOfBounds(usize),
+    /// Length in bytes the value would have.
+    ValueTooLarge(usize),
+    MissingSearchIndex(String),
+    MissingCollection(String),
+    CollectionExists(String),
+    /// Expected and actual dimension of a vector.
+    DimensionMismatch(usize, usize),
+    Serde(String),
+    /// Key of the rejected object and the violated constraints.
+    #[cfg(feature = "schema")]
+    SchemaViolation(String, Vec<Violation>),
+    InvalidPattern(String),
+    /// The key is not tracked or its history does not reach back to the requested time.
+    NoHistory(String),
+    /// Repository ids cannot contain the key separator `:`.
+    InvalidId(String),
+}
+
+impl MiniKVDBError {
+    pub fn wrong_type(expected: impl Into<Option<KVDBType>>, actual: &KVDBValue) -> Self {
+        Self::WrongFieldType {
+            expected: expected.into(),
+            actual: actual.kvdb_type(),
+        }
+    }
+
+    pub fn field(field: impl Into<String>, source: MiniKVDBError) -> Self {
+        Self::Field {
+            field: field.into(),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn invalid_object(key: impl Into<String>, source: impl Into<MiniKVDBError>) -> Self {
+        Self::InvalidObject {
+            key: key.into(),
+            source: Box::new(source.into()),
+        }
+    }
+
+    pub fn invalid_value(key: impl Into<String>, source: MiniKVDBError) -> Self {
+        Self::InvalidValue {
+            key: key.into(),
+            source: Box::new(source),
+        }
+    }
+
+    /// Prefixes the names of missing or invalid fields, used for flattened entities.
+    pub fn prefixed(self, prefix: &str) -> Self {
+        match self {
+            Self::MissingField(f) => Self::MissingField(format!("{prefix}{f}")),
+            Self::Field { field, source } => Self::Field {
+                field: format!("{prefix}{field}"),
+                source,
+            },
+            e => e,
+        }
+    }
+
+    /// The innermost error of the chain.
+    pub fn root(&self) -> &MiniKVDBError {
+        match self {
+            Self::InvalidObject { source, .. }
+            | Self::InvalidValue { source, .. }
+            | Self::Field { source, .. } => source.root(),
+            e => e,
+        }
+    }
+}
+
+impl Display for MiniKVDBError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RWLockWritePoison => write!(f, "store lock poisoned while writing"),
+            Self::RWLockReadPoison => write!(f, "store lock poisoned while reading"),
+            Self::CannotIncrement => write!(f, "value cannot be incremented"),
+            Self::WrongFieldType {
+                expected: Some(expected),
+                actual,
+            } => write!(f, "expected {expected}, found {actual}"),
+            Self::WrongFieldType {
+                expected: None,
+                actual,
+            } => write!(f, "unexpected {actual} value"),
+            Self::InvalidUtf8 => write!(f, "value is not valid UTF-8"),
+            Self::InvalidObject { key, .. } => write!(f, "invalid object at `{key}`"),
+            Self::InvalidValue { key, .. } => write!(f, "invalid value at `{key}`"),
+            Self::Field { field, .. } => write!(f, "invalid field `{field}`"),
+            Self::MissingField(field) => write!(f, "missing field `{field}`"),
+            Self::UnknownVariant(variant) => write!(f, "unknown variant `{variant}`"),
+            Self::InvalidPath(path) => write!(f, "invalid path `{path}`"),
+            Self::IndexOutOfBounds(i) => write!(f, "index {i} is out of bounds"),
+            Self::ValueTooLarge(len) => write!(f, "value of {len} bytes is too large"),
+            Self::MissingSearchIndex(prefix) => write!(f, "no search index for `{prefix}`"),
+            Self::MissingCollection(name) => write!(f, "no vector collection `{name}`"),
+            Self::CollectionExists(name) => write!(f, "vector collection `{name}` already exists"),
+            Self::DimensionMismatch(expected, actual) => {
+                write!(
+                    f,
+                    "expected a vector of dimension {expected}, found {actual}"
+                )
+            }
+            Self::Serde(msg) => write!(f, "{msg}"),
+            #[cfg(feature = "schema")]
+            Self::SchemaViolation(key, violations) => {
+                let violations: Vec<_> = violations.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "object at `{key}` violates schema: {}",
+                    violations.join(", ")
+                )
+            }
+            Self::InvalidPattern(msg) => write!(f, "invalid pattern: {msg}"),
+            Self::NoHistory(key) => write!(f, "no history of `{key}` at the requested time"),
+            Self::InvalidId(id) => write!(f, "invalid id `{id}`, ids cannot contain `:`"),
+        }
+    }
+}
+
+impl std::error::Error for MiniKVDBError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidObject { source, .. }
+            | Self::InvalidValue { source, .. }
+            | Self::Field { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Infallible> for MiniKVDBError {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
 }
 
 impl<T: KVDBStore> From<PoisonError<RwLockWriteGuard<'_, T>>> for MiniKVDBError {
@@ -25,3 +188,49 @@ impl<T: KVDBStore> From<PoisonError<RwLockReadGuard<'_, T>>> for MiniKVDBError {
         Self::RWLockReadPoison
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn displays_error_chain() {
+        let err = MiniKVDBError::invalid_object(
+            "user:1",
+            MiniKVDBError::field("age", MiniKVDBError::wrong_type(KVDBType::Int, &"x".into())),
+        );
+        assert_eq!(err.to_string(), "invalid object at `user:1`");
+
+        let mut chain = vec![];
+        let mut current: Option<&dyn Error> = Some(&err);
+        while let Some(e) = current {
+            chain.push(e.to_string());
+            current = e.source();
+        }
+        assert_eq!(
+            chain,
+            vec![
+                "invalid object at `user:1`",
+                "invalid field `age`",
+                "expected Int, found String"
+            ]
+        );
+        assert!(matches!(
+            err.root(),
+            MiniKVDBError::WrongFieldType {
+                expected: Some(KVDBType::Int),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn converts_into_boxed_errors() {
+        fn fails() -> std::result::Result<(), Box<dyn Error>> {
+            Err(MiniKVDBError::MissingField("name".to_owned()))?
+        }
+        assert_eq!(fails().unwrap_err().to_string(), "missing field `name`");
+    }
+}
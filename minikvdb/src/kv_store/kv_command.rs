@@ -12,6 +12,38 @@ pub struct DeleteCommand(pub Key);
 #[derive(Debug, Clone)]
 pub struct IncrementCommand(pub Key, pub Increment);
 
+#[derive(Debug, Clone)]
+pub struct GetRangeCommand(pub Key, pub usize, pub usize);
+
+#[derive(Debug, Clone)]
+pub struct SetRangeCommand(pub Key, pub usize, pub KVDBValue);
+
+#[derive(Debug, Clone)]
+pub struct AppendCommand(pub Key, pub KVDBValue);
+
+#[derive(Debug, Clone)]
+pub struct StrLenCommand(pub Key);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct EnableHistoryCommand(pub Key, pub crate::minikvdb::kvdb_history::HistoryRetention);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct DisableHistoryCommand(pub Key);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct VersionsCommand(pub Key);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct GetAsOfCommand(pub Key, pub std::time::SystemTime);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct RestoreCommand(pub Key, pub std::time::SystemTime);
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Increment {
     Int(i32),
@@ -20,6 +52,69 @@ pub enum Increment {
     Long(i64),
     #[cfg(feature = "big-types")]
     Double(f64),
+    #[cfg(feature = "big-types")]
+    UInt(u32),
+    #[cfg(feature = "big-types")]
+    ULong(u64),
+    #[cfg(feature = "big-types")]
+    Int128(i128),
+    #[cfg(feature = "big-types")]
+    UInt128(u128),
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal),
+    #[cfg(feature = "duration")]
+    Duration(std::time::Duration),
+}
+
+impl Increment {
+    /// Integer amount of the increment, floating point values are truncated.
+    pub(crate) fn as_i128(self) -> Option<i128> {
+        match self {
+            Increment::Int(v) => Some(v.into()),
+            Increment::Float(v) => Some(v as i128),
+            #[cfg(feature = "big-types")]
+            Increment::Long(v) => Some(v.into()),
+            #[cfg(feature = "big-types")]
+            Increment::Double(v) => Some(v as i128),
+            #[cfg(feature = "big-types")]
+            Increment::UInt(v) => Some(v.into()),
+            #[cfg(feature = "big-types")]
+            Increment::ULong(v) => Some(v.into()),
+            #[cfg(feature = "big-types")]
+            Increment::Int128(v) => Some(v),
+            #[cfg(feature = "big-types")]
+            Increment::UInt128(v) => v.try_into().ok(),
+            #[cfg(feature = "decimal")]
+            Increment::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_i128(&v.trunc()),
+            #[cfg(feature = "duration")]
+            Increment::Duration(_) => None,
+        }
+    }
+
+    #[cfg(feature = "decimal")]
+    pub(crate) fn as_decimal(self) -> Option<rust_decimal::Decimal> {
+        use rust_decimal::prelude::FromPrimitive;
+        match self {
+            Increment::Decimal(v) => Some(v),
+            Increment::Float(v) => rust_decimal::Decimal::from_f32(v),
+            #[cfg(feature = "big-types")]
+            Increment::Double(v) => rust_decimal::Decimal::from_f64(v),
+            v => v.as_i128().and_then(rust_decimal::Decimal::from_i128),
+        }
+    }
+
+    pub(crate) fn as_f64(self) -> Option<f64> {
+        match self {
+            Increment::Float(v) => Some(v.into()),
+            #[cfg(feature = "big-types")]
+            Increment::Double(v) => Some(v),
+            #[cfg(feature = "decimal")]
+            Increment::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_f64(&v),
+            #[cfg(feature = "duration")]
+            Increment::Duration(_) => None,
+            v => v.as_i128().map(|v| v as f64),
+        }
+    }
 }
 
 impl From<f32> for Increment {
@@ -47,6 +142,40 @@ mod big_types {
             Self::Double(value)
         }
     }
+    impl From<u32> for Increment {
+        fn from(value: u32) -> Self {
+            Self::UInt(value)
+        }
+    }
+    impl From<u64> for Increment {
+        fn from(value: u64) -> Self {
+            Self::ULong(value)
+        }
+    }
+    impl From<i128> for Increment {
+        fn from(value: i128) -> Self {
+            Self::Int128(value)
+        }
+    }
+    impl From<u128> for Increment {
+        fn from(value: u128) -> Self {
+            Self::UInt128(value)
+        }
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl From<rust_decimal::Decimal> for Increment {
+    fn from(value: rust_decimal::Decimal) -> Self {
+        Self::Decimal(value)
+    }
+}
+
+#[cfg(feature = "duration")]
+impl From<std::time::Duration> for Increment {
+    fn from(value: std::time::Duration) -> Self {
+        Self::Duration(value)
+    }
 }
 
 impl From<Increment> for KVDBValue {
@@ -58,6 +187,18 @@ impl From<Increment> for KVDBValue {
             Increment::Long(v) => KVDBValue::Long(v),
             #[cfg(feature = "big-types")]
             Increment::Double(v) => KVDBValue::Double(v),
+            #[cfg(feature = "big-types")]
+            Increment::UInt(v) => KVDBValue::UInt(v),
+            #[cfg(feature = "big-types")]
+            Increment::ULong(v) => KVDBValue::ULong(v),
+            #[cfg(feature = "big-types")]
+            Increment::Int128(v) => KVDBValue::Int128(v),
+            #[cfg(feature = "big-types")]
+            Increment::UInt128(v) => KVDBValue::UInt128(v),
+            #[cfg(feature = "decimal")]
+            Increment::Decimal(v) => KVDBValue::Decimal(v),
+            #[cfg(feature = "duration")]
+            Increment::Duration(v) => KVDBValue::Duration(v),
         }
     }
 }
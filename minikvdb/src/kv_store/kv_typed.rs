@@ -0,0 +1,97 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use crate::{
+    error::{MiniKVDBError, Result},
+    minikvdb::{kvdb_key::Key, kvdb_value::KVDBValue, MiniKVDB},
+};
+
+use super::kv_command::Increment;
+
+/// Key holding a single value of type `T`.
+#[derive(Debug)]
+pub struct TypedKey<T> {
+    key: Key,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for TypedKey<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+}
+
+impl<T> TypedKey<T> {
+    pub fn new(key: impl Into<Key>) -> Self {
+        Self {
+            key: key.into(),
+            _type: PhantomData,
+        }
+    }
+
+    /// Handle for a nested namespace, e.g. `counter` -> `counter:42`.
+    pub fn ns(&self, value: impl Display) -> Self {
+        Self::new(format!("{}:{value}", self.key.0))
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+impl<T> TypedKey<T>
+where
+    T: Into<KVDBValue> + TryFrom<KVDBValue, Error = MiniKVDBError>,
+{
+    pub fn get(&self, db: &MiniKVDB) -> Result<Option<T>> {
+        db.get_as(self.key.clone())
+    }
+
+    pub fn set(&self, db: &MiniKVDB, value: T) -> Result<()> {
+        db.set(self.key.clone(), value)?;
+        Ok(())
+    }
+
+    pub fn del(&self, db: &MiniKVDB) -> Result<bool> {
+        Ok(db.del(self.key.clone())?.is_some())
+    }
+}
+
+impl<T> TypedKey<T>
+where
+    T: Into<Increment> + TryFrom<KVDBValue, Error = MiniKVDBError>,
+{
+    pub fn increment(&self, db: &MiniKVDB, value: T) -> Result<T> {
+        let inc = db.increment(self.key.clone(), value)?;
+        db.coercion.convert(inc.into())
+    }
+}
+
+impl<T> From<Key> for TypedKey<T> {
+    fn from(value: Key) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::minikvdb::namespaced_key::NamespacedKey;
+
+    use super::*;
+
+    #[test]
+    fn reads_and_writes_typed_values() {
+        let db = MiniKVDB::default();
+        let visits: TypedKey<i32> = TypedKey::new(NamespacedKey::new("visits"));
+        let page = visits.ns("home");
+
+        assert_eq!(page.get(&db).unwrap(), None);
+        page.set(&db, 3).unwrap();
+        assert_eq!(page.increment(&db, 2).unwrap(), 5);
+        assert_eq!(page.get(&db).unwrap(), Some(5));
+        assert_eq!(page.key(), &Key::from("visits:home"));
+
+        let _ = db.set("visits:home", "five");
+        assert!(page.get(&db).is_err());
+        assert!(page.del(&db).unwrap());
+    }
+}
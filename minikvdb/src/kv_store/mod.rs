@@ -1,68 +1,368 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-
-macro_rules! impl_increment {
-    ($val:ident, $cmd_val:ident, $in:ident, $cast:ty) => {{
-        *$val += match $cmd_val {
-            Increment::Int(v) => v as $cast,
-            Increment::Float(v) => v as $cast,
-            #[cfg(feature = "big-types")]
-            Increment::Long(v) => v as $cast,
-            #[cfg(feature = "big-types")]
-            Increment::Double(v) => v as $cast,
-        };
-
-        Ok(Increment::$in(*$val))
-    }};
-}
+use serde::{Deserialize, Serialize, Serializer};
+#[cfg(feature = "history")]
+use std::time::SystemTime;
 
+#[cfg(feature = "persistent")]
+use crate::minikvdb::kvdb_snapshot::{scan, shard, MiniKVDBSnapshot};
 use crate::{
     error::{MiniKVDBError, Result},
-    minikvdb::{kvdb_key::Key, kvdb_value::KVDBValue, KVDBStore, MiniKVDB},
+    minikvdb::{
+        kvdb_key::Key,
+        kvdb_lock::{Journal, Journaled},
+        kvdb_shard::{ShardEntries, ShardedStore},
+        kvdb_value::KVDBValue,
+        KVDBStore, MiniKVDB,
+    },
 };
 
-use self::kv_command::{DeleteCommand, GetCommand, Increment, IncrementCommand, SetCommand};
+#[cfg(feature = "history")]
+use crate::minikvdb::kvdb_history::{History, HistoryRetention, Version};
+
+use self::kv_command::{
+    AppendCommand, DeleteCommand, GetCommand, GetRangeCommand, Increment, IncrementCommand,
+    SetCommand, SetRangeCommand, StrLenCommand,
+};
+#[cfg(feature = "history")]
+use self::kv_command::{
+    DisableHistoryCommand, EnableHistoryCommand, GetAsOfCommand, RestoreCommand, VersionsCommand,
+};
 
 pub mod kv_command;
+pub mod kv_typed;
+
+/// Values by key. With the `persistent` feature shards are persistent maps, which are
+/// copied without cloning their values when written while shared with a snapshot.
+#[cfg(not(feature = "persistent"))]
+type Values = std::collections::HashMap<Key, KVDBValue>;
+#[cfg(feature = "persistent")]
+type Values = imbl::HashMap<Key, KVDBValue>;
+
+/// Largest value `set_range` can grow a value to, 512 MiB.
+pub const MAX_VALUE_LEN: usize = 512 * 1024 * 1024;
+
+/// Adds the increment to an integer value, failing instead of overflowing.
+fn add_int<T>(val: &mut T, inc: Increment) -> Result<T>
+where
+    T: Copy + Into<i128> + TryFrom<i128>,
+{
+    let sum = inc.as_i128().and_then(|inc| (*val).into().checked_add(inc));
+    *val = sum
+        .and_then(|sum| T::try_from(sum).ok())
+        .ok_or(MiniKVDBError::CannotIncrement)?;
+    Ok(*val)
+}
+
+fn add_float(inc: Increment) -> Result<f64> {
+    inc.as_f64().ok_or(MiniKVDBError::CannotIncrement)
+}
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct KVStore(HashMap<Key, KVDBValue>);
+#[serde(transparent)]
+pub struct KVStore {
+    data: Values,
+    #[cfg(feature = "history")]
+    #[serde(skip)]
+    history: History<KVDBValue>,
+    #[serde(skip)]
+    journal: Journal<(Key, Option<KVDBValue>)>,
+}
 
 impl KVDBStore for KVStore {}
 
+impl ShardedStore for KVStore {
+    fn empty_like(&self) -> Self {
+        Self {
+            data: Values::new(),
+            #[cfg(feature = "history")]
+            history: self.history.empty_like(),
+            journal: Journal::default(),
+        }
+    }
+
+    fn split_into(self, shards: &mut [Self], shard: impl Fn(&Key) -> usize) {
+        #[cfg(feature = "history")]
+        {
+            let histories = self.history.split(shards.len(), &shard);
+            for (store, history) in shards.iter_mut().zip(histories) {
+                store.history.merge(history);
+            }
+        }
+        for (k, v) in self.data {
+            shards[shard(&k)].data.insert(k, v);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.data.extend(other.data);
+        #[cfg(feature = "history")]
+        self.history.merge(other.history);
+    }
+
+    fn key_count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn has_key(&self, key: &Key) -> bool {
+        self.data.contains_key(key)
+    }
+
+    fn move_entry(&mut self, key: &Key, other: &mut Self) -> Result<()> {
+        if let Some(value) = self.delete(DeleteCommand(key.clone())) {
+            other.set(SetCommand(key.clone(), value));
+        }
+        Ok(())
+    }
+
+    fn serialize_shards<S: Serializer>(
+        shards: &[&Self],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        ShardEntries(shards.iter().map(|s| &s.data).collect()).serialize(serializer)
+    }
+}
+
+impl Journaled for KVStore {
+    fn set_journaling(&mut self, enabled: bool) {
+        self.journal.set_enabled(enabled);
+        #[cfg(feature = "history")]
+        self.history.set_journaling(enabled);
+    }
+
+    /// Previous values are written back directly, the versions recorded by the write
+    /// are dropped.
+    fn rollback(&mut self) {
+        for (k, old) in self.journal.take() {
+            match old {
+                Some(v) => self.data.insert(k, v),
+                None => self.data.remove(&k),
+            };
+        }
+        #[cfg(feature = "history")]
+        self.history.rollback();
+    }
+}
+
 impl KVStore {
+    /// Records the current value of the key in the undo journal.
+    fn record_undo(&mut self, k: &Key) {
+        let data = &self.data;
+        self.journal.record(|| (k.clone(), data.get(k).cloned()));
+    }
+
     pub fn set(&mut self, cmd: impl Into<SetCommand>) -> Option<KVDBValue> {
         let SetCommand(k, v) = cmd.into();
-        self.0.insert(k, v)
+        self.record_undo(&k);
+        #[cfg(feature = "history")]
+        self.history.record(&k, Some(&v));
+        self.data.insert(k, v)
     }
 
     pub fn get(&self, cmd: impl Into<GetCommand>) -> Option<KVDBValue> {
         let GetCommand(k) = cmd.into();
-        self.0.get(&k).cloned()
+        self.data.get(&k).cloned()
     }
 
     pub fn delete(&mut self, cmd: impl Into<DeleteCommand>) -> Option<KVDBValue> {
         let DeleteCommand(k) = cmd.into();
-        self.0.remove(&k)
+        self.record_undo(&k);
+        let old = self.data.remove(&k);
+        #[cfg(feature = "history")]
+        if old.is_some() {
+            self.history.record(&k, None);
+        }
+        old
     }
 
     pub fn increment(&mut self, cmd: impl Into<IncrementCommand>) -> Result<Increment> {
         let IncrementCommand(k, v) = cmd.into();
-        if let Some(value) = self.0.get_mut(&k) {
+        self.record_undo(&k);
+        let incremented = self.add(&k, v)?;
+        #[cfg(feature = "history")]
+        self.history.record(&k, self.data.get(&k));
+        Ok(incremented)
+    }
+
+    fn add(&mut self, k: &Key, v: Increment) -> Result<Increment> {
+        if let Some(value) = self.data.get_mut(k) {
             match value {
-                KVDBValue::Int(val) => impl_increment!(val, v, Int, i32),
-                KVDBValue::Float(val) => impl_increment!(val, v, Float, f32),
+                KVDBValue::Int(val) => add_int(val, v).map(Increment::Int),
+                KVDBValue::Float(val) => add_float(v).map(|v| {
+                    *val += v as f32;
+                    Increment::Float(*val)
+                }),
+                #[cfg(feature = "big-types")]
+                KVDBValue::Long(val) => add_int(val, v).map(Increment::Long),
+                #[cfg(feature = "big-types")]
+                KVDBValue::Double(val) => add_float(v).map(|v| {
+                    *val += v;
+                    Increment::Double(*val)
+                }),
                 #[cfg(feature = "big-types")]
-                KVDBValue::Long(val) => impl_increment!(val, v, Long, i64),
+                KVDBValue::UInt(val) => add_int(val, v).map(Increment::UInt),
                 #[cfg(feature = "big-types")]
-                KVDBValue::Double(val) => impl_increment!(val, v, Double, f64),
+                KVDBValue::ULong(val) => add_int(val, v).map(Increment::ULong),
+                #[cfg(feature = "big-types")]
+                KVDBValue::Int128(val) => add_int(val, v).map(Increment::Int128),
+                #[cfg(feature = "big-types")]
+                KVDBValue::UInt128(val) => {
+                    let sum = match v {
+                        Increment::UInt128(v) => val.checked_add(v),
+                        v => v.as_i128().and_then(|v| val.checked_add_signed(v)),
+                    };
+                    *val = sum.ok_or(MiniKVDBError::CannotIncrement)?;
+                    Ok(Increment::UInt128(*val))
+                }
+                #[cfg(feature = "decimal")]
+                KVDBValue::Decimal(val) => {
+                    let sum = v.as_decimal().and_then(|v| val.checked_add(v));
+                    *val = sum.ok_or(MiniKVDBError::CannotIncrement)?;
+                    Ok(Increment::Decimal(*val))
+                }
+                #[cfg(feature = "duration")]
+                KVDBValue::Duration(val) => {
+                    let sum = match v {
+                        Increment::Duration(v) => val.checked_add(v),
+                        _ => None,
+                    };
+                    *val = sum.ok_or(MiniKVDBError::CannotIncrement)?;
+                    Ok(Increment::Duration(*val))
+                }
                 _ => Err(MiniKVDBError::CannotIncrement),
             }
         } else {
-            self.0.insert(k.to_owned(), v.into());
+            self.data.insert(k.clone(), v.into());
             Ok(v)
         }
     }
+
+    pub fn get_range(&self, cmd: impl Into<GetRangeCommand>) -> Result<Option<KVDBValue>> {
+        let GetRangeCommand(k, start, len) = cmd.into();
+        let Some(value) = self.data.get(&k) else {
+            return Ok(None);
+        };
+        let bytes = binary(value)?;
+        let start = start.min(bytes.len());
+        let end = start.saturating_add(len).min(bytes.len());
+        rebuild(value, bytes[start..end].to_vec()).map(Some)
+    }
+
+    /// Overwrites the value starting at `offset`, padding with zero bytes if needed.
+    /// Returns the new length. Fails with `ValueTooLarge` past `MAX_VALUE_LEN`.
+    pub fn set_range(&mut self, cmd: impl Into<SetRangeCommand>) -> Result<usize> {
+        let SetRangeCommand(k, offset, v) = cmd.into();
+        let patch = binary(&v)?;
+        let end = offset
+            .checked_add(patch.len())
+            .ok_or(MiniKVDBError::ValueTooLarge(usize::MAX))?;
+        if end > MAX_VALUE_LEN {
+            return Err(MiniKVDBError::ValueTooLarge(end));
+        }
+        let current = self.data.get(&k).unwrap_or(&v);
+        let mut bytes = match self.data.get(&k) {
+            Some(old) => binary(old)?.to_vec(),
+            None => vec![],
+        };
+
+        if bytes.len() < end {
+            bytes.resize(end, 0);
+        }
+        bytes[offset..end].copy_from_slice(patch);
+
+        let len = bytes.len();
+        let value = rebuild(current, bytes)?;
+        self.record_undo(&k);
+        #[cfg(feature = "history")]
+        self.history.record(&k, Some(&value));
+        self.data.insert(k, value);
+        Ok(len)
+    }
+
+    pub fn append(&mut self, cmd: impl Into<AppendCommand>) -> Result<usize> {
+        let AppendCommand(k, v) = cmd.into();
+        let tail = binary(&v)?;
+        let Some(current) = self.data.get(&k) else {
+            let len = tail.len();
+            self.record_undo(&k);
+            #[cfg(feature = "history")]
+            self.history.record(&k, Some(&v));
+            self.data.insert(k, v);
+            return Ok(len);
+        };
+
+        let mut bytes = binary(current)?.to_vec();
+        bytes.extend_from_slice(tail);
+        let len = bytes.len();
+        let value = rebuild(current, bytes)?;
+        self.record_undo(&k);
+        #[cfg(feature = "history")]
+        self.history.record(&k, Some(&value));
+        self.data.insert(k, value);
+        Ok(len)
+    }
+
+    pub fn strlen(&self, cmd: impl Into<StrLenCommand>) -> Result<usize> {
+        let StrLenCommand(k) = cmd.into();
+        match self.data.get(&k) {
+            Some(value) => Ok(binary(value)?.len()),
+            None => Ok(0),
+        }
+    }
+
+    /// Keeps versions of the keys under the prefix, starting with their current values.
+    #[cfg(feature = "history")]
+    pub fn enable_history(&mut self, cmd: impl Into<EnableHistoryCommand>) {
+        let EnableHistoryCommand(prefix, retention) = cmd.into();
+        self.history.enable(prefix, retention, self.data.iter());
+    }
+
+    #[cfg(feature = "history")]
+    pub fn disable_history(&mut self, cmd: impl Into<DisableHistoryCommand>) -> bool {
+        let DisableHistoryCommand(prefix) = cmd.into();
+        self.history.disable(&prefix)
+    }
+
+    #[cfg(feature = "history")]
+    pub fn versions(&self, cmd: impl Into<VersionsCommand>) -> Vec<Version<KVDBValue>> {
+        let VersionsCommand(k) = cmd.into();
+        self.history.versions(&k)
+    }
+
+    #[cfg(feature = "history")]
+    pub fn get_as_of(&self, cmd: impl Into<GetAsOfCommand>) -> Result<Option<KVDBValue>> {
+        let GetAsOfCommand(k, at) = cmd.into();
+        self.history.as_of(&k, at)
+    }
+
+    /// Sets the key to its value at the time, deleting it if it did not exist then. The
+    /// restored value is recorded as a new version.
+    #[cfg(feature = "history")]
+    pub fn restore(&mut self, cmd: impl Into<RestoreCommand>) -> Result<Option<KVDBValue>> {
+        let RestoreCommand(k, at) = cmd.into();
+        let value = self.history.as_of(&k, at)?;
+        match &value {
+            Some(v) => self.set(SetCommand(k, v.clone())),
+            None => self.delete(DeleteCommand(k)),
+        };
+        Ok(value)
+    }
+}
+
+fn binary(value: &KVDBValue) -> Result<&[u8]> {
+    match value {
+        KVDBValue::String(v) => Ok(v.as_bytes()),
+        KVDBValue::Bytes(v) => Ok(v),
+        v => Err(MiniKVDBError::wrong_type(None, v)),
+    }
+}
+
+/// Builds a value of the same type as `like` from raw bytes.
+fn rebuild(like: &KVDBValue, bytes: Vec<u8>) -> Result<KVDBValue> {
+    match like {
+        KVDBValue::String(_) => String::from_utf8(bytes)
+            .map(KVDBValue::String)
+            .map_err(|_| MiniKVDBError::InvalidUtf8),
+        _ => Ok(KVDBValue::Bytes(bytes)),
+    }
 }
 
 // Key-Value store.
@@ -72,21 +372,164 @@ impl MiniKVDB {
         key: impl Into<Key>,
         value: impl Into<KVDBValue>,
     ) -> Result<Option<KVDBValue>> {
-        Ok(self.kv.write()?.set(SetCommand(key.into(), value.into())))
+        let key = key.into();
+        Ok(self
+            .kv
+            .shard(&key)
+            .write()?
+            .set(SetCommand(key, value.into())))
     }
 
     pub fn get(&self, key: impl Into<Key>) -> Result<Option<KVDBValue>> {
-        Ok(self.kv.read()?.get(GetCommand(key.into())))
+        let key = key.into();
+        Ok(self.kv.shard(&key).read()?.get(GetCommand(key)))
+    }
+
+    pub fn get_as<T>(&self, key: impl Into<Key>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let key = key.into();
+        self.convert(&key, self.get(key.clone())?)
     }
 
     pub fn del(&self, key: impl Into<Key>) -> Result<Option<KVDBValue>> {
-        Ok(self.kv.write()?.delete(DeleteCommand(key.into())))
+        let key = key.into();
+        Ok(self.kv.shard(&key).write()?.delete(DeleteCommand(key)))
     }
 
     pub fn increment(&self, key: impl Into<Key>, value: impl Into<Increment>) -> Result<Increment> {
+        let key = key.into();
+        self.kv
+            .shard(&key)
+            .write()?
+            .increment(IncrementCommand(key, value.into()))
+    }
+
+    pub fn get_range(
+        &self,
+        key: impl Into<Key>,
+        start: usize,
+        len: usize,
+    ) -> Result<Option<KVDBValue>> {
+        let key = key.into();
+        self.kv
+            .shard(&key)
+            .read()?
+            .get_range(GetRangeCommand(key, start, len))
+    }
+
+    pub fn set_range(
+        &self,
+        key: impl Into<Key>,
+        offset: usize,
+        value: impl Into<KVDBValue>,
+    ) -> Result<usize> {
+        let key = key.into();
+        self.kv
+            .shard(&key)
+            .write()?
+            .set_range(SetRangeCommand(key, offset, value.into()))
+    }
+
+    pub fn append(&self, key: impl Into<Key>, value: impl Into<KVDBValue>) -> Result<usize> {
+        let key = key.into();
         self.kv
+            .shard(&key)
             .write()?
-            .increment(IncrementCommand(key.into(), value.into()))
+            .append(AppendCommand(key, value.into()))
+    }
+
+    pub fn strlen(&self, key: impl Into<Key>) -> Result<usize> {
+        let key = key.into();
+        self.kv.shard(&key).read()?.strlen(StrLenCommand(key))
+    }
+
+    /// Keeps previous values of the keys under the prefix in memory, bounded by the
+    /// retention. Enabling a prefix again replaces its retention.
+    #[cfg(feature = "history")]
+    pub fn enable_history(
+        &self,
+        prefix: impl Into<Key>,
+        retention: HistoryRetention,
+    ) -> Result<()> {
+        let prefix = prefix.into();
+        for mut shard in self.kv.write_all()? {
+            shard.enable_history(EnableHistoryCommand(prefix.clone(), retention));
+        }
+        Ok(())
+    }
+
+    /// Drops the versions kept for the prefix, returns whether history was enabled.
+    #[cfg(feature = "history")]
+    pub fn disable_history(&self, prefix: impl Into<Key>) -> Result<bool> {
+        let prefix = prefix.into();
+        let mut disabled = false;
+        for mut shard in self.kv.write_all()? {
+            disabled |= shard.disable_history(DisableHistoryCommand(prefix.clone()));
+        }
+        Ok(disabled)
+    }
+
+    /// Versions of the value, oldest first and ending with the current one.
+    #[cfg(feature = "history")]
+    pub fn versions(&self, key: impl Into<Key>) -> Result<Vec<Version<KVDBValue>>> {
+        let key = key.into();
+        Ok(self.kv.shard(&key).read()?.versions(VersionsCommand(key)))
+    }
+
+    #[cfg(feature = "history")]
+    pub fn get_as_of(&self, key: impl Into<Key>, at: SystemTime) -> Result<Option<KVDBValue>> {
+        let key = key.into();
+        self.kv
+            .shard(&key)
+            .read()?
+            .get_as_of(GetAsOfCommand(key, at))
+    }
+
+    #[cfg(feature = "history")]
+    pub fn restore(&self, key: impl Into<Key>, at: SystemTime) -> Result<Option<KVDBValue>> {
+        let key = key.into();
+        self.kv
+            .shard(&key)
+            .write()?
+            .restore(RestoreCommand(key, at))
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl MiniKVDBSnapshot {
+    pub fn get(&self, key: impl Into<Key>) -> Option<KVDBValue> {
+        let key = key.into();
+        shard(&self.kv, &key).get(GetCommand(key))
+    }
+
+    pub fn get_as<T>(&self, key: impl Into<Key>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let key = key.into();
+        self.coercion.convert_stored(&key, self.get(key.clone()))
+    }
+
+    pub fn get_range(
+        &self,
+        key: impl Into<Key>,
+        start: usize,
+        len: usize,
+    ) -> Result<Option<KVDBValue>> {
+        let key = key.into();
+        shard(&self.kv, &key).get_range(GetRangeCommand(key, start, len))
+    }
+
+    pub fn strlen(&self, key: impl Into<Key>) -> Result<usize> {
+        let key = key.into();
+        shard(&self.kv, &key).strlen(StrLenCommand(key))
+    }
+
+    /// Sorted keys of the key-value store starting with the prefix.
+    pub fn keys(&self, prefix: impl Into<Key>) -> Vec<Key> {
+        scan(self.kv.iter().flat_map(|s| s.data.keys()), &prefix.into())
     }
 }
 
@@ -111,7 +554,7 @@ mod tests {
 
         assert_eq!(ins, None);
         assert_eq!(
-            *db.0.get("name").unwrap(),
+            *db.data.get("name").unwrap(),
             KVDBValue::String("bob".to_string())
         );
     }
@@ -192,4 +635,201 @@ mod tests {
         assert_eq!(inc_float_with_float, Increment::Float(14.9));
         assert_eq!(inc_float_with_int, Increment::Float(19.9));
     }
+
+    #[cfg(feature = "big-types")]
+    #[test]
+    fn increments_unsigned_values_without_overflow() {
+        let mut db = test_db();
+        let _ = db.set(SetCommand("a".into(), 5u64.into()));
+
+        let dec = db.increment(IncrementCommand("a".into(), (-3).into()));
+        assert_eq!(dec.unwrap(), Increment::ULong(2));
+
+        let underflow = db.increment(IncrementCommand("a".into(), (-3).into()));
+        assert!(underflow.is_err());
+        assert_eq!(db.get(GetCommand("a".into())), Some(KVDBValue::ULong(2)));
+
+        let _ = db.set(SetCommand("b".into(), u128::MAX.into()));
+        assert!(db
+            .increment(IncrementCommand("b".into(), 1.into()))
+            .is_err());
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn increments_decimal_values_exactly() {
+        use rust_decimal::Decimal;
+
+        let mut db = test_db();
+        let _ = db.set(SetCommand("price".into(), Decimal::new(1010, 2).into()));
+
+        let inc = db
+            .increment(IncrementCommand("price".into(), Decimal::new(20, 2).into()))
+            .unwrap();
+        assert_eq!(inc, Increment::Decimal(Decimal::new(1030, 2)));
+
+        let inc = db
+            .increment(IncrementCommand("price".into(), 1.into()))
+            .unwrap();
+        assert_eq!(inc, Increment::Decimal(Decimal::new(1130, 2)));
+    }
+
+    #[cfg(feature = "duration")]
+    #[test]
+    fn increments_durations_only_with_durations() {
+        use std::time::Duration;
+
+        let mut db = test_db();
+        let _ = db.set(SetCommand("ttl".into(), Duration::from_secs(5).into()));
+
+        let inc = db
+            .increment(IncrementCommand(
+                "ttl".into(),
+                Duration::from_millis(500).into(),
+            ))
+            .unwrap();
+        assert_eq!(inc, Increment::Duration(Duration::from_millis(5500)));
+        assert!(db
+            .increment(IncrementCommand("ttl".into(), 1.into()))
+            .is_err());
+    }
+
+    #[test]
+    fn stores_binary_values() {
+        let mut db = test_db();
+        let _ = db.set(SetCommand("img".into(), vec![0u8, 159, 146, 150].into()));
+
+        let value = db.get(GetCommand("img".into())).unwrap();
+        assert_eq!(value, KVDBValue::Bytes(vec![0, 159, 146, 150]));
+        assert_eq!(Vec::<u8>::try_from(value).unwrap(), vec![0, 159, 146, 150]);
+    }
+
+    #[test]
+    fn gets_range_of_string_and_bytes() {
+        let mut db = test_db();
+        let _ = db.set(SetCommand("s".into(), "Hello World".into()));
+        let _ = db.set(SetCommand("b".into(), vec![1u8, 2, 3, 4].into()));
+
+        let s = db.get_range(GetRangeCommand("s".into(), 6, 100)).unwrap();
+        let b = db.get_range(GetRangeCommand("b".into(), 1, 2)).unwrap();
+        let missing = db.get_range(GetRangeCommand("x".into(), 0, 1)).unwrap();
+
+        assert_eq!(s, Some("World".into()));
+        assert_eq!(b, Some(vec![2u8, 3].into()));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn sets_range_with_padding() {
+        let mut db = test_db();
+        let _ = db.set(SetCommand("s".into(), "Hello World".into()));
+
+        assert_eq!(
+            db.set_range(SetRangeCommand("s".into(), 6, "Redis".into()))
+                .unwrap(),
+            11
+        );
+        assert_eq!(db.get(GetCommand("s".into())), Some("Hello Redis".into()));
+
+        assert_eq!(
+            db.set_range(SetRangeCommand("b".into(), 2, vec![7u8].into()))
+                .unwrap(),
+            3
+        );
+        assert_eq!(db.get(GetCommand("b".into())), Some(vec![0u8, 0, 7].into()));
+
+        for offset in [MAX_VALUE_LEN, usize::MAX] {
+            let res = db.set_range(SetRangeCommand("b".into(), offset, vec![1u8].into()));
+            assert!(matches!(res, Err(MiniKVDBError::ValueTooLarge(_))));
+        }
+        assert_eq!(db.get(GetCommand("b".into())), Some(vec![0u8, 0, 7].into()));
+    }
+
+    #[test]
+    fn appends_and_measures_length() {
+        let mut db = test_db();
+        assert_eq!(
+            db.append(AppendCommand("s".into(), "foo".into())).unwrap(),
+            3
+        );
+        assert_eq!(
+            db.append(AppendCommand("s".into(), "bar".into())).unwrap(),
+            6
+        );
+        assert_eq!(db.strlen(StrLenCommand("s".into())).unwrap(), 6);
+        assert_eq!(db.strlen(StrLenCommand("missing".into())).unwrap(), 0);
+
+        let invalid = db.append(AppendCommand("s".into(), vec![0xffu8].into()));
+        assert!(invalid.is_err());
+        assert_eq!(db.get(GetCommand("s".into())), Some("foobar".into()));
+
+        let _ = db.set(SetCommand("n".into(), 1.into()));
+        assert!(db.strlen(StrLenCommand("n".into())).is_err());
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn reads_and_restores_previous_values() {
+        let db = MiniKVDB::with_shards(4);
+        db.set("cfg:a", 1).unwrap();
+        db.set("other", 1).unwrap();
+        db.enable_history("cfg:", HistoryRetention::default().max_versions(3))
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let before = SystemTime::now();
+        db.increment("cfg:a", 1).unwrap();
+        db.append("cfg:b", "x").unwrap();
+        db.del("cfg:a").unwrap();
+
+        assert_eq!(db.get_as_of("cfg:a", before).unwrap(), Some(1.into()));
+        assert_eq!(db.get_as_of("cfg:b", before).unwrap(), None);
+        assert!(db.get_as_of("other", before).is_err());
+        assert_eq!(db.versions("cfg:a").unwrap().len(), 3);
+
+        assert_eq!(db.restore("cfg:a", before).unwrap(), Some(1.into()));
+        assert_eq!(db.get("cfg:a").unwrap(), Some(1.into()));
+        assert_eq!(db.restore("cfg:b", before).unwrap(), None);
+        assert_eq!(db.get("cfg:b").unwrap(), None);
+        let values: Vec<_> = db
+            .versions("cfg:a")
+            .unwrap()
+            .into_iter()
+            .map(|v| v.value)
+            .collect();
+        assert_eq!(values, vec![Some(2.into()), None, Some(1.into())]);
+
+        assert!(db.disable_history("cfg:").unwrap());
+        assert!(db.versions("cfg:a").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn rollback_drops_versions_of_interrupted_write() {
+        use crate::prelude::PoisonPolicy;
+
+        let db = MiniKVDB::default();
+        db.set_poison_policy(PoisonPolicy::Rollback);
+        db.enable_history("cfg:", HistoryRetention::default())
+            .unwrap();
+        db.set("cfg:a", 1).unwrap();
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut store = db.kv.shards()[0].write().unwrap();
+            store.set(SetCommand("cfg:a".into(), 2.into()));
+            store.set(SetCommand("cfg:b".into(), 3.into()));
+            store.delete(DeleteCommand("cfg:a".into()));
+            panic!("interrupted write");
+        }));
+        db.clear_poison();
+
+        assert_eq!(db.get("cfg:a").unwrap(), Some(1.into()));
+        assert_eq!(db.get("cfg:b").unwrap(), None);
+        let values: Vec<_> = db
+            .versions("cfg:a")
+            .unwrap()
+            .into_iter()
+            .map(|v| v.value)
+            .collect();
+        assert_eq!(values, vec![Some(1.into())]);
+        assert!(db.versions("cfg:b").unwrap().is_empty());
+    }
 }
@@ -4,6 +4,8 @@ pub mod kv_store;
 pub mod list_store;
 #[cfg(feature = "map")]
 pub mod map_store;
+#[cfg(feature = "vector")]
+pub mod vector_store;
 
 pub mod error;
 pub mod minikvdb;
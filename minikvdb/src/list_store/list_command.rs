@@ -106,6 +106,20 @@ where
     }
 }
 
+/// Moves the last value of the first list to the front of the second.
+#[derive(Debug, Clone)]
+pub struct ListMoveCommand(pub Key, pub Key);
+
+impl<F, T> From<(F, T)> for ListMoveCommand
+where
+    F: Into<Key>,
+    T: Into<Key>,
+{
+    fn from(value: (F, T)) -> Self {
+        Self(value.0.into(), value.1.into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,116 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use crate::{
+    error::{MiniKVDBError, Result},
+    minikvdb::{kvdb_key::Key, kvdb_value::KVDBValue, MiniKVDB},
+};
+
+use super::list_command::{ListRangeCommand, ListRangeOption, ListRemoveCommand, ListRemoveOption};
+
+/// Key holding a list of values of type `T`.
+#[derive(Debug)]
+pub struct TypedList<T> {
+    key: Key,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for TypedList<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+}
+
+impl<T> TypedList<T> {
+    pub fn new(key: impl Into<Key>) -> Self {
+        Self {
+            key: key.into(),
+            _type: PhantomData,
+        }
+    }
+
+    /// Handle for a nested namespace, e.g. `queue` -> `queue:emails`.
+    pub fn ns(&self, value: impl Display) -> Self {
+        Self::new(format!("{}:{value}", self.key.0))
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+impl<T> TypedList<T>
+where
+    T: Into<KVDBValue> + TryFrom<KVDBValue, Error = MiniKVDBError>,
+{
+    pub fn push_front(&self, db: &MiniKVDB, values: impl IntoIterator<Item = T>) -> Result<usize> {
+        db.push_front(self.key.clone(), Self::values(values))
+    }
+
+    pub fn push_back(&self, db: &MiniKVDB, values: impl IntoIterator<Item = T>) -> Result<usize> {
+        db.push_back(self.key.clone(), Self::values(values))
+    }
+
+    pub fn pop_front(&self, db: &MiniKVDB) -> Result<Option<T>> {
+        db.pop_front_as(self.key.clone())
+    }
+
+    pub fn pop_back(&self, db: &MiniKVDB) -> Result<Option<T>> {
+        db.pop_back_as(self.key.clone())
+    }
+
+    pub fn range(&self, db: &MiniKVDB, opts: ListRangeOption) -> Result<Option<Vec<T>>> {
+        db.list_range_as(ListRangeCommand(self.key.clone(), opts))
+    }
+
+    pub fn len(&self, db: &MiniKVDB) -> Result<Option<usize>> {
+        db.list_len(self.key.clone())
+    }
+
+    pub fn contains(&self, db: &MiniKVDB, value: T) -> Result<bool> {
+        db.list_contains(self.key.clone(), value)
+    }
+
+    /// Removes up to `count` occurrences of the value, or all of them.
+    pub fn remove(&self, db: &MiniKVDB, value: T, count: Option<usize>) -> Result<usize> {
+        let opts = match count {
+            Some(count) => ListRemoveOption::Count(count, value.into()),
+            None => ListRemoveOption::All(value.into()),
+        };
+        db.list_remove(ListRemoveCommand(self.key.clone(), opts))
+    }
+
+    fn values(values: impl IntoIterator<Item = T>) -> Vec<KVDBValue> {
+        values.into_iter().map(Into::into).collect()
+    }
+}
+
+impl<T> From<Key> for TypedList<T> {
+    fn from(value: Key) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushes_and_pops_typed_values() {
+        let db = MiniKVDB::default();
+        let queue: TypedList<String> = TypedList::new("queue").ns("emails");
+
+        queue
+            .push_back(&db, ["a".to_owned(), "b".to_owned(), "c".to_owned()])
+            .unwrap();
+        assert_eq!(queue.len(&db).unwrap(), Some(3));
+        assert!(queue.contains(&db, "b".to_owned()).unwrap());
+        assert_eq!(queue.pop_front(&db).unwrap(), Some("a".to_owned()));
+        assert_eq!(queue.pop_back(&db).unwrap(), Some("c".to_owned()));
+        assert_eq!(
+            queue.range(&db, ListRangeOption::Whole).unwrap(),
+            Some(vec!["b".to_owned()])
+        );
+        assert_eq!(queue.remove(&db, "b".to_owned(), None).unwrap(), 1);
+        assert_eq!(queue.len(&db).unwrap(), None);
+    }
+}
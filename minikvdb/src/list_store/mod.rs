@@ -1,26 +1,164 @@
-use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize, Serializer};
+use std::ops::Range;
 
+#[cfg(feature = "persistent")]
+use crate::minikvdb::kvdb_snapshot::{scan, shard, MiniKVDBSnapshot};
 use crate::{
-    error::Result,
-    minikvdb::{kvdb_key::Key, kvdb_value::KVDBValue, KVDBStore, MiniKVDB},
+    error::{MiniKVDBError, Result},
+    minikvdb::{
+        kvdb_key::Key,
+        kvdb_lock::{self, Journal, Journaled},
+        kvdb_shard::{ShardEntries, ShardedStore},
+        kvdb_value::KVDBValue,
+        KVDBStore, MiniKVDB,
+    },
 };
 
 use self::list_command::{
-    ListContainsValueCommand, ListLenCommmand, ListRangeCommand, ListRemoveCommand, PopBackCommand,
-    PopFrontCommand, PushBackCommand, PushFrontCommand,
+    ListContainsValueCommand, ListLenCommmand, ListMoveCommand, ListRangeCommand,
+    ListRemoveCommand, PopBackCommand, PopFrontCommand, PushBackCommand, PushFrontCommand,
 };
 
 pub mod list_command;
+pub mod list_typed;
+
+/// Values of a list. With the `persistent` feature lists are persistent vectors, which
+/// are copied and sliced without cloning their values.
+#[cfg(not(feature = "persistent"))]
+type List = std::collections::VecDeque<KVDBValue>;
+#[cfg(feature = "persistent")]
+type List = imbl::Vector<KVDBValue>;
+
+#[cfg(not(feature = "persistent"))]
+type Lists = std::collections::HashMap<Key, List>;
+#[cfg(feature = "persistent")]
+type Lists = imbl::HashMap<Key, List>;
+
+/// Lock of the list store, reads of `SnapshotLock` never block.
+#[cfg(not(feature = "persistent"))]
+pub type ListLock = kvdb_lock::StoreLock<ListStore>;
+#[cfg(feature = "persistent")]
+pub type ListLock = kvdb_lock::SnapshotLock<ListStore>;
+
+/// Values of a list range, shared with the store under `persistent`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListSlice(List);
+
+impl ListSlice {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&KVDBValue> {
+        self.0.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &KVDBValue> + '_ {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for ListSlice {
+    type Item = KVDBValue;
+    type IntoIter = ListSliceIter;
+
+    fn into_iter(self) -> ListSliceIter {
+        ListSliceIter(self.0.into_iter())
+    }
+}
+
+impl From<ListSlice> for Vec<KVDBValue> {
+    fn from(value: ListSlice) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+/// Owned values of a `ListSlice`.
+pub struct ListSliceIter(<List as IntoIterator>::IntoIter);
+
+impl Iterator for ListSliceIter {
+    type Item = KVDBValue;
+
+    fn next(&mut self) -> Option<KVDBValue> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct ListStore(HashMap<Key, VecDeque<KVDBValue>>);
+#[serde(transparent)]
+pub struct ListStore(Lists, #[serde(skip)] Journal<(Key, Option<List>)>);
 
 impl KVDBStore for ListStore {}
 
+impl ShardedStore for ListStore {
+    fn split_into(self, shards: &mut [Self], shard: impl Fn(&Key) -> usize) {
+        for (k, v) in self.0 {
+            shards[shard(&k)].0.insert(k, v);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.0.extend(other.0);
+    }
+
+    fn key_count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn has_key(&self, key: &Key) -> bool {
+        self.0.contains_key(key)
+    }
+
+    fn move_entry(&mut self, key: &Key, other: &mut Self) -> Result<()> {
+        self.record_undo(key);
+        other.record_undo(key);
+        if let Some(list) = self.0.remove(key) {
+            other.0.insert(key.clone(), list);
+        }
+        Ok(())
+    }
+
+    fn serialize_shards<S: Serializer>(
+        shards: &[&Self],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        ShardEntries(shards.iter().map(|s| &s.0).collect()).serialize(serializer)
+    }
+}
+
+impl Journaled for ListStore {
+    fn set_journaling(&mut self, enabled: bool) {
+        self.1.set_enabled(enabled);
+    }
+
+    fn rollback(&mut self) {
+        for (k, old) in self.1.take() {
+            match old {
+                Some(list) => self.0.insert(k, list),
+                None => self.0.remove(&k),
+            };
+        }
+    }
+}
+
 impl ListStore {
+    /// Records the current list of the key in the undo journal.
+    fn record_undo(&mut self, k: &Key) {
+        let lists = &self.0;
+        self.1.record(|| (k.clone(), lists.get(k).cloned()));
+    }
+
     pub fn push_front(&mut self, cmd: impl Into<PushFrontCommand>) -> usize {
         let PushFrontCommand(k, v) = cmd.into();
+        self.record_undo(&k);
         if let Some(list) = self.0.get_mut(&k) {
             for value in v {
                 list.push_front(value.to_owned());
@@ -39,6 +177,7 @@ impl ListStore {
 
     pub fn pop_front(&mut self, cmd: impl Into<PopFrontCommand>) -> Option<KVDBValue> {
         let PopFrontCommand(k) = cmd.into();
+        self.record_undo(&k);
         if let Some(list) = self.0.get_mut(&k) {
             let pop = list.pop_front();
             if list.is_empty() {
@@ -52,6 +191,7 @@ impl ListStore {
 
     pub fn push_back(&mut self, cmd: impl Into<PushBackCommand>) -> usize {
         let PushBackCommand(k, v) = cmd.into();
+        self.record_undo(&k);
         if let Some(list) = self.0.get_mut(&k) {
             for value in v {
                 list.push_back(value);
@@ -66,6 +206,7 @@ impl ListStore {
 
     pub fn pop_back(&mut self, cmd: impl Into<PopBackCommand>) -> Option<KVDBValue> {
         let PopBackCommand(k) = cmd.into();
+        self.record_undo(&k);
         if let Some(list) = self.0.get_mut(&k) {
             let pop = list.pop_back();
             if list.is_empty() {
@@ -77,34 +218,29 @@ impl ListStore {
         }
     }
 
+    /// Values in the range, only the values in the range are cloned.
     pub fn range(&self, cmd: impl Into<ListRangeCommand>) -> Option<Vec<KVDBValue>> {
         let ListRangeCommand(k, opts) = cmd.into();
-
-        if let Some(list) = self.0.get(&k) {
-            match opts {
-                list_command::ListRangeOption::Whole => Some(list.clone().into()),
-                list_command::ListRangeOption::FromIndex(start) => {
-                    if start < list.len() {
-                        let list: Vec<KVDBValue> = list.clone().into();
-                        let list = list[start..].to_vec();
-                        Some(list)
-                    } else {
-                        Some(vec![])
-                    }
-                }
-                list_command::ListRangeOption::FromIndexWithLen(start, count) => {
-                    if start < list.len() {
-                        let list: Vec<KVDBValue> = list.clone().into();
-                        let list = list[start..=(start + count).min(list.len() - 1)].to_vec();
-                        Some(list)
-                    } else {
-                        Some(vec![])
-                    }
-                }
-            }
-        } else {
-            None
-        }
+        let list = self.0.get(&k)?;
+        let range = bounds(list.len(), opts);
+        Some(
+            list.iter()
+                .skip(range.start)
+                .take(range.len())
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// The range as a list, sharing the values with the store under `persistent`.
+    pub fn slice(&self, cmd: impl Into<ListRangeCommand>) -> Option<ListSlice> {
+        let ListRangeCommand(k, opts) = cmd.into();
+        let list = self.0.get(&k)?;
+        let range = bounds(list.len(), opts);
+        #[cfg(feature = "persistent")]
+        return Some(ListSlice(list.skip(range.start).take(range.len())));
+        #[cfg(not(feature = "persistent"))]
+        Some(ListSlice(list.range(range).cloned().collect()))
     }
 
     pub fn len(&self, cmd: impl Into<ListLenCommmand>) -> Option<usize> {
@@ -114,6 +250,7 @@ impl ListStore {
 
     pub fn remove(&mut self, cmd: impl Into<ListRemoveCommand>) -> usize {
         let ListRemoveCommand(k, opts) = cmd.into();
+        self.record_undo(&k);
 
         match opts {
             list_command::ListRemoveOption::All(v) => {
@@ -172,20 +309,53 @@ impl ListStore {
     }
 }
 
+/// Indexes of the list selected by the option, `FromIndexWithLen` includes the value
+/// at `start + len`.
+fn bounds(len: usize, opts: list_command::ListRangeOption) -> Range<usize> {
+    let (start, end) = match opts {
+        list_command::ListRangeOption::Whole => (0, len),
+        list_command::ListRangeOption::FromIndex(start) => (start, len),
+        list_command::ListRangeOption::FromIndexWithLen(start, count) => (
+            start,
+            start.saturating_add(count).saturating_add(1).min(len),
+        ),
+    };
+    start.min(len)..end
+}
+
 impl MiniKVDB {
     pub fn push_front(
         &self,
         key: impl Into<Key>,
         values: impl Into<Vec<KVDBValue>>,
     ) -> Result<usize> {
+        let key = key.into();
         Ok(self
             .list
+            .shard(&key)
             .write()?
-            .push_front(PushFrontCommand(key.into(), values.into())))
+            .push_front(PushFrontCommand(key, values.into())))
     }
 
     pub fn pop_front(&self, cmd: impl Into<PopFrontCommand>) -> Result<Option<KVDBValue>> {
-        Ok(ListStore::pop_front(&mut *self.list.write()?, cmd))
+        let cmd = cmd.into();
+        Ok(ListStore::pop_front(
+            &mut *self.list.shard(&cmd.0).write()?,
+            cmd,
+        ))
+    }
+
+    /// Pops the first value only if it converts into `T`.
+    pub fn pop_front_as<T>(&self, cmd: impl Into<PopFrontCommand>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let cmd = cmd.into();
+        let mut list = self.list.shard(&cmd.0).write()?;
+        let front = list.0.get(&cmd.0).and_then(|l| l.front()).cloned();
+        let value = self.convert(&cmd.0, front)?;
+        list.pop_front(cmd);
+        Ok(value)
     }
 
     pub fn push_back(
@@ -193,33 +363,138 @@ impl MiniKVDB {
         key: impl Into<Key>,
         values: impl Into<Vec<KVDBValue>>,
     ) -> Result<usize> {
+        let key = key.into();
         Ok(self
             .list
+            .shard(&key)
             .write()?
-            .push_back(PushBackCommand(key.into(), values.into())))
+            .push_back(PushBackCommand(key, values.into())))
     }
 
     pub fn pop_back(&self, cmd: impl Into<PopBackCommand>) -> Result<Option<KVDBValue>> {
-        Ok(self.list.write()?.pop_back(cmd))
+        let cmd = cmd.into();
+        Ok(self.list.shard(&cmd.0).write()?.pop_back(cmd))
+    }
+
+    /// Pops the last value only if it converts into `T`.
+    pub fn pop_back_as<T>(&self, cmd: impl Into<PopBackCommand>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let cmd = cmd.into();
+        let mut list = self.list.shard(&cmd.0).write()?;
+        let back = list.0.get(&cmd.0).and_then(|l| l.back()).cloned();
+        let value = self.convert(&cmd.0, back)?;
+        list.pop_back(cmd);
+        Ok(value)
+    }
+
+    /// Atomically moves the last value of `from` to the front of `to`, returns the moved value.
+    pub fn list_move(&self, cmd: impl Into<ListMoveCommand>) -> Result<Option<KVDBValue>> {
+        let ListMoveCommand(from, to) = cmd.into();
+        let mut shards = self.list.write_keys([&from, &to])?;
+        let Some(value) = shards.store(&from).pop_back(PopBackCommand(from)) else {
+            return Ok(None);
+        };
+        shards
+            .store(&to)
+            .push_front(PushFrontCommand(to, vec![value.clone()]));
+        Ok(Some(value))
     }
 
     pub fn list_range(&self, cmd: impl Into<ListRangeCommand>) -> Result<Option<Vec<KVDBValue>>> {
-        Ok(self.list.read()?.range(cmd))
+        let cmd = cmd.into();
+        Ok(self.list.shard(&cmd.0).read()?.range(cmd))
+    }
+
+    pub fn list_slice(&self, cmd: impl Into<ListRangeCommand>) -> Result<Option<ListSlice>> {
+        let cmd = cmd.into();
+        Ok(self.list.shard(&cmd.0).read()?.slice(cmd))
+    }
+
+    pub fn list_range_as<T>(&self, cmd: impl Into<ListRangeCommand>) -> Result<Option<Vec<T>>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let cmd = cmd.into();
+        let key = cmd.0.clone();
+        self.list_range(cmd)?
+            .map(|l| {
+                l.into_iter()
+                    .map(|v| {
+                        self.coercion
+                            .convert(v)
+                            .map_err(|e| MiniKVDBError::invalid_value(key.0.clone(), e))
+                    })
+                    .collect()
+            })
+            .transpose()
     }
 
     pub fn list_len(&self, key: impl Into<Key>) -> Result<Option<usize>> {
-        Ok(self.list.read()?.len(ListLenCommmand(key.into())))
+        let key = key.into();
+        Ok(self.list.shard(&key).read()?.len(ListLenCommmand(key)))
     }
 
     pub fn list_remove(&self, cmd: impl Into<ListRemoveCommand>) -> Result<usize> {
-        Ok(self.list.write()?.remove(cmd))
+        let cmd = cmd.into();
+        Ok(self.list.shard(&cmd.0).write()?.remove(cmd))
     }
 
     pub fn list_contains(&self, key: impl Into<Key>, value: impl Into<KVDBValue>) -> Result<bool> {
+        let key = key.into();
         Ok(self
             .list
+            .shard(&key)
             .read()?
-            .contains(ListContainsValueCommand(key.into(), value.into())))
+            .contains(ListContainsValueCommand(key, value.into())))
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl MiniKVDBSnapshot {
+    pub fn list_range(&self, cmd: impl Into<ListRangeCommand>) -> Option<Vec<KVDBValue>> {
+        let cmd = cmd.into();
+        shard(&self.list, &cmd.0).range(cmd)
+    }
+
+    pub fn list_slice(&self, cmd: impl Into<ListRangeCommand>) -> Option<ListSlice> {
+        let cmd = cmd.into();
+        shard(&self.list, &cmd.0).slice(cmd)
+    }
+
+    pub fn list_range_as<T>(&self, cmd: impl Into<ListRangeCommand>) -> Result<Option<Vec<T>>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let cmd = cmd.into();
+        let key = cmd.0.clone();
+        self.list_range(cmd)
+            .map(|l| {
+                l.into_iter()
+                    .map(|v| {
+                        self.coercion
+                            .convert(v)
+                            .map_err(|e| MiniKVDBError::invalid_value(key.0.clone(), e))
+                    })
+                    .collect()
+            })
+            .transpose()
+    }
+
+    pub fn list_len(&self, key: impl Into<Key>) -> Option<usize> {
+        let key = key.into();
+        shard(&self.list, &key).len(ListLenCommmand(key))
+    }
+
+    pub fn list_contains(&self, key: impl Into<Key>, value: impl Into<KVDBValue>) -> bool {
+        let key = key.into();
+        shard(&self.list, &key).contains(ListContainsValueCommand(key, value.into()))
+    }
+
+    /// Sorted keys of the list store starting with the prefix.
+    pub fn list_keys(&self, prefix: impl Into<Key>) -> Vec<Key> {
+        scan(self.list.iter().flat_map(|s| s.0.keys()), &prefix.into())
     }
 }
 
@@ -227,12 +502,16 @@ impl MiniKVDB {
 mod tests {
     use tests::list_command::ListRemoveOption;
 
-    use crate::values;
+    use crate::{minikvdb::kvdb_coerce::Coercion, values};
 
     use self::list_command::ListRangeOption;
 
     use super::*;
 
+    fn list(db: &ListStore, key: &str) -> Vec<KVDBValue> {
+        db.0.get(key).unwrap().iter().cloned().collect()
+    }
+
     fn test_db() -> ListStore {
         ListStore::default()
     }
@@ -243,7 +522,7 @@ mod tests {
         let res = db.push_back(PushBackCommand("a".into(), values!(1, 2, 3, 4)));
 
         assert_eq!(res, 4);
-        assert_eq!(db.0.get("a").unwrap().clone(), values!(1, 2, 3, 4));
+        assert_eq!(list(&db, "a"), values!(1, 2, 3, 4));
     }
 
     #[test]
@@ -253,7 +532,7 @@ mod tests {
         let res = db.push_back(PushBackCommand("a".into(), values!(1, 2, 3, 4)));
 
         assert_eq!(res, 5);
-        assert_eq!(db.0.get("a").unwrap().clone(), values!(22, 1, 2, 3, 4));
+        assert_eq!(list(&db, "a"), values!(22, 1, 2, 3, 4));
     }
 
     #[test]
@@ -262,7 +541,7 @@ mod tests {
         let res = db.push_front(PushFrontCommand("a".into(), values!(1, 2, 3, 4)));
 
         assert_eq!(res, 4);
-        assert_eq!(db.0.get("a").unwrap().clone(), values!(4, 3, 2, 1));
+        assert_eq!(list(&db, "a"), values!(4, 3, 2, 1));
     }
 
     #[test]
@@ -272,7 +551,7 @@ mod tests {
         let res = db.push_front(PushFrontCommand("a".into(), values!(1, 2, 3, 4)));
 
         assert_eq!(res, 5);
-        assert_eq!(db.0.get("a").unwrap().clone(), values!(4, 3, 2, 1, 33));
+        assert_eq!(list(&db, "a"), values!(4, 3, 2, 1, 33));
     }
 
     #[test]
@@ -287,7 +566,7 @@ mod tests {
 
         let pop = db.pop_back(PopBackCommand("a".into()));
         assert!(pop.is_none());
-        assert!(db.0.get("a").is_none());
+        assert!(!db.0.contains_key("a"));
     }
 
     #[test]
@@ -302,7 +581,7 @@ mod tests {
 
         let pop = db.pop_front(PopFrontCommand("a".into()));
         assert!(pop.is_none());
-        assert!(db.0.get("a").is_none());
+        assert!(!db.0.contains_key("a"));
     }
 
     #[test]
@@ -352,7 +631,7 @@ mod tests {
             ListRemoveOption::All(2.into()),
         ));
         assert_eq!(count, 5);
-        assert!(db.0.get("items").is_none());
+        assert!(!db.0.contains_key("items"));
     }
 
     #[test]
@@ -364,10 +643,7 @@ mod tests {
         ));
 
         assert_eq!(del_num, 2);
-        assert_eq!(
-            db.0.get("mixed".into()).unwrap().clone(),
-            values!(1, 2.2, true, 4, "text")
-        );
+        assert_eq!(list(&db, "mixed"), values!(1, 2.2, true, 4, "text"));
     }
 
     #[test]
@@ -379,10 +655,7 @@ mod tests {
         ));
 
         assert_eq!(del_num, 1);
-        assert_eq!(
-            db.0.get("mixed".into()).unwrap().clone(),
-            values!(1, 2.2, true, 4, false, "text")
-        );
+        assert_eq!(list(&db, "mixed"), values!(1, 2.2, true, 4, false, "text"));
     }
 
     #[test]
@@ -485,4 +758,48 @@ mod tests {
         assert!(list.is_some());
         assert_eq!(list.unwrap(), vec![]);
     }
+
+    #[test]
+    fn pops_typed_values_only_when_they_convert() {
+        let db = MiniKVDB::default();
+        let _ = db.push_back("a", values!["3", 4]);
+
+        assert!(db.pop_front_as::<i32>("a").is_err());
+        assert_eq!(db.list_len("a").unwrap(), Some(2));
+
+        let lenient = db.with_coercion(Coercion::Lenient);
+        assert_eq!(
+            lenient.list_range_as::<i32>(("a",)).unwrap(),
+            Some(vec![3, 4])
+        );
+        assert_eq!(lenient.pop_front_as::<i32>("a").unwrap(), Some(3));
+        assert_eq!(db.pop_front_as::<i32>("a").unwrap(), Some(4));
+        assert_eq!(db.pop_front_as::<i32>("a").unwrap(), None);
+    }
+
+    #[test]
+    fn moves_values_between_lists() {
+        let db = MiniKVDB::with_shards(4);
+        let _ = db.push_back("a", values![1, 2]);
+        let _ = db.push_back("b", values![3]);
+
+        assert_eq!(db.list_move(("a", "b")).unwrap(), Some(2.into()));
+        assert_eq!(db.list_move(("a", "a")).unwrap(), Some(1.into()));
+        assert_eq!(db.list_range(("b",)).unwrap(), Some(values![2, 3]));
+        assert_eq!(db.list_move(("c", "b")).unwrap(), None);
+    }
+
+    #[test]
+    fn slices_lists() {
+        let db = MiniKVDB::default();
+        let _ = db.push_back("a", values![1, 2, 3, 4]);
+
+        let slice = db.list_slice(("a", 1usize, 1usize)).unwrap().unwrap();
+        let _ = db.push_front("a", values![0]);
+
+        assert_eq!(slice.len(), 2);
+        assert_eq!(slice.get(0), Some(&2.into()));
+        assert_eq!(Vec::from(slice), values![2, 3]);
+        assert_eq!(db.list_slice(("b",)).unwrap(), None);
+    }
 }
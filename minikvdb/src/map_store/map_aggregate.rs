@@ -0,0 +1,479 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
+
+use crate::minikvdb::{
+    kvdb_key::Key,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+use super::{
+    map_index::IndexValue,
+    map_query::{Predicate, SortOrder},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reducer {
+    Count,
+    /// Sum of the numbers, `Null` when the field holds none. Dates cannot be summed.
+    Sum(Key),
+    Avg(Key),
+    Min(Key),
+    Max(Key),
+}
+
+impl Reducer {
+    pub fn sum(field: impl Into<Key>) -> Self {
+        Self::Sum(field.into())
+    }
+
+    pub fn avg(field: impl Into<Key>) -> Self {
+        Self::Avg(field.into())
+    }
+
+    pub fn min(field: impl Into<Key>) -> Self {
+        Self::Min(field.into())
+    }
+
+    pub fn max(field: impl Into<Key>) -> Self {
+        Self::Max(field.into())
+    }
+
+    fn reduce(&self, objects: &[&KVDBObject]) -> Option<KVDBValue> {
+        let numbers = |field: &Key| {
+            objects
+                .iter()
+                .filter_map(|obj| obj.get(field))
+                .filter_map(|v| Number::new(v).map(|n| (n, v)))
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            Reducer::Count => Some(int_value(objects.len() as i128)),
+            Reducer::Sum(field) => {
+                let numbers = numbers(field);
+                let mut int_sum = 0i128;
+                let mut float_sum = 0.0;
+                let mut is_float = false;
+                let mut summed = false;
+                for (n, _) in numbers {
+                    match n {
+                        Number::Int(v) => int_sum = int_sum.saturating_add(v as i128),
+                        Number::Float(v) => {
+                            float_sum += v;
+                            is_float = true;
+                        }
+                        #[cfg(feature = "chrono")]
+                        Number::Time(_) => continue,
+                    }
+                    summed = true;
+                }
+                if !summed {
+                    Some(KVDBValue::Null)
+                } else if is_float {
+                    Some(float_value(int_sum as f64 + float_sum))
+                } else {
+                    Some(int_value(int_sum))
+                }
+            }
+            Reducer::Avg(field) => {
+                let numbers = numbers(field);
+                let mut sum = 0.0;
+                let mut count = 0;
+                #[cfg(feature = "chrono")]
+                let mut times = vec![];
+                for (n, _) in numbers {
+                    match n {
+                        Number::Int(v) => sum += v as f64,
+                        Number::Float(v) => sum += v,
+                        #[cfg(feature = "chrono")]
+                        Number::Time(t) => {
+                            times.push(t);
+                            continue;
+                        }
+                    }
+                    count += 1;
+                }
+
+                #[cfg(feature = "chrono")]
+                if count == 0 && !times.is_empty() {
+                    let micros: i128 = times.iter().map(|t| t.timestamp_micros() as i128).sum();
+                    let avg = (micros / times.len() as i128) as i64;
+                    return chrono::DateTime::from_timestamp_micros(avg)
+                        .map(KVDBValue::DateTimeUtc);
+                }
+
+                (count > 0).then(|| float_value(sum / count as f64))
+            }
+            Reducer::Min(field) => numbers(field)
+                .into_iter()
+                .min_by(|(a, _), (b, _)| a.compare(b))
+                .map(|(_, v)| v.clone()),
+            Reducer::Max(field) => numbers(field)
+                .into_iter()
+                .max_by(|(a, _), (b, _)| a.compare(b))
+                .map(|(_, v)| v.clone()),
+        }
+    }
+}
+
+/// Numeric view over values so that `Int`, `Float`, `Long` and `Double` aggregate together.
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+    #[cfg(feature = "chrono")]
+    Time(chrono::DateTime<chrono::Utc>),
+}
+
+impl Number {
+    fn new(value: &KVDBValue) -> Option<Self> {
+        match value {
+            KVDBValue::Int(v) => Some(Self::Int(*v as i64)),
+            KVDBValue::Float(v) => Some(Self::Float(*v as f64)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(v) => Some(Self::Int(*v)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(v) => Some(Self::Float(*v)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(v) => Some(Self::Int(*v as i64)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(v) => Some(Self::wide(*v as i128)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(v) => Some(Self::wide(*v)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(v) => {
+                Some(i128::try_from(*v).map_or(Self::Float(*v as f64), Self::wide))
+            }
+            #[cfg(feature = "decimal")]
+            KVDBValue::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_f64(v).map(Self::Float),
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTimeUtc(v) => Some(Self::Time(*v)),
+            _ => None,
+        }
+    }
+
+    /// Integers outside of the `i64` range are aggregated as floats.
+    #[cfg(feature = "big-types")]
+    fn wide(v: i128) -> Self {
+        i64::try_from(v).map_or(Self::Float(v as f64), Self::Int)
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.cmp(b),
+            (Number::Int(a), Number::Float(b)) => (*a as f64).total_cmp(b),
+            (Number::Float(a), Number::Int(b)) => a.total_cmp(&(*b as f64)),
+            (Number::Float(a), Number::Float(b)) => a.total_cmp(b),
+            #[cfg(feature = "chrono")]
+            (Number::Time(a), Number::Time(b)) => a.cmp(b),
+            // Dates are always greater than numbers.
+            #[cfg(feature = "chrono")]
+            (Number::Time(_), _) => Ordering::Greater,
+            #[cfg(feature = "chrono")]
+            (_, Number::Time(_)) => Ordering::Less,
+        }
+    }
+}
+
+/// Integers too large for the widest integer value become floats.
+#[cfg(feature = "big-types")]
+fn int_value(v: i128) -> KVDBValue {
+    i64::try_from(v).map_or(float_value(v as f64), KVDBValue::Long)
+}
+
+#[cfg(not(feature = "big-types"))]
+fn int_value(v: i128) -> KVDBValue {
+    i32::try_from(v).map_or(float_value(v as f64), KVDBValue::Int)
+}
+
+/// Integers of every width group together.
+fn group_key(value: &KVDBValue) -> KVDBValue {
+    match Number::new(value) {
+        Some(Number::Int(v)) => int_value(v as i128),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(feature = "big-types")]
+fn float_value(v: f64) -> KVDBValue {
+    KVDBValue::Double(v)
+}
+
+#[cfg(not(feature = "big-types"))]
+fn float_value(v: f64) -> KVDBValue {
+    KVDBValue::Float(v as f32)
+}
+
+#[derive(Debug, Clone)]
+pub struct Aggregation {
+    pub prefix: Key,
+    pub filter: Option<Predicate>,
+    pub group_by: Option<Key>,
+    pub reducers: Vec<(Key, Reducer)>,
+    pub sort: Option<(Key, SortOrder)>,
+    pub limit: Option<usize>,
+}
+
+impl Aggregation {
+    pub fn new(prefix: impl Into<Key>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            filter: None,
+            group_by: None,
+            reducers: vec![],
+            sort: None,
+            limit: None,
+        }
+    }
+
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.filter = Some(match self.filter {
+            Some(f) => f.and(predicate),
+            None => predicate,
+        });
+        self
+    }
+
+    pub fn group_by(mut self, field: impl Into<Key>) -> Self {
+        self.group_by = Some(field.into());
+        self
+    }
+
+    /// Adds a reducer whose result is stored under `name` in every output row.
+    pub fn reduce(mut self, name: impl Into<Key>, reducer: Reducer) -> Self {
+        self.reducers.push((name.into(), reducer));
+        self
+    }
+
+    pub fn sort_by(mut self, field: impl Into<Key>, order: SortOrder) -> Self {
+        self.sort = Some((field.into(), order));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub(crate) fn run<'a>(
+        &self,
+        objects: impl Iterator<Item = (&'a Key, &'a KVDBObject)>,
+    ) -> Vec<KVDBObject> {
+        let matching = objects.filter(|(k, obj)| {
+            k.0.starts_with(&self.prefix.0) && self.filter.as_ref().is_none_or(|f| f.matches(obj))
+        });
+
+        let mut groups: BTreeMap<Option<IndexValue>, Vec<&KVDBObject>> = BTreeMap::new();
+        match &self.group_by {
+            Some(field) => {
+                // Objects without the grouped field are left out.
+                for (_, obj) in matching {
+                    if let Some(v) = obj.get(field) {
+                        groups
+                            .entry(Some(IndexValue(group_key(v))))
+                            .or_default()
+                            .push(obj);
+                    }
+                }
+            }
+            None => {
+                groups.insert(None, matching.map(|(_, obj)| obj).collect());
+            }
+        }
+
+        let mut rows: Vec<KVDBObject> = groups
+            .into_iter()
+            .map(|(group, objects)| {
+                let mut row = HashMap::new();
+                if let (Some(field), Some(IndexValue(v))) = (&self.group_by, group) {
+                    row.insert(field.clone(), v);
+                }
+                for (name, reducer) in self.reducers.iter() {
+                    if let Some(v) = reducer.reduce(&objects) {
+                        row.insert(name.clone(), v);
+                    }
+                }
+                row
+            })
+            .collect();
+
+        if let Some((field, order)) = &self.sort {
+            rows.sort_by(|a, b| match (a.get(field), b.get(field)) {
+                (Some(a), Some(b)) => match order {
+                    SortOrder::Asc => a.total_cmp(b),
+                    SortOrder::Desc => b.total_cmp(a),
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+
+        rows.truncate(self.limit.unwrap_or(usize::MAX));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded() -> HashMap<Key, KVDBObject> {
+        [
+            ("user:1", "pl", 20, 10.5f32),
+            ("user:2", "pl", 30, 2.0),
+            ("user:3", "de", 40, 1.0),
+            ("user:4", "uk", 50, 4.0),
+            ("user:5", "uk", 10, 8.0),
+            ("user:6", "uk", 60, 0.5),
+        ]
+        .into_iter()
+        .map(|(k, country, age, balance)| {
+            (
+                k.into(),
+                [
+                    ("country".into(), country.into()),
+                    ("age".into(), age.into()),
+                    ("balance".into(), balance.into()),
+                ]
+                .into(),
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn aggregates_without_grouping() {
+        let data = seeded();
+        let rows = Aggregation::new("user:")
+            .filter(Predicate::gt("age", 15))
+            .reduce("count", Reducer::Count)
+            .reduce("total_age", Reducer::sum("age"))
+            .reduce("avg_age", Reducer::avg("age"))
+            .reduce("oldest", Reducer::max("age"))
+            .reduce("poorest", Reducer::min("balance"))
+            .run(data.iter());
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["count"], int_value(5));
+        assert_eq!(rows[0]["total_age"], int_value(200));
+        assert_eq!(rows[0]["avg_age"], float_value(40.0));
+        assert_eq!(rows[0]["oldest"], KVDBValue::Int(60));
+        assert_eq!(rows[0]["poorest"], KVDBValue::Float(0.5));
+    }
+
+    #[test]
+    fn groups_sorts_and_limits() {
+        let data = seeded();
+        let rows = Aggregation::new("user:")
+            .group_by("country")
+            .reduce("count", Reducer::Count)
+            .reduce("balance", Reducer::sum("balance"))
+            .sort_by("count", SortOrder::Desc)
+            .limit(2)
+            .run(data.iter());
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["country"], "uk".into());
+        assert_eq!(rows[0]["count"], int_value(3));
+        assert_eq!(rows[0]["balance"], float_value(12.5));
+        assert_eq!(rows[1]["country"], "pl".into());
+    }
+
+    #[cfg(feature = "big-types")]
+    #[test]
+    fn mixes_numeric_types() {
+        let data: HashMap<Key, KVDBObject> = [
+            ("a".into(), [("v".into(), 1.into())].into()),
+            ("b".into(), [("v".into(), 2i64.into())].into()),
+            ("c".into(), [("v".into(), 0.5f64.into())].into()),
+        ]
+        .into();
+        let rows = Aggregation::new("")
+            .reduce("sum", Reducer::sum("v"))
+            .reduce("max", Reducer::max("v"))
+            .run(data.iter());
+
+        assert_eq!(rows[0]["sum"], KVDBValue::Double(3.5));
+        assert_eq!(rows[0]["max"], KVDBValue::Long(2));
+
+        let data: HashMap<Key, KVDBObject> = [
+            ("a".into(), [("v".into(), 5.into())].into()),
+            ("b".into(), [("v".into(), 5i64.into())].into()),
+            ("c".into(), [("v".into(), i64::MAX.into())].into()),
+            ("d".into(), [("v".into(), i64::MAX.into())].into()),
+        ]
+        .into();
+        let rows = Aggregation::new("")
+            .group_by("v")
+            .reduce("count", Reducer::Count)
+            .run(data.iter());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["v"], KVDBValue::Long(5));
+        assert_eq!(rows[0]["count"], KVDBValue::Long(2));
+
+        let rows = Aggregation::new("")
+            .reduce("sum", Reducer::sum("v"))
+            .run(data.iter());
+        assert_eq!(
+            rows[0]["sum"],
+            KVDBValue::Double(i64::MAX as f64 * 2.0 + 10.0)
+        );
+    }
+
+    #[cfg(not(feature = "big-types"))]
+    #[test]
+    fn sums_past_int_range_as_float() {
+        let data: HashMap<Key, KVDBObject> = [
+            ("a".into(), [("v".into(), i32::MAX.into())].into()),
+            ("b".into(), [("v".into(), 1.into())].into()),
+        ]
+        .into();
+        let rows = Aggregation::new("")
+            .reduce("sum", Reducer::sum("v"))
+            .run(data.iter());
+        assert_eq!(rows[0]["sum"], KVDBValue::Float(i32::MAX as f32 + 1.0));
+    }
+
+    #[test]
+    fn sums_to_null_without_numbers() {
+        let data: HashMap<Key, KVDBObject> = [
+            ("a".into(), [("v".into(), "one".into())].into()),
+            ("b".into(), [("w".into(), 1.into())].into()),
+        ]
+        .into();
+        let rows = Aggregation::new("")
+            .reduce("sum", Reducer::sum("v"))
+            .run(data.iter());
+        assert_eq!(rows[0]["sum"], KVDBValue::Null);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn averages_dates() {
+        let first = chrono::DateTime::from_timestamp(1_000, 0).unwrap();
+        let second = chrono::DateTime::from_timestamp(3_000, 0).unwrap();
+        let data: HashMap<Key, KVDBObject> = [
+            ("a".into(), [("at".into(), first.into())].into()),
+            ("b".into(), [("at".into(), second.into())].into()),
+        ]
+        .into();
+        let rows = Aggregation::new("")
+            .reduce("avg", Reducer::avg("at"))
+            .reduce("min", Reducer::min("at"))
+            .run(data.iter());
+
+        assert_eq!(
+            rows[0]["avg"],
+            chrono::DateTime::from_timestamp(2_000, 0).unwrap().into()
+        );
+        assert_eq!(rows[0]["min"], first.into());
+
+        let rows = Aggregation::new("")
+            .reduce("sum", Reducer::sum("at"))
+            .run(data.iter());
+        assert_eq!(rows[0]["sum"], KVDBValue::Null);
+    }
+}
@@ -1,4 +1,15 @@
-use crate::minikvdb::{kvdb_key::Key, kvdb_value::KVDBObject};
+use std::ops::{Bound, RangeBounds};
+
+use crate::minikvdb::{
+    kvdb_key::Key,
+    kvdb_path::KVDBPath,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+use super::{
+    map_aggregate::Aggregation, map_index::MapIndexDefinition, map_migrate::Migration,
+    map_query::MapQuery,
+};
 
 #[derive(Debug, Clone)]
 pub struct SetCommand(pub Key, pub KVDBObject);
@@ -17,3 +28,103 @@ pub struct GetObjectCommand(pub Key);
 
 #[derive(Debug, Clone)]
 pub struct ContainsKeyCommand(pub Key);
+
+#[derive(Debug, Clone)]
+pub struct CountCommand(pub Key);
+
+#[derive(Debug, Clone)]
+pub struct GetPathCommand(pub Key, pub KVDBPath);
+
+#[derive(Debug, Clone)]
+pub struct SetPathCommand(pub Key, pub KVDBPath, pub KVDBValue);
+
+#[derive(Debug, Clone)]
+pub struct CreateIndexCommand(pub MapIndexDefinition);
+
+#[derive(Debug, Clone)]
+pub struct DropIndexCommand(pub Key, pub Key);
+
+#[derive(Debug, Clone)]
+pub struct FindCommand(pub Key, pub Key, pub KVDBValue);
+
+#[derive(Debug, Clone)]
+pub struct FindRangeCommand(pub Key, pub Key, pub Bound<KVDBValue>, pub Bound<KVDBValue>);
+
+impl FindRangeCommand {
+    pub fn new<V: Into<KVDBValue> + Clone>(
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        range: impl RangeBounds<V>,
+    ) -> Self {
+        Self(
+            prefix.into(),
+            field.into(),
+            range.start_bound().cloned().map(Into::into),
+            range.end_bound().cloned().map(Into::into),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryCommand(pub MapQuery);
+
+impl From<MapQuery> for QueryCommand {
+    fn from(value: MapQuery) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AggregateCommand(pub Aggregation);
+
+impl From<Aggregation> for AggregateCommand {
+    fn from(value: Aggregation) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisterMigrationCommand(pub Key, pub u32, pub Migration);
+
+#[derive(Debug, Clone)]
+pub struct MigrateCommand(pub Key);
+
+#[cfg(feature = "schema")]
+#[derive(Debug, Clone)]
+pub struct SetSchemaCommand(pub super::map_schema::MapSchema);
+
+#[cfg(feature = "schema")]
+#[derive(Debug, Clone)]
+pub struct DropSchemaCommand(pub Key);
+
+#[cfg(feature = "search")]
+#[derive(Debug, Clone)]
+pub struct CreateSearchIndexCommand(pub super::map_search::SearchIndexDefinition);
+
+#[cfg(feature = "search")]
+#[derive(Debug, Clone)]
+pub struct DropSearchIndexCommand(pub Key);
+
+#[cfg(feature = "search")]
+#[derive(Debug, Clone)]
+pub struct SearchCommand(pub Key, pub super::map_search::SearchQuery);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct EnableHistoryCommand(pub Key, pub crate::minikvdb::kvdb_history::HistoryRetention);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct DisableHistoryCommand(pub Key);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct VersionsCommand(pub Key);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct GetAsOfCommand(pub Key, pub std::time::SystemTime);
+
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct RestoreCommand(pub Key, pub std::time::SystemTime);
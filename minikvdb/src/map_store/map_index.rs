@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    ops::Bound,
+};
+
+use crate::minikvdb::{
+    kvdb_key::Key,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MapIndexKind {
+    Hash,
+    Ordered,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MapIndexDefinition {
+    pub prefix: Key,
+    pub field: Key,
+    pub kind: MapIndexKind,
+}
+
+impl MapIndexDefinition {
+    pub fn covers(&self, key: &Key) -> bool {
+        key.0.starts_with(&self.prefix.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct IndexValue(pub KVDBValue);
+
+impl PartialEq for IndexValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexValue {}
+
+impl PartialOrd for IndexValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IndexValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.range_cmp(&other.0)
+    }
+}
+
+impl Hash for IndexValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(&self.0).hash(state);
+        match &self.0 {
+            KVDBValue::Null => {}
+            KVDBValue::Int(v) => v.hash(state),
+            KVDBValue::Float(v) => v.to_bits().hash(state),
+            KVDBValue::Bool(v) => v.hash(state),
+            KVDBValue::String(v) => v.hash(state),
+            KVDBValue::Bytes(v) => v.hash(state),
+            KVDBValue::List(v) => {
+                for v in v {
+                    IndexValue(v.clone()).hash(state);
+                }
+            }
+            KVDBValue::Map(v) => {
+                for (k, v) in KVDBValue::sorted_entries(v) {
+                    k.hash(state);
+                    IndexValue(v.clone()).hash(state);
+                }
+            }
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTimeUtc(v) => v.hash(state),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(v) => v.hash(state),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(v) => v.to_bits().hash(state),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(v) => v.hash(state),
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(v) => v.hash(state),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(v) => v.hash(state),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(v) => v.hash(state),
+            #[cfg(feature = "chrono")]
+            KVDBValue::Date(v) => v.hash(state),
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTime(v) => v.hash(state),
+            #[cfg(feature = "uuid")]
+            KVDBValue::Uuid(v) => v.hash(state),
+            #[cfg(feature = "decimal")]
+            KVDBValue::Decimal(v) => v.hash(state),
+            #[cfg(feature = "duration")]
+            KVDBValue::Duration(v) => v.hash(state),
+        }
+    }
+}
+
+/// Keys holding an indexed value. With the `persistent` feature the entries are
+/// persistent maps and sets, which snapshots share without cloning them.
+#[cfg(not(feature = "persistent"))]
+type Keys = std::collections::BTreeSet<Key>;
+#[cfg(feature = "persistent")]
+type Keys = imbl::OrdSet<Key>;
+
+#[cfg(not(feature = "persistent"))]
+type HashEntries = std::collections::HashMap<IndexValue, Keys>;
+#[cfg(feature = "persistent")]
+type HashEntries = imbl::HashMap<IndexValue, Keys>;
+
+#[cfg(not(feature = "persistent"))]
+type OrderedEntries = std::collections::BTreeMap<IndexValue, Keys>;
+#[cfg(feature = "persistent")]
+type OrderedEntries = imbl::OrdMap<IndexValue, Keys>;
+
+#[derive(Debug, Clone)]
+enum IndexEntries {
+    Hash(HashEntries),
+    Ordered(OrderedEntries),
+}
+
+#[derive(Debug, Clone)]
+pub struct MapIndex {
+    definition: MapIndexDefinition,
+    entries: IndexEntries,
+}
+
+impl MapIndex {
+    pub fn new(definition: MapIndexDefinition) -> Self {
+        let entries = match definition.kind {
+            MapIndexKind::Hash => IndexEntries::Hash(HashEntries::new()),
+            MapIndexKind::Ordered => IndexEntries::Ordered(OrderedEntries::new()),
+        };
+        Self {
+            definition,
+            entries,
+        }
+    }
+
+    pub fn definition(&self) -> &MapIndexDefinition {
+        &self.definition
+    }
+
+    pub fn insert(&mut self, key: &Key, obj: &KVDBObject) {
+        if !self.definition.covers(key) {
+            return;
+        }
+        let Some(value) = obj.get(&self.definition.field) else {
+            return;
+        };
+        let value = IndexValue(value.clone());
+        match &mut self.entries {
+            IndexEntries::Hash(e) => e.entry(value).or_default().insert(key.clone()),
+            IndexEntries::Ordered(e) => e.entry(value).or_default().insert(key.clone()),
+        };
+    }
+
+    pub fn remove(&mut self, key: &Key, obj: &KVDBObject) {
+        if !self.definition.covers(key) {
+            return;
+        }
+        let Some(value) = obj.get(&self.definition.field) else {
+            return;
+        };
+        let value = IndexValue(value.clone());
+        match &mut self.entries {
+            IndexEntries::Hash(e) => {
+                if let Some(keys) = e.get_mut(&value) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        e.remove(&value);
+                    }
+                }
+            }
+            IndexEntries::Ordered(e) => {
+                if let Some(keys) = e.get_mut(&value) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        e.remove(&value);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn find(&self, value: &KVDBValue) -> Vec<Key> {
+        let value = IndexValue(value.clone());
+        let keys = match &self.entries {
+            IndexEntries::Hash(e) => e.get(&value),
+            IndexEntries::Ordered(e) => e.get(&value),
+        };
+        keys.map(|k| k.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns `None` when the index does not support range lookups.
+    pub fn find_range(&self, from: Bound<&KVDBValue>, to: Bound<&KVDBValue>) -> Option<Vec<Key>> {
+        let IndexEntries::Ordered(e) = &self.entries else {
+            return None;
+        };
+        let wrap = |b: Bound<&KVDBValue>| b.map(|v| IndexValue(v.clone()));
+        // The range also spans values of other kinds ordered between the bounds.
+        let comparable = |v: &KVDBValue| {
+            [from, to].iter().all(|b| match b {
+                Bound::Included(b) | Bound::Excluded(b) => v.kind_cmp(b).is_some(),
+                Bound::Unbounded => true,
+            })
+        };
+        Some(
+            e.range((wrap(from), wrap(to)))
+                .filter(|(v, _)| comparable(&v.0))
+                .flat_map(|(_, keys)| keys.iter().cloned())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person(name: &str, age: i32) -> KVDBObject {
+        [("name".into(), name.into()), ("age".into(), age.into())].into()
+    }
+
+    fn seeded_index(kind: MapIndexKind) -> MapIndex {
+        let mut idx = MapIndex::new(MapIndexDefinition {
+            prefix: "user:".into(),
+            field: "age".into(),
+            kind,
+        });
+        idx.insert(&"user:1".into(), &person("tom", 22));
+        idx.insert(&"user:2".into(), &person("bob", 42));
+        idx.insert(&"user:3".into(), &person("john", 22));
+        idx.insert(&"admin:1".into(), &person("root", 22));
+        idx
+    }
+
+    #[test]
+    fn finds_keys_by_exact_value() {
+        let idx = seeded_index(MapIndexKind::Hash);
+        assert_eq!(
+            idx.find(&22.into()),
+            vec![Key::from("user:1"), Key::from("user:3")]
+        );
+        assert!(idx.find(&99.into()).is_empty());
+    }
+
+    #[test]
+    fn removes_keys_from_index() {
+        let mut idx = seeded_index(MapIndexKind::Hash);
+        idx.remove(&"user:1".into(), &person("tom", 22));
+        assert_eq!(idx.find(&22.into()), vec![Key::from("user:3")]);
+    }
+
+    #[test]
+    fn finds_keys_by_range_on_ordered_index() {
+        let idx = seeded_index(MapIndexKind::Ordered);
+        let keys = idx.find_range(Bound::Included(&20.into()), Bound::Excluded(&42.into()));
+        assert_eq!(keys, Some(vec![Key::from("user:1"), Key::from("user:3")]));
+    }
+
+    #[test]
+    fn hash_index_does_not_support_range() {
+        let idx = seeded_index(MapIndexKind::Hash);
+        assert!(idx.find_range(Bound::Unbounded, Bound::Unbounded).is_none());
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+    error::Result,
+    minikvdb::{
+        kvdb_entity::VERSION_FIELD,
+        kvdb_key::Key,
+        kvdb_value::{KVDBObject, KVDBValue},
+    },
+};
+
+/// Upgrades an object by one version. The version field is updated by the caller.
+#[derive(Clone)]
+pub struct Migration(pub Arc<dyn Fn(KVDBObject) -> Result<KVDBObject> + Send + Sync>);
+
+impl Debug for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Migration")
+    }
+}
+
+impl<F> From<F> for Migration
+where
+    F: Fn(KVDBObject) -> Result<KVDBObject> + Send + Sync + 'static,
+{
+    fn from(value: F) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MigrationStep {
+    prefix: Key,
+    from: u32,
+    migration: Migration,
+}
+
+/// Migrations registered per key prefix, applied in order of versions.
+#[derive(Debug, Default, Clone)]
+pub struct Migrations {
+    steps: Vec<MigrationStep>,
+}
+
+impl Migrations {
+    /// Replaces the migration registered for the same prefix and version.
+    pub fn register(&mut self, prefix: Key, from: u32, migration: Migration) {
+        self.steps.retain(|s| s.prefix != prefix || s.from != from);
+        self.steps.push(MigrationStep {
+            prefix,
+            from,
+            migration,
+        });
+    }
+
+    fn step(&self, key: &Key, version: u32) -> Option<&MigrationStep> {
+        self.steps
+            .iter()
+            .filter(|s| s.from == version && key.0.starts_with(&s.prefix.0))
+            .max_by_key(|s| s.prefix.0.len())
+    }
+
+    /// Upgraded object, or `None` when no migration applies.
+    pub fn apply(&self, key: &Key, obj: &KVDBObject) -> Result<Option<KVDBObject>> {
+        let mut version = version(obj);
+        let mut migrated = None;
+        while let Some(step) = self.step(key, version) {
+            let mut obj = (step.migration.0)(migrated.unwrap_or_else(|| obj.clone()))?;
+            version += 1;
+            obj.insert(VERSION_FIELD.into(), KVDBValue::Int(version as i32));
+            migrated = Some(obj);
+        }
+        Ok(migrated)
+    }
+
+    /// Object upgraded to the latest version, unchanged when no migration applies.
+    pub fn upgrade(&self, key: &Key, obj: KVDBObject) -> Result<KVDBObject> {
+        Ok(self.apply(key, &obj)?.unwrap_or(obj))
+    }
+}
+
+/// Version of a stored object.
+pub fn version(obj: &KVDBObject) -> u32 {
+    match obj.get(VERSION_FIELD) {
+        Some(KVDBValue::Int(v)) => (*v).max(1) as u32,
+        _ => 1,
+    }
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&MigrationProgress) + 'a>;
+
+/// Batch migration of every object under a prefix, run with `MiniKVDB::hash_migrate`.
+pub struct MigrationJob<'a> {
+    pub prefix: Key,
+    /// Reports what would be migrated without writing.
+    pub dry_run: bool,
+    pub(crate) progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> MigrationJob<'a> {
+    pub fn new(prefix: impl Into<Key>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            dry_run: false,
+            progress: None,
+        }
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Called after every processed object.
+    pub fn on_progress(mut self, progress: impl FnMut(&MigrationProgress) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationProgress {
+    pub key: Key,
+    pub processed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub scanned: usize,
+    /// Keys of objects that were migrated, or would be in a dry run.
+    pub migrated: Vec<Key>,
+    pub failed: Vec<(Key, crate::error::MiniKVDBError)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrations() -> Migrations {
+        let mut migrations = Migrations::default();
+        migrations.register(
+            "user:".into(),
+            1,
+            Migration::from(|mut obj: KVDBObject| {
+                obj.insert("email".into(), KVDBValue::Null);
+                Ok(obj)
+            }),
+        );
+        migrations.register(
+            "user:".into(),
+            2,
+            Migration::from(|mut obj: KVDBObject| {
+                let name = obj.remove("name").unwrap_or(KVDBValue::Null);
+                obj.insert("full_name".into(), name);
+                Ok(obj)
+            }),
+        );
+        migrations
+    }
+
+    #[test]
+    fn applies_migrations_in_order() {
+        let obj: KVDBObject = [("name".into(), "tom".into())].into();
+        let migrated = migrations().apply(&"user:1".into(), &obj).unwrap().unwrap();
+
+        assert_eq!(version(&migrated), 3);
+        assert_eq!(migrated["full_name"], "tom".into());
+        assert_eq!(migrated["email"], KVDBValue::Null);
+        assert!(!migrated.contains_key("name"));
+    }
+
+    #[test]
+    fn skips_objects_at_latest_version_or_other_prefix() {
+        let obj: KVDBObject = [(VERSION_FIELD.into(), 3.into())].into();
+        assert!(migrations()
+            .apply(&"user:1".into(), &obj)
+            .unwrap()
+            .is_none());
+        assert!(migrations()
+            .apply(&"admin:1".into(), &KVDBObject::new())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,251 @@
+use std::{cmp::Ordering, ops::Not};
+
+use crate::minikvdb::{
+    kvdb_key::Key,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Eq(Key, KVDBValue),
+    Ne(Key, KVDBValue),
+    Lt(Key, KVDBValue),
+    Le(Key, KVDBValue),
+    Gt(Key, KVDBValue),
+    Ge(Key, KVDBValue),
+    In(Key, Vec<KVDBValue>),
+    Prefix(Key, String),
+    Exists(Key),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn eq(field: impl Into<Key>, value: impl Into<KVDBValue>) -> Self {
+        Self::Eq(field.into(), value.into())
+    }
+
+    pub fn ne(field: impl Into<Key>, value: impl Into<KVDBValue>) -> Self {
+        Self::Ne(field.into(), value.into())
+    }
+
+    pub fn lt(field: impl Into<Key>, value: impl Into<KVDBValue>) -> Self {
+        Self::Lt(field.into(), value.into())
+    }
+
+    pub fn le(field: impl Into<Key>, value: impl Into<KVDBValue>) -> Self {
+        Self::Le(field.into(), value.into())
+    }
+
+    pub fn gt(field: impl Into<Key>, value: impl Into<KVDBValue>) -> Self {
+        Self::Gt(field.into(), value.into())
+    }
+
+    pub fn ge(field: impl Into<Key>, value: impl Into<KVDBValue>) -> Self {
+        Self::Ge(field.into(), value.into())
+    }
+
+    pub fn is_in(field: impl Into<Key>, values: impl Into<Vec<KVDBValue>>) -> Self {
+        Self::In(field.into(), values.into())
+    }
+
+    pub fn prefix(field: impl Into<Key>, prefix: impl Into<String>) -> Self {
+        Self::Prefix(field.into(), prefix.into())
+    }
+
+    pub fn exists(field: impl Into<Key>) -> Self {
+        Self::Exists(field.into())
+    }
+
+    pub fn and(self, other: Predicate) -> Self {
+        match self {
+            Self::And(mut all) => {
+                all.push(other);
+                Self::And(all)
+            }
+            this => Self::And(vec![this, other]),
+        }
+    }
+
+    pub fn or(self, other: Predicate) -> Self {
+        match self {
+            Self::Or(mut any) => {
+                any.push(other);
+                Self::Or(any)
+            }
+            this => Self::Or(vec![this, other]),
+        }
+    }
+
+    pub fn matches(&self, obj: &KVDBObject) -> bool {
+        // Ordering comparisons only match numbers or values of the same type.
+        let cmp = |field: &Key, value: &KVDBValue| obj.get(field).and_then(|v| v.kind_cmp(value));
+
+        match self {
+            Predicate::Eq(f, v) => cmp(f, v).is_some_and(Ordering::is_eq),
+            Predicate::Ne(f, v) => !cmp(f, v).is_some_and(Ordering::is_eq),
+            Predicate::Lt(f, v) => cmp(f, v).is_some_and(Ordering::is_lt),
+            Predicate::Le(f, v) => cmp(f, v).is_some_and(Ordering::is_le),
+            Predicate::Gt(f, v) => cmp(f, v).is_some_and(Ordering::is_gt),
+            Predicate::Ge(f, v) => cmp(f, v).is_some_and(Ordering::is_ge),
+            Predicate::In(f, vs) => vs.iter().any(|v| cmp(f, v).is_some_and(Ordering::is_eq)),
+            Predicate::Prefix(f, p) => {
+                matches!(obj.get(f), Some(KVDBValue::String(s)) if s.starts_with(p.as_str()))
+            }
+            Predicate::Exists(f) => obj.contains_key(f),
+            Predicate::And(all) => all.iter().all(|p| p.matches(obj)),
+            Predicate::Or(any) => any.iter().any(|p| p.matches(obj)),
+            Predicate::Not(p) => !p.matches(obj),
+        }
+    }
+}
+
+impl Not for Predicate {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self::Not(Box::new(self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapQuery {
+    pub prefix: Key,
+    pub filter: Option<Predicate>,
+    pub sort: Option<(Key, SortOrder)>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub fields: Option<Vec<Key>>,
+}
+
+impl MapQuery {
+    pub fn new(prefix: impl Into<Key>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            filter: None,
+            sort: None,
+            offset: 0,
+            limit: None,
+            fields: None,
+        }
+    }
+
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.filter = Some(match self.filter {
+            Some(f) => f.and(predicate),
+            None => predicate,
+        });
+        self
+    }
+
+    pub fn sort_by(mut self, field: impl Into<Key>, order: SortOrder) -> Self {
+        self.sort = Some((field.into(), order));
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn select<K: Into<Key>>(mut self, fields: impl IntoIterator<Item = K>) -> Self {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub(crate) fn matches(&self, key: &Key, obj: &KVDBObject) -> bool {
+        key.0.starts_with(&self.prefix.0) && self.filter.as_ref().is_none_or(|f| f.matches(obj))
+    }
+
+    /// Sorts, paginates and projects objects that already matched the query.
+    pub(crate) fn finish(&self, mut found: Vec<(Key, KVDBObject)>) -> Vec<(Key, KVDBObject)> {
+        match &self.sort {
+            Some((field, order)) => found.sort_by(|(ak, a), (bk, b)| {
+                let ord = match (a.get(field), b.get(field)) {
+                    (Some(a), Some(b)) => match order {
+                        SortOrder::Asc => a.total_cmp(b),
+                        SortOrder::Desc => b.total_cmp(a),
+                    },
+                    // Objects without the sort field always go last.
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                };
+                ord.then_with(|| ak.cmp(bk))
+            }),
+            None => found.sort_by(|(a, _), (b, _)| a.cmp(b)),
+        }
+
+        found
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(k, obj)| match &self.fields {
+                Some(fields) => {
+                    let obj = obj
+                        .into_iter()
+                        .filter(|(f, _)| fields.contains(f))
+                        .collect();
+                    (k, obj)
+                }
+                None => (k, obj),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person() -> KVDBObject {
+        [
+            ("name".into(), "tom".into()),
+            ("age".into(), 22.into()),
+            ("premium".into(), true.into()),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn matches_comparison_predicates() {
+        let obj = person();
+        assert!(Predicate::eq("name", "tom").matches(&obj));
+        assert!(Predicate::ne("name", "bob").matches(&obj));
+        assert!(Predicate::gt("age", 20).matches(&obj));
+        assert!(Predicate::le("age", 22).matches(&obj));
+        assert!(!Predicate::lt("age", 22).matches(&obj));
+        assert!(Predicate::gt("age", 20.5f32).matches(&obj));
+        assert!(!Predicate::gt("age", "20").matches(&obj));
+        assert!(!Predicate::lt("premium", 1).matches(&obj));
+        assert!(Predicate::is_in("age", crate::values!(1, 22)).matches(&obj));
+        assert!(Predicate::prefix("name", "to").matches(&obj));
+        assert!(Predicate::exists("premium").matches(&obj));
+        assert!(!Predicate::exists("email").matches(&obj));
+    }
+
+    #[test]
+    fn combines_predicates() {
+        let obj = person();
+        assert!(Predicate::eq("name", "tom")
+            .and(Predicate::gt("age", 18))
+            .matches(&obj));
+        assert!(Predicate::eq("name", "bob")
+            .or(Predicate::eq("premium", true))
+            .matches(&obj));
+        assert!((!Predicate::eq("name", "bob")).matches(&obj));
+    }
+}
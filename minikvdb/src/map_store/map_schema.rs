@@ -0,0 +1,418 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Display;
+
+use crate::{
+    error::{MiniKVDBError, Result},
+    minikvdb::{
+        kvdb_entity::KVDBEntity,
+        kvdb_key::Key,
+        kvdb_type::{EntityField, KVDBType},
+        kvdb_value::{KVDBObject, KVDBValue},
+    },
+};
+
+/// Constraints checked on every write of an object whose key starts with `prefix`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapSchema {
+    pub prefix: Key,
+    pub fields: Vec<FieldSchema>,
+    /// Rejects fields that are not listed in the schema.
+    #[serde(default)]
+    pub deny_unknown_fields: bool,
+}
+
+impl MapSchema {
+    pub fn new(prefix: impl Into<Key>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            fields: vec![],
+            deny_unknown_fields: false,
+        }
+    }
+
+    /// Schema with the fields and types of the entity, optional fields are not required.
+    pub fn for_entity<T: KVDBEntity>(prefix: impl Into<Key>) -> Self {
+        T::fields()
+            .into_iter()
+            .fold(Self::new(prefix), |schema, f| schema.field(f.into()))
+    }
+
+    pub fn field(mut self, field: FieldSchema) -> Self {
+        self.fields.retain(|f| f.name != field.name);
+        self.fields.push(field);
+        self
+    }
+
+    pub fn deny_unknown_fields(mut self) -> Self {
+        self.deny_unknown_fields = true;
+        self
+    }
+
+    pub fn covers(&self, key: &Key) -> bool {
+        key.0.starts_with(&self.prefix.0)
+    }
+
+    /// All constraints the object violates.
+    pub fn violations(&self, obj: &KVDBObject) -> Vec<Violation> {
+        let mut out: Vec<Violation> = self
+            .fields
+            .iter()
+            .filter_map(|f| {
+                f.check(obj.get(&f.name)).map(|kind| Violation {
+                    field: f.name.clone(),
+                    kind,
+                })
+            })
+            .collect();
+        if self.deny_unknown_fields {
+            let mut unknown: Vec<_> = obj
+                .keys()
+                .filter(|k| !self.fields.iter().any(|f| f.name == **k))
+                .map(|k| Violation {
+                    field: k.clone(),
+                    kind: ViolationKind::UnknownField,
+                })
+                .collect();
+            unknown.sort_by(|a, b| a.field.cmp(&b.field));
+            out.extend(unknown);
+        }
+        out
+    }
+
+    pub fn validate(&self, key: &Key, obj: &KVDBObject) -> Result<()> {
+        let violations = self.violations(obj);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(MiniKVDBError::SchemaViolation(key.0.clone(), violations))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: Key,
+    /// Accepted type, any type when `None`.
+    pub kvdb_type: Option<KVDBType>,
+    /// Required fields must be present and not null.
+    pub required: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Length of strings in characters, of bytes, lists and maps in elements.
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    pub pattern: Option<Pattern>,
+}
+
+impl FieldSchema {
+    /// Optional field of any type.
+    pub fn new(name: impl Into<Key>) -> Self {
+        Self {
+            name: name.into(),
+            kvdb_type: None,
+            required: false,
+            min: None,
+            max: None,
+            min_len: None,
+            max_len: None,
+            pattern: None,
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn of_type(mut self, kvdb_type: KVDBType) -> Self {
+        self.kvdb_type = Some(kvdb_type);
+        self
+    }
+
+    /// Inclusive range of numeric values.
+    pub fn range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn length(mut self, min: Option<usize>, max: Option<usize>) -> Self {
+        self.min_len = min;
+        self.max_len = max;
+        self
+    }
+
+    /// Strings must match the regular expression.
+    pub fn pattern(mut self, pattern: &str) -> Result<Self> {
+        self.pattern = Some(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    fn check(&self, value: Option<&KVDBValue>) -> Option<ViolationKind> {
+        let value = match value {
+            None | Some(KVDBValue::Null) if self.required => return Some(ViolationKind::Missing),
+            None | Some(KVDBValue::Null) => return None,
+            Some(v) => v,
+        };
+        if let Some(expected) = self.kvdb_type {
+            if value.kvdb_type() != expected {
+                return Some(ViolationKind::WrongType {
+                    expected,
+                    actual: value.kvdb_type(),
+                });
+            }
+        }
+        if let Some(n) = number(value) {
+            if self.min.is_some_and(|min| n < min) || self.max.is_some_and(|max| n > max) {
+                return Some(ViolationKind::OutOfRange {
+                    value: n,
+                    min: self.min,
+                    max: self.max,
+                });
+            }
+        }
+        if let Some(len) = length(value) {
+            let too_short = self.min_len.is_some_and(|min| len < min);
+            let too_long = self.max_len.is_some_and(|max| len > max);
+            if too_short || too_long {
+                return Some(ViolationKind::WrongLength {
+                    len,
+                    min: self.min_len,
+                    max: self.max_len,
+                });
+            }
+        }
+        match (&self.pattern, value) {
+            (Some(pattern), KVDBValue::String(s)) if !pattern.0.is_match(s) => Some(
+                ViolationKind::PatternMismatch(pattern.0.as_str().to_owned()),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// A constraint of the schema violated by a field of an object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub field: Key,
+    pub kind: ViolationKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    /// A required field is missing or null.
+    Missing,
+    WrongType {
+        expected: KVDBType,
+        actual: KVDBType,
+    },
+    OutOfRange {
+        value: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    WrongLength {
+        len: usize,
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// The string does not match the pattern, stored as its source.
+    PatternMismatch(String),
+    /// The field is not listed in a schema that denies unknown fields.
+    UnknownField,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field.0, self.kind)
+    }
+}
+
+impl Display for ViolationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing required field"),
+            Self::WrongType { expected, actual } => {
+                write!(f, "expected {expected}, found {actual}")
+            }
+            Self::OutOfRange { value, min, max } => {
+                write!(f, "{value} is out of range {}", bounds(*min, *max))
+            }
+            Self::WrongLength { len, min, max } => {
+                write!(f, "length {len} is out of range {}", bounds(*min, *max))
+            }
+            Self::PatternMismatch(pattern) => write!(f, "does not match pattern `{pattern}`"),
+            Self::UnknownField => write!(f, "unknown field"),
+        }
+    }
+}
+
+impl From<EntityField> for FieldSchema {
+    fn from(value: EntityField) -> Self {
+        Self {
+            kvdb_type: value.kvdb_type,
+            required: value.required,
+            ..Self::new(value.name)
+        }
+    }
+}
+
+/// Compiled regular expression, stored as its source.
+#[derive(Debug, Clone)]
+pub struct Pattern(pub Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(Self)
+            .map_err(|e| MiniKVDBError::InvalidPattern(e.to_string()))
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Self)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+fn number(value: &KVDBValue) -> Option<f64> {
+    match value {
+        KVDBValue::Int(v) => Some(*v as f64),
+        KVDBValue::Float(v) => Some(*v as f64),
+        #[cfg(feature = "big-types")]
+        KVDBValue::Long(v) => Some(*v as f64),
+        #[cfg(feature = "big-types")]
+        KVDBValue::Double(v) => Some(*v),
+        #[cfg(feature = "big-types")]
+        KVDBValue::UInt(v) => Some(*v as f64),
+        #[cfg(feature = "big-types")]
+        KVDBValue::ULong(v) => Some(*v as f64),
+        #[cfg(feature = "big-types")]
+        KVDBValue::Int128(v) => Some(*v as f64),
+        #[cfg(feature = "big-types")]
+        KVDBValue::UInt128(v) => Some(*v as f64),
+        #[cfg(feature = "decimal")]
+        KVDBValue::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_f64(v),
+        _ => None,
+    }
+}
+
+fn length(value: &KVDBValue) -> Option<usize> {
+    match value {
+        KVDBValue::String(v) => Some(v.chars().count()),
+        KVDBValue::Bytes(v) => Some(v.len()),
+        KVDBValue::List(v) => Some(v.len()),
+        KVDBValue::Map(v) => Some(v.len()),
+        _ => None,
+    }
+}
+
+fn bounds<T: std::fmt::Display>(min: Option<T>, max: Option<T>) -> String {
+    let show = |v: Option<T>| v.map_or_else(String::new, |v| v.to_string());
+    format!("{}..={}", show(min), show(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> MapSchema {
+        MapSchema::new("user:")
+            .field(
+                FieldSchema::new("name")
+                    .required()
+                    .of_type(KVDBType::String)
+                    .length(Some(2), Some(10)),
+            )
+            .field(
+                FieldSchema::new("age")
+                    .of_type(KVDBType::Int)
+                    .range(Some(0.0), Some(150.0)),
+            )
+            .field(FieldSchema::new("email").pattern(r"^[^@]+@[^@]+$").unwrap())
+    }
+
+    #[test]
+    fn accepts_valid_objects() {
+        let obj: KVDBObject = [("name".into(), "tom".into()), ("age".into(), 30.into())].into();
+        assert!(schema().violations(&obj).is_empty());
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let obj: KVDBObject = [
+            ("age".into(), 200.into()),
+            ("email".into(), "nope".into()),
+            ("extra".into(), true.into()),
+        ]
+        .into();
+        let violations = schema().deny_unknown_fields().violations(&obj);
+        assert_eq!(
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "name: missing required field",
+                "age: 200 is out of range 0..=150",
+                "email: does not match pattern `^[^@]+@[^@]+$`",
+                "extra: unknown field",
+            ]
+        );
+        assert_eq!(
+            violations[1],
+            Violation {
+                field: "age".into(),
+                kind: ViolationKind::OutOfRange {
+                    value: 200.0,
+                    min: Some(0.0),
+                    max: Some(150.0),
+                },
+            }
+        );
+        assert_eq!(violations[3].kind, ViolationKind::UnknownField);
+
+        let obj: KVDBObject = [("name".into(), 1.into()), ("age".into(), KVDBValue::Null)].into();
+        let violations = schema().violations(&obj);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].to_string(),
+            "name: expected String, found Int"
+        );
+        assert_eq!(
+            violations[0].kind,
+            ViolationKind::WrongType {
+                expected: KVDBType::String,
+                actual: KVDBType::Int,
+            }
+        );
+
+        let obj: KVDBObject = [("name".into(), "t".into())].into();
+        assert_eq!(
+            schema().violations(&obj),
+            vec![Violation {
+                field: "name".into(),
+                kind: ViolationKind::WrongLength {
+                    len: 1,
+                    min: Some(2),
+                    max: Some(10),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(FieldSchema::new("name").pattern("(").is_err());
+    }
+}
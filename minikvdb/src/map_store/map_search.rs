@@ -0,0 +1,458 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::minikvdb::{
+    kvdb_key::Key,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// Positions left between fields, so that phrases with fewer stop words never match
+/// across fields.
+const FIELD_GAP: usize = 100;
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Splits text into lowercase alphanumeric tokens, skipping stop words.
+/// Stop words still advance the position, so phrase queries keep their spacing.
+pub fn tokenize(text: &str) -> Vec<(usize, String)> {
+    words(text)
+        .enumerate()
+        .filter(|(_, t)| !STOP_WORDS.contains(&t.as_str()))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchIndexDefinition {
+    pub prefix: Key,
+    pub fields: Vec<Key>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchClause {
+    Term(String),
+    Prefix(String),
+    Phrase(Vec<(usize, String)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    pub clauses: Vec<SearchClause>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    /// Parses a query where every clause has to match. Words ending with `*` are prefix
+    /// queries and text in double quotes is a phrase query.
+    pub fn new(text: &str) -> Self {
+        let mut clauses = vec![];
+        for (i, part) in text.split('"').enumerate() {
+            if i % 2 == 1 {
+                let tokens = tokenize(part);
+                if !tokens.is_empty() {
+                    clauses.push(SearchClause::Phrase(tokens));
+                }
+                continue;
+            }
+            for word in part.split_whitespace() {
+                if let Some(stem) = word.strip_suffix('*') {
+                    let stem = stem.to_lowercase();
+                    if !stem.is_empty() {
+                        clauses.push(SearchClause::Prefix(stem));
+                    }
+                    continue;
+                }
+                let mut tokens = tokenize(word);
+                match tokens.len() {
+                    0 => {}
+                    1 => clauses.push(SearchClause::Term(tokens.remove(0).1)),
+                    _ => clauses.push(SearchClause::Phrase(tokens)),
+                }
+            }
+        }
+
+        Self {
+            clauses,
+            offset: 0,
+            limit: None,
+        }
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl From<&str> for SearchQuery {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub key: Key,
+    pub score: f64,
+}
+
+/// Positions of a term in every document containing it.
+/// With the `persistent` feature postings and documents are persistent maps, which
+/// snapshots share without cloning them.
+#[cfg(not(feature = "persistent"))]
+type Postings = HashMap<Key, Vec<usize>>;
+#[cfg(feature = "persistent")]
+type Postings = imbl::HashMap<Key, Vec<usize>>;
+
+#[cfg(not(feature = "persistent"))]
+type Terms = std::collections::BTreeMap<String, Postings>;
+#[cfg(feature = "persistent")]
+type Terms = imbl::OrdMap<String, Postings>;
+
+// Document length and the distinct terms it contains.
+#[cfg(not(feature = "persistent"))]
+type Docs = HashMap<Key, (usize, Vec<String>)>;
+#[cfg(feature = "persistent")]
+type Docs = imbl::HashMap<Key, (usize, Vec<String>)>;
+
+#[derive(Debug, Clone)]
+pub struct SearchIndex {
+    definition: SearchIndexDefinition,
+    postings: Terms,
+    docs: Docs,
+    total_len: usize,
+}
+
+impl SearchIndex {
+    pub fn new(definition: SearchIndexDefinition) -> Self {
+        Self {
+            definition,
+            postings: Terms::new(),
+            docs: Docs::new(),
+            total_len: 0,
+        }
+    }
+
+    pub fn definition(&self) -> &SearchIndexDefinition {
+        &self.definition
+    }
+
+    pub fn insert(&mut self, key: &Key, obj: &KVDBObject) {
+        if !key.0.starts_with(&self.definition.prefix.0) {
+            return;
+        }
+
+        let mut offset = 0;
+        let mut len = 0;
+        let mut terms = vec![];
+        for field in self.definition.fields.iter() {
+            let Some(KVDBValue::String(text)) = obj.get(field) else {
+                continue;
+            };
+            let tokens = tokenize(text);
+            for (pos, token) in tokens.iter() {
+                let positions = self
+                    .postings
+                    .entry(token.clone())
+                    .or_default()
+                    .entry(key.clone())
+                    .or_default();
+                if positions.is_empty() {
+                    terms.push(token.clone());
+                }
+                positions.push(offset + pos);
+            }
+            len += tokens.len();
+            offset += words(text).count() + FIELD_GAP;
+        }
+
+        self.docs.insert(key.clone(), (len, terms));
+        self.total_len += len;
+    }
+
+    pub fn remove(&mut self, key: &Key) {
+        let Some((len, terms)) = self.docs.remove(key) else {
+            return;
+        };
+        self.total_len -= len;
+        for term in terms {
+            if let Some(docs) = self.postings.get_mut(&term) {
+                docs.remove(key);
+                if docs.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    fn bm25(&self, stats: &CorpusStats, term: &str, key: &Key, tf: usize) -> f64 {
+        let n = stats.docs as f64;
+        let df = stats.df.get(term).copied().unwrap_or_default() as f64;
+        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+        let avg_len = stats.total_len as f64 / n.max(1.0);
+        let len = self.docs.get(key).map_or(0, |(len, _)| *len) as f64;
+        let tf = tf as f64;
+        idf * tf * (BM25_K1 + 1.0)
+            / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len.max(1.0)))
+    }
+
+    /// Postings of every term the clause can match.
+    fn clause_postings<'a>(
+        &'a self,
+        clause: &'a SearchClause,
+    ) -> Box<dyn Iterator<Item = (&'a String, &'a Postings)> + 'a> {
+        match clause {
+            SearchClause::Term(term) => Box::new(self.postings.get_key_value(term).into_iter()),
+            SearchClause::Prefix(stem) => Box::new(
+                self.postings
+                    .range(stem.clone()..)
+                    .take_while(|(term, _)| term.starts_with(stem.as_str())),
+            ),
+            SearchClause::Phrase(tokens) => Box::new(
+                tokens
+                    .iter()
+                    .filter_map(|(_, t)| self.postings.get_key_value(t)),
+            ),
+        }
+    }
+
+    /// Statistics of the indexed documents needed to score the query.
+    pub fn stats(&self, query: &SearchQuery) -> CorpusStats {
+        CorpusStats {
+            docs: self.docs.len(),
+            total_len: self.total_len,
+            df: query
+                .clauses
+                .iter()
+                .flat_map(|c| self.clause_postings(c))
+                .map(|(term, docs)| (term.clone(), docs.len()))
+                .collect(),
+        }
+    }
+
+    /// Scores every document matching the clause.
+    fn score_clause(&self, clause: &SearchClause, stats: &CorpusStats) -> HashMap<Key, f64> {
+        let mut scores: HashMap<Key, f64> = HashMap::new();
+        match clause {
+            SearchClause::Term(_) | SearchClause::Prefix(_) => {
+                for (term, docs) in self.clause_postings(clause) {
+                    for (key, positions) in docs {
+                        *scores.entry(key.clone()).or_default() +=
+                            self.bm25(stats, term, key, positions.len());
+                    }
+                }
+            }
+            SearchClause::Phrase(tokens) => {
+                let Some(postings) = tokens
+                    .iter()
+                    .map(|(_, t)| self.postings.get(t))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    return scores;
+                };
+                let first_pos = tokens[0].0;
+                for (key, starts) in postings[0] {
+                    let occurrences = starts
+                        .iter()
+                        .filter(|start| {
+                            tokens
+                                .iter()
+                                .zip(postings.iter())
+                                .skip(1)
+                                .all(|((p, _), docs)| {
+                                    docs.get(key)
+                                        .is_some_and(|pos| pos.contains(&(**start + p - first_pos)))
+                                })
+                        })
+                        .count();
+                    if occurrences > 0 {
+                        let score = tokens
+                            .iter()
+                            .map(|(_, term)| self.bm25(stats, term, key, occurrences))
+                            .sum();
+                        scores.insert(key.clone(), score);
+                    }
+                }
+            }
+        }
+        scores
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchHit> {
+        rank(self.matches(query, &self.stats(query)), query)
+    }
+
+    /// Unordered hits scored with the statistics of a larger corpus, such as all shards
+    /// of a store.
+    pub fn matches(&self, query: &SearchQuery, stats: &CorpusStats) -> Vec<SearchHit> {
+        let mut clauses = query.clauses.iter();
+        let Some(first) = clauses.next() else {
+            return vec![];
+        };
+
+        let mut scores = self.score_clause(first, stats);
+        for clause in clauses {
+            let other = self.score_clause(clause, stats);
+            scores.retain(|key, score| {
+                if let Some(s) = other.get(key) {
+                    *score += s;
+                    true
+                } else {
+                    false
+                }
+            });
+        }
+
+        scores
+            .into_iter()
+            .map(|(key, score)| SearchHit { key, score })
+            .collect()
+    }
+}
+
+/// Document count, total length and document frequency of the query terms.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorpusStats {
+    docs: usize,
+    total_len: usize,
+    df: HashMap<String, usize>,
+}
+
+impl CorpusStats {
+    pub fn merge(&mut self, other: CorpusStats) {
+        self.docs += other.docs;
+        self.total_len += other.total_len;
+        for (term, df) in other.df {
+            *self.df.entry(term).or_default() += df;
+        }
+    }
+}
+
+/// Orders hits by score and paginates them.
+pub fn rank(mut hits: Vec<SearchHit>, query: &SearchQuery) -> Vec<SearchHit> {
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.key.cmp(&b.key)));
+    hits.into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(name: &str, description: &str) -> KVDBObject {
+        [
+            ("name".into(), name.into()),
+            ("description".into(), description.into()),
+        ]
+        .into()
+    }
+
+    fn seeded_index() -> SearchIndex {
+        let mut idx = SearchIndex::new(SearchIndexDefinition {
+            prefix: "product:".into(),
+            fields: vec!["name".into(), "description".into()],
+        });
+        idx.insert(
+            &"product:1".into(),
+            &product("Red running shoes", "Light shoes for the road"),
+        );
+        idx.insert(
+            &"product:2".into(),
+            &product("Blue shoes", "Running is not their purpose"),
+        );
+        idx.insert(
+            &"product:3".into(),
+            &product("Red hat", "A hat for the sun"),
+        );
+        idx.insert(&"other:1".into(), &product("Red shoes", "Not indexed"));
+        idx
+    }
+
+    fn keys(hits: Vec<SearchHit>) -> Vec<Key> {
+        hits.into_iter().map(|h| h.key).collect()
+    }
+
+    #[test]
+    fn tokenizes_lowercase_without_stop_words() {
+        assert_eq!(
+            tokenize("The Quick, brown-fox!"),
+            vec![
+                (1, "quick".to_owned()),
+                (2, "brown".to_owned()),
+                (3, "fox".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn ranks_documents_matching_all_terms() {
+        let idx = seeded_index();
+        let hits = keys(idx.search(&"RED shoes".into()));
+        assert_eq!(hits, vec![Key::from("product:1")]);
+
+        // More occurrences of the term rank higher.
+        let hits = keys(idx.search(&"shoes".into()));
+        assert_eq!(hits, vec![Key::from("product:1"), Key::from("product:2")]);
+    }
+
+    #[test]
+    fn matches_prefix_and_phrase_queries() {
+        let idx = seeded_index();
+        let hits = keys(idx.search(&"run*".into()));
+        assert_eq!(hits.len(), 2);
+
+        let hits = keys(idx.search(&"\"running shoes\"".into()));
+        assert_eq!(hits, vec![Key::from("product:1")]);
+
+        // Phrases do not match across field boundaries.
+        let hits = keys(idx.search(&"\"shoes running\"".into()));
+        assert!(hits.is_empty());
+
+        // Also not when the first field ends with stop words.
+        let mut idx = seeded_index();
+        idx.insert(
+            &"product:4".into(),
+            &product("Shoes for the", "running on the road"),
+        );
+        let hits = keys(idx.search(&"\"shoes for running\"".into()));
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn removes_documents() {
+        let mut idx = seeded_index();
+        idx.remove(&"product:1".into());
+        assert_eq!(
+            keys(idx.search(&"red".into())),
+            vec![Key::from("product:3")]
+        );
+    }
+
+    #[test]
+    fn paginates_results() {
+        let idx = seeded_index();
+        let all = keys(idx.search(&"red".into()));
+        let page = keys(idx.search(&SearchQuery::new("red").offset(1).limit(1)));
+        assert_eq!(page, all[1..2].to_vec());
+    }
+}
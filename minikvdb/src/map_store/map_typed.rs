@@ -0,0 +1,128 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use crate::{
+    error::{MiniKVDBError, Result},
+    minikvdb::{kvdb_entity::KVDBEntity, kvdb_key::Key, kvdb_value::KVDBValue, MiniKVDB},
+};
+
+/// Key holding an entity stored as a map object.
+#[derive(Debug)]
+pub struct TypedHash<T: KVDBEntity> {
+    key: Key,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: KVDBEntity> Clone for TypedHash<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+}
+
+impl<T: KVDBEntity> TypedHash<T> {
+    pub fn new(key: impl Into<Key>) -> Self {
+        Self {
+            key: key.into(),
+            _type: PhantomData,
+        }
+    }
+
+    /// Handle for a nested namespace, e.g. `user` -> `user:42`.
+    pub fn ns(&self, value: impl Display) -> Self {
+        Self::new(format!("{}:{value}", self.key.0))
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub fn get(&self, db: &MiniKVDB) -> Result<Option<T>> {
+        db.hash_get_object(self.key.clone())
+    }
+
+    pub fn set(&self, db: &MiniKVDB, value: T) -> Result<()> {
+        db.hash_set(self.key.clone(), value)?;
+        Ok(())
+    }
+
+    pub fn delete(&self, db: &MiniKVDB) -> Result<bool> {
+        Ok(db.hash_delete(self.key.clone())?.is_some())
+    }
+
+    pub fn exists(&self, db: &MiniKVDB) -> Result<bool> {
+        db.hash_contains_key(self.key.clone())
+    }
+
+    pub fn get_field<V>(&self, db: &MiniKVDB, field: impl Into<Key>) -> Result<Option<V>>
+    where
+        V: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        db.hash_get_as(self.key.clone(), field)
+    }
+}
+
+impl<T: KVDBEntity> From<Key> for TypedHash<T> {
+    fn from(value: Key) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::minikvdb::kvdb_value::KVDBObject;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        name: String,
+    }
+
+    impl From<User> for KVDBObject {
+        fn from(value: User) -> Self {
+            [("name".into(), value.name.into())].into()
+        }
+    }
+
+    impl TryFrom<KVDBObject> for User {
+        type Error = MiniKVDBError;
+        fn try_from(value: KVDBObject) -> Result<Self> {
+            Ok(Self {
+                name: value
+                    .get("name")
+                    .ok_or(MiniKVDBError::MissingField("name".to_owned()))?
+                    .try_into()?,
+            })
+        }
+    }
+
+    impl KVDBEntity for User {}
+
+    #[test]
+    fn stores_typed_entities() {
+        let db = MiniKVDB::default();
+        let users: TypedHash<User> = TypedHash::new("user");
+        let tom = users.ns(1);
+
+        assert!(!tom.exists(&db).unwrap());
+        tom.set(&db, User { name: "tom".into() }).unwrap();
+        assert_eq!(tom.get(&db).unwrap(), Some(User { name: "tom".into() }));
+        assert_eq!(
+            tom.get_field::<String>(&db, "name").unwrap(),
+            Some("tom".into())
+        );
+        assert!(tom.delete(&db).unwrap());
+        assert_eq!(tom.get(&db).unwrap(), None);
+    }
+
+    #[test]
+    fn reports_key_of_invalid_objects() {
+        let db = MiniKVDB::default();
+        let users: TypedHash<User> = TypedHash::new("user");
+        let obj: KVDBObject = [("age".into(), KVDBValue::Int(1))].into();
+        db.hash_set("user:1", obj).unwrap();
+
+        let err = users.ns(1).get(&db).unwrap_err();
+        assert!(matches!(&err, MiniKVDBError::InvalidObject { key, .. } if key == "user:1"));
+        assert!(matches!(err.root(), MiniKVDBError::MissingField(f) if f == "name"));
+    }
+}
@@ -1,64 +1,700 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+#[cfg(feature = "history")]
+use std::time::SystemTime;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    ops::{Bound, Deref, RangeBounds},
+};
 
+#[cfg(feature = "history")]
+use crate::minikvdb::kvdb_history::{self, History};
+#[cfg(feature = "persistent")]
+use crate::minikvdb::kvdb_snapshot::{shard, MiniKVDBSnapshot};
 use crate::{
     error::{MiniKVDBError, Result},
-    minikvdb::{kvdb_key::Key, kvdb_value::KVDBValue, KVDBStore, MiniKVDB},
+    minikvdb::{
+        kvdb_key::Key,
+        kvdb_lock::{Journal, Journaled},
+        kvdb_path::KVDBPath,
+        kvdb_serde,
+        kvdb_shard::{shard_index, ShardEntries, ShardedStore},
+        kvdb_snapshot::scan,
+        kvdb_value::KVDBValue,
+        KVDBStore, MiniKVDB,
+    },
     prelude::KVDBObject,
 };
 
-use self::map_command::{
-    ContainsKeyCommand, DeleteCommand, GetAllCommand, GetCommand, GetObjectCommand, SetCommand,
+use self::{
+    map_aggregate::Aggregation,
+    map_command::{
+        AggregateCommand, ContainsKeyCommand, CountCommand, CreateIndexCommand, DeleteCommand,
+        DropIndexCommand, FindCommand, FindRangeCommand, GetAllCommand, GetCommand,
+        GetObjectCommand, GetPathCommand, MigrateCommand, QueryCommand, RegisterMigrationCommand,
+        SetCommand, SetPathCommand,
+    },
+    map_index::{MapIndex, MapIndexDefinition, MapIndexKind},
+    map_migrate::{MigrationJob, MigrationProgress, MigrationReport, Migrations},
+    map_query::{MapQuery, Predicate},
 };
 
+pub mod map_aggregate;
 pub mod map_command;
+pub mod map_index;
+pub mod map_migrate;
+pub mod map_query;
+#[cfg(feature = "schema")]
+pub mod map_schema;
+#[cfg(feature = "search")]
+pub mod map_search;
+pub mod map_typed;
+
+/// Objects by key. With the `persistent` feature shards are persistent maps, which are
+/// copied without cloning their objects when written while shared with a snapshot.
+#[cfg(not(feature = "persistent"))]
+type Objects = HashMap<Key, KVDBObject>;
+#[cfg(feature = "persistent")]
+type Objects = imbl::HashMap<Key, KVDBObject>;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct MapStore(HashMap<Key, KVDBObject>);
+#[serde(from = "MapStoreFormat", into = "MapStoreRepr")]
+pub struct MapStore {
+    data: Objects,
+    indexes: Vec<MapIndex>,
+    #[cfg(feature = "search")]
+    search_indexes: Vec<map_search::SearchIndex>,
+    #[cfg(feature = "schema")]
+    schemas: Vec<map_schema::MapSchema>,
+    migrations: Migrations,
+    #[cfg(feature = "history")]
+    history: History<KVDBObject>,
+    journal: Journal<(Key, Option<KVDBObject>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MapStoreRepr {
+    data: Objects,
+    #[serde(default)]
+    indexes: Vec<MapIndexDefinition>,
+    #[cfg(feature = "search")]
+    #[serde(default)]
+    search_indexes: Vec<map_search::SearchIndexDefinition>,
+    #[cfg(feature = "schema")]
+    #[serde(default)]
+    schemas: Vec<map_schema::MapSchema>,
+}
+
+/// `MapStoreRepr` of the shards of a store, borrowing their objects and definitions.
+#[derive(Serialize)]
+struct ShardsRepr<'a> {
+    data: ShardEntries<'a, Objects>,
+    indexes: Vec<&'a MapIndexDefinition>,
+    #[cfg(feature = "search")]
+    search_indexes: Vec<&'a map_search::SearchIndexDefinition>,
+    #[cfg(feature = "schema")]
+    schemas: &'a [map_schema::MapSchema],
+}
+
+/// Stores persisted before indexes were added are the bare map of objects.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MapStoreFormat {
+    Legacy(Objects),
+    Current(MapStoreRepr),
+}
+
+impl From<MapStoreFormat> for MapStore {
+    fn from(value: MapStoreFormat) -> Self {
+        match value {
+            MapStoreFormat::Legacy(data) => Self {
+                data,
+                ..Default::default()
+            },
+            MapStoreFormat::Current(repr) => repr.into(),
+        }
+    }
+}
+
+impl From<MapStoreRepr> for MapStore {
+    fn from(value: MapStoreRepr) -> Self {
+        let mut store = Self {
+            data: value.data,
+            #[cfg(feature = "schema")]
+            schemas: value.schemas,
+            ..Default::default()
+        };
+        for definition in value.indexes {
+            store.create_index(CreateIndexCommand(definition));
+        }
+        #[cfg(feature = "search")]
+        for definition in value.search_indexes {
+            store.create_search_index(map_command::CreateSearchIndexCommand(definition));
+        }
+        store
+    }
+}
+
+impl From<MapStore> for MapStoreRepr {
+    fn from(value: MapStore) -> Self {
+        Self {
+            data: value.data,
+            indexes: value
+                .indexes
+                .into_iter()
+                .map(|i| i.definition().clone())
+                .collect(),
+            #[cfg(feature = "search")]
+            search_indexes: value
+                .search_indexes
+                .into_iter()
+                .map(|i| i.definition().clone())
+                .collect(),
+            #[cfg(feature = "schema")]
+            schemas: value.schemas,
+        }
+    }
+}
 
 impl KVDBStore for MapStore {}
 
+impl ShardedStore for MapStore {
+    fn empty_like(&self) -> Self {
+        Self {
+            data: Objects::new(),
+            indexes: self
+                .indexes
+                .iter()
+                .map(|i| MapIndex::new(i.definition().clone()))
+                .collect(),
+            #[cfg(feature = "search")]
+            search_indexes: self
+                .search_indexes
+                .iter()
+                .map(|i| map_search::SearchIndex::new(i.definition().clone()))
+                .collect(),
+            #[cfg(feature = "schema")]
+            schemas: self.schemas.clone(),
+            migrations: self.migrations.clone(),
+            #[cfg(feature = "history")]
+            history: self.history.empty_like(),
+            journal: Journal::default(),
+        }
+    }
+
+    fn split_into(self, shards: &mut [Self], shard: impl Fn(&Key) -> usize) {
+        #[cfg(feature = "history")]
+        {
+            let histories = self.history.split(shards.len(), &shard);
+            for (store, history) in shards.iter_mut().zip(histories) {
+                store.history.merge(history);
+            }
+        }
+        for (k, v) in self.data {
+            shards[shard(&k)].put(k, v);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        for (k, v) in other.data {
+            self.put(k, v);
+        }
+        #[cfg(feature = "history")]
+        self.history.merge(other.history);
+    }
+
+    fn key_count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn has_key(&self, key: &Key) -> bool {
+        self.data.contains_key(key)
+    }
+
+    /// The object is validated against the schemas of `other` before it is moved.
+    fn move_entry(&mut self, key: &Key, other: &mut Self) -> Result<()> {
+        #[cfg(feature = "schema")]
+        if let Some(obj) = self.data.get(key) {
+            other.validate(key, obj)?;
+        }
+        if let Some(obj) = self.delete(DeleteCommand(key.clone())) {
+            other.set(SetCommand(key.clone(), obj));
+        }
+        Ok(())
+    }
+
+    /// Definitions are the same on every shard and are taken from the first one.
+    fn serialize_shards<S: Serializer>(
+        shards: &[&Self],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let first = shards[0];
+        ShardsRepr {
+            data: ShardEntries(shards.iter().map(|s| &s.data).collect()),
+            indexes: first.indexes.iter().map(|i| i.definition()).collect(),
+            #[cfg(feature = "search")]
+            search_indexes: first
+                .search_indexes
+                .iter()
+                .map(|i| i.definition())
+                .collect(),
+            #[cfg(feature = "schema")]
+            schemas: &first.schemas,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Journaled for MapStore {
+    fn set_journaling(&mut self, enabled: bool) {
+        self.journal.set_enabled(enabled);
+        #[cfg(feature = "history")]
+        self.history.set_journaling(enabled);
+    }
+
+    /// Previous objects are written back directly, the versions recorded by the write
+    /// are dropped.
+    fn rollback(&mut self) {
+        for (k, old) in self.journal.take() {
+            match old {
+                Some(obj) => self.put(k, obj),
+                None => self.remove(&k),
+            };
+        }
+        #[cfg(feature = "history")]
+        self.history.rollback();
+    }
+}
+
 impl MapStore {
     pub fn set(&mut self, cmd: impl Into<SetCommand>) -> Option<KVDBObject> {
         let SetCommand(k, v) = cmd.into();
-        self.0.insert(k.to_owned(), v)
+        #[cfg(feature = "history")]
+        self.history.record(&k, Some(&v));
+        let old = self.put(k.clone(), v);
+        self.journal.record(|| (k, old.clone()));
+        old
+    }
+
+    /// Stores the object and updates the indexes, without journaling or history.
+    fn put(&mut self, k: Key, v: KVDBObject) -> Option<KVDBObject> {
+        let old = self.data.insert(k.to_owned(), v);
+        let new = &self.data[&k];
+        for index in self.indexes.iter_mut() {
+            if let Some(old) = &old {
+                index.remove(&k, old);
+            }
+            index.insert(&k, new);
+        }
+        #[cfg(feature = "search")]
+        for index in self.search_indexes.iter_mut() {
+            index.remove(&k);
+            index.insert(&k, new);
+        }
+        old
     }
 
     pub fn get(&self, cmd: impl Into<GetCommand>) -> Option<KVDBValue> {
         let GetCommand(k, field) = cmd.into();
-        if let Some(store) = self.0.get(&k) {
+        if let Some(store) = self.data.get(&k) {
             store.get(&field).cloned()
         } else {
             None
         }
     }
 
+    pub fn get_path(&self, cmd: impl Into<GetPathCommand>) -> Option<KVDBValue> {
+        let GetPathCommand(k, path) = cmd.into();
+        self.data.get(&k).and_then(|obj| path.get(obj)).cloned()
+    }
+
+    /// Sets a nested value, creating the object if it does not exist. Returns the replaced value.
+    pub fn set_path(&mut self, cmd: impl Into<SetPathCommand>) -> Result<Option<KVDBValue>> {
+        let SetPathCommand(k, path, v) = cmd.into();
+        let mut obj = self.data.get(&k).cloned().unwrap_or_default();
+        let old = path.set(&mut obj, v)?;
+        #[cfg(feature = "schema")]
+        self.validate(&k, &obj)?;
+        self.set(SetCommand(k, obj));
+        Ok(old)
+    }
+
     pub fn contains_key(&self, cmd: impl Into<ContainsKeyCommand>) -> bool {
         let ContainsKeyCommand(key) = cmd.into();
-        self.0.contains_key(&key)
+        self.data.contains_key(&key)
     }
 
     pub fn get_all(&self, cmd: impl Into<GetAllCommand>) -> Option<HashMap<Key, KVDBValue>> {
         let GetAllCommand(k) = cmd.into();
-        self.0.get(&k).cloned()
+        self.data.get(&k).cloned()
+    }
+
+    /// Number of objects whose key starts with the prefix.
+    pub fn count(&self, cmd: impl Into<CountCommand>) -> usize {
+        let CountCommand(prefix) = cmd.into();
+        self.data
+            .keys()
+            .filter(|k| k.0.starts_with(&prefix.0))
+            .count()
     }
 
     pub fn get_object<T: TryFrom<KVDBObject>>(
         &self,
         cmd: impl Into<GetObjectCommand>,
-    ) -> Result<Option<T>> {
+    ) -> Result<Option<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
         let GetObjectCommand(k) = cmd.into();
-        if let Some(obj) = self.0.get(&k).cloned() {
-            Ok(Some(
-                obj.try_into().map_err(|_| MiniKVDBError::InvalidObject)?,
-            ))
-        } else {
-            Ok(None)
+        self.data
+            .get(&k)
+            .cloned()
+            .map(|obj| self.entity(&k, obj))
+            .transpose()
+    }
+
+    /// Objects of older versions are upgraded when read as entities and written back
+    /// by `migrate`. Raw reads, finds, queries and aggregations see the stored version,
+    /// which is what the indexes hold.
+    pub fn register_migration(&mut self, cmd: impl Into<RegisterMigrationCommand>) {
+        let RegisterMigrationCommand(prefix, from, migration) = cmd.into();
+        self.migrations.register(prefix, from, migration);
+    }
+
+    /// The upgraded object without storing it, `None` when it is missing or up to date.
+    pub fn pending_migration(&self, cmd: impl Into<MigrateCommand>) -> Result<Option<KVDBObject>> {
+        let MigrateCommand(k) = cmd.into();
+        let Some(obj) = self.data.get(&k) else {
+            return Ok(None);
+        };
+        let migrated = self.migrations.apply(&k, obj)?;
+        #[cfg(feature = "schema")]
+        if let Some(migrated) = &migrated {
+            self.validate(&k, migrated)?;
         }
+        Ok(migrated)
+    }
+
+    pub fn migrate(&mut self, cmd: impl Into<MigrateCommand>) -> Result<Option<KVDBObject>> {
+        let MigrateCommand(k) = cmd.into();
+        let migrated = self.pending_migration(MigrateCommand(k.clone()))?;
+        if let Some(migrated) = &migrated {
+            self.set(SetCommand(k, migrated.clone()));
+        }
+        Ok(migrated)
+    }
+
+    fn keys(&self, prefix: &Key) -> Vec<Key> {
+        let mut keys: Vec<Key> = self
+            .data
+            .keys()
+            .filter(|k| k.0.starts_with(&prefix.0))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
     }
 
     pub fn delete(&mut self, cmd: impl Into<DeleteCommand>) -> Option<KVDBObject> {
-        self.0.remove(&cmd.into().0)
+        let DeleteCommand(k) = cmd.into();
+        let old = self.remove(&k);
+        if let Some(old) = &old {
+            self.journal.record(|| (k.clone(), Some(old.clone())));
+            #[cfg(feature = "history")]
+            self.history.record(&k, None);
+        }
+        old
+    }
+
+    /// Removes the object and its index entries, without journaling or history.
+    fn remove(&mut self, k: &Key) -> Option<KVDBObject> {
+        let old = self.data.remove(k);
+        if let Some(old) = &old {
+            for index in self.indexes.iter_mut() {
+                index.remove(k, old);
+            }
+            #[cfg(feature = "search")]
+            for index in self.search_indexes.iter_mut() {
+                index.remove(k);
+            }
+        }
+        old
+    }
+
+    /// Keeps versions of the objects under the prefix, starting with their current state.
+    #[cfg(feature = "history")]
+    pub fn enable_history(&mut self, cmd: impl Into<map_command::EnableHistoryCommand>) {
+        let map_command::EnableHistoryCommand(prefix, retention) = cmd.into();
+        self.history.enable(prefix, retention, self.data.iter());
+    }
+
+    #[cfg(feature = "history")]
+    pub fn disable_history(&mut self, cmd: impl Into<map_command::DisableHistoryCommand>) -> bool {
+        let map_command::DisableHistoryCommand(prefix) = cmd.into();
+        self.history.disable(&prefix)
+    }
+
+    #[cfg(feature = "history")]
+    pub fn versions(
+        &self,
+        cmd: impl Into<map_command::VersionsCommand>,
+    ) -> Vec<kvdb_history::Version<KVDBObject>> {
+        let map_command::VersionsCommand(k) = cmd.into();
+        self.history.versions(&k)
+    }
+
+    #[cfg(feature = "history")]
+    pub fn get_all_as_of(
+        &self,
+        cmd: impl Into<map_command::GetAsOfCommand>,
+    ) -> Result<Option<KVDBObject>> {
+        let map_command::GetAsOfCommand(k, at) = cmd.into();
+        self.history.as_of(&k, at)
+    }
+
+    /// Sets the object to its state at the time, deleting it if it did not exist then.
+    /// The restored object is validated and recorded as a new version.
+    #[cfg(feature = "history")]
+    pub fn restore(
+        &mut self,
+        cmd: impl Into<map_command::RestoreCommand>,
+    ) -> Result<Option<KVDBObject>> {
+        let map_command::RestoreCommand(k, at) = cmd.into();
+        let obj = self.history.as_of(&k, at)?;
+        match &obj {
+            Some(obj) => {
+                #[cfg(feature = "schema")]
+                self.validate(&k, obj)?;
+                self.set(SetCommand(k, obj.clone()));
+            }
+            None => {
+                self.delete(DeleteCommand(k));
+            }
+        }
+        Ok(obj)
+    }
+
+    pub fn create_index(&mut self, cmd: impl Into<CreateIndexCommand>) {
+        let CreateIndexCommand(definition) = cmd.into();
+        self.indexes.retain(|i| {
+            i.definition().prefix != definition.prefix || i.definition().field != definition.field
+        });
+
+        let mut index = MapIndex::new(definition);
+        for (k, v) in self.data.iter() {
+            index.insert(k, v);
+        }
+        self.indexes.push(index);
+    }
+
+    pub fn drop_index(&mut self, cmd: impl Into<DropIndexCommand>) -> bool {
+        let DropIndexCommand(prefix, field) = cmd.into();
+        let len = self.indexes.len();
+        self.indexes
+            .retain(|i| i.definition().prefix != prefix || i.definition().field != field);
+        len != self.indexes.len()
+    }
+
+    pub fn indexes(&self) -> Vec<MapIndexDefinition> {
+        self.indexes
+            .iter()
+            .map(|i| i.definition().clone())
+            .collect()
+    }
+
+    fn index_for(&self, prefix: &Key, field: &Key) -> Option<&MapIndex> {
+        self.indexes
+            .iter()
+            .find(|i| i.definition().prefix == *prefix && i.definition().field == *field)
+    }
+
+    pub fn find(&self, cmd: impl Into<FindCommand>) -> Vec<Key> {
+        let FindCommand(prefix, field, value) = cmd.into();
+        if let Some(index) = self.index_for(&prefix, &field) {
+            return index.find(&value);
+        }
+
+        let mut keys: Vec<Key> = self
+            .data
+            .iter()
+            .filter(|(k, _)| k.0.starts_with(&prefix.0))
+            .filter(|(_, obj)| obj.get(&field).is_some_and(|v| v.total_cmp(&value).is_eq()))
+            .map(|(k, _)| k.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn find_range(&self, cmd: impl Into<FindRangeCommand>) -> Vec<Key> {
+        let FindRangeCommand(prefix, field, from, to) = cmd.into();
+        if let Some(keys) = self
+            .index_for(&prefix, &field)
+            .and_then(|i| i.find_range(from.as_ref(), to.as_ref()))
+        {
+            return keys;
+        }
+
+        // Only numbers, or values of the bound's variant, are in range.
+        let in_range = |v: &KVDBValue| {
+            (match &from {
+                Bound::Included(f) => v.kind_cmp(f).is_some_and(Ordering::is_ge),
+                Bound::Excluded(f) => v.kind_cmp(f).is_some_and(Ordering::is_gt),
+                Bound::Unbounded => true,
+            }) && (match &to {
+                Bound::Included(t) => v.kind_cmp(t).is_some_and(Ordering::is_le),
+                Bound::Excluded(t) => v.kind_cmp(t).is_some_and(Ordering::is_lt),
+                Bound::Unbounded => true,
+            })
+        };
+
+        let mut found: Vec<(&KVDBValue, &Key)> = self
+            .data
+            .iter()
+            .filter(|(k, _)| k.0.starts_with(&prefix.0))
+            .filter_map(|(k, obj)| obj.get(&field).filter(|v| in_range(v)).map(|v| (v, k)))
+            .collect();
+        found.sort_by(|a, b| a.0.range_cmp(b.0).then_with(|| a.1.cmp(b.1)));
+        found.into_iter().map(|(_, k)| k.clone()).collect()
+    }
+
+    /// Replaces the schema registered for the same prefix. Objects already stored are not
+    /// checked.
+    #[cfg(feature = "schema")]
+    pub fn set_schema(&mut self, cmd: impl Into<map_command::SetSchemaCommand>) {
+        let map_command::SetSchemaCommand(schema) = cmd.into();
+        self.schemas.retain(|s| s.prefix != schema.prefix);
+        self.schemas.push(schema);
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn drop_schema(&mut self, cmd: impl Into<map_command::DropSchemaCommand>) -> bool {
+        let map_command::DropSchemaCommand(prefix) = cmd.into();
+        let len = self.schemas.len();
+        self.schemas.retain(|s| s.prefix != prefix);
+        len != self.schemas.len()
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn schemas(&self) -> Vec<map_schema::MapSchema> {
+        self.schemas.clone()
+    }
+
+    /// Checks the object against every schema covering the key.
+    #[cfg(feature = "schema")]
+    pub fn validate(&self, key: &Key, obj: &KVDBObject) -> Result<()> {
+        self.schemas
+            .iter()
+            .filter(|s| s.covers(key))
+            .try_for_each(|s| s.validate(key, obj))
+    }
+
+    #[cfg(feature = "search")]
+    pub fn create_search_index(&mut self, cmd: impl Into<map_command::CreateSearchIndexCommand>) {
+        let map_command::CreateSearchIndexCommand(definition) = cmd.into();
+        self.search_indexes
+            .retain(|i| i.definition().prefix != definition.prefix);
+
+        let mut index = map_search::SearchIndex::new(definition);
+        for (k, v) in self.data.iter() {
+            index.insert(k, v);
+        }
+        self.search_indexes.push(index);
+    }
+
+    #[cfg(feature = "search")]
+    pub fn drop_search_index(
+        &mut self,
+        cmd: impl Into<map_command::DropSearchIndexCommand>,
+    ) -> bool {
+        let map_command::DropSearchIndexCommand(prefix) = cmd.into();
+        let len = self.search_indexes.len();
+        self.search_indexes
+            .retain(|i| i.definition().prefix != prefix);
+        len != self.search_indexes.len()
+    }
+
+    #[cfg(feature = "search")]
+    pub fn search(
+        &self,
+        cmd: impl Into<map_command::SearchCommand>,
+    ) -> Result<Vec<map_search::SearchHit>> {
+        let map_command::SearchCommand(prefix, query) = cmd.into();
+        Ok(self.search_index(&prefix)?.search(&query))
+    }
+
+    #[cfg(feature = "search")]
+    fn search_index(&self, prefix: &Key) -> Result<&map_search::SearchIndex> {
+        self.search_indexes
+            .iter()
+            .find(|i| i.definition().prefix == *prefix)
+            .ok_or_else(|| MiniKVDBError::MissingSearchIndex(prefix.0.clone()))
+    }
+
+    pub fn query(&self, cmd: impl Into<QueryCommand>) -> Vec<(Key, KVDBObject)> {
+        let QueryCommand(query) = cmd.into();
+        let found = self.matching(&query);
+        query.finish(found)
+    }
+
+    /// Objects matching the query, before sorting and pagination.
+    fn matching(&self, query: &MapQuery) -> Vec<(Key, KVDBObject)> {
+        match query
+            .filter
+            .as_ref()
+            .and_then(|f| self.indexed_candidates(&query.prefix, f))
+        {
+            Some(keys) => keys
+                .into_iter()
+                .filter_map(|k| self.data.get_key_value(&k))
+                .filter(|(k, obj)| query.matches(k, obj))
+                .map(|(k, obj)| (k.clone(), obj.clone()))
+                .collect(),
+            None => self
+                .data
+                .iter()
+                .filter(|(k, obj)| query.matches(k, obj))
+                .map(|(k, obj)| (k.clone(), obj.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn aggregate(&self, cmd: impl Into<AggregateCommand>) -> Vec<KVDBObject> {
+        let AggregateCommand(aggregation) = cmd.into();
+        aggregation.run(self.data.iter())
+    }
+
+    /// Narrows down the keys that can match the predicate using an index, if one exists.
+    fn indexed_candidates(&self, prefix: &Key, predicate: &Predicate) -> Option<Vec<Key>> {
+        let range = |f: &Key, from: Bound<&KVDBValue>, to: Bound<&KVDBValue>| {
+            self.index_for(prefix, f)
+                .and_then(|i| i.find_range(from, to))
+        };
+
+        match predicate {
+            Predicate::Eq(f, v) => self.index_for(prefix, f).map(|i| i.find(v)),
+            Predicate::In(f, vs) => self.index_for(prefix, f).map(|i| {
+                let mut keys: Vec<Key> = vs.iter().flat_map(|v| i.find(v)).collect();
+                keys.sort();
+                keys.dedup();
+                keys
+            }),
+            Predicate::Lt(f, v) => range(f, Bound::Unbounded, Bound::Excluded(v)),
+            Predicate::Le(f, v) => range(f, Bound::Unbounded, Bound::Included(v)),
+            Predicate::Gt(f, v) => range(f, Bound::Excluded(v), Bound::Unbounded),
+            Predicate::Ge(f, v) => range(f, Bound::Included(v), Bound::Unbounded),
+            Predicate::And(all) => all.iter().find_map(|p| self.indexed_candidates(prefix, p)),
+            _ => None,
+        }
+    }
+
+    /// Converts the object stored under the key, upgrading it to the latest version.
+    fn entity<T: TryFrom<KVDBObject>>(&self, key: &Key, obj: KVDBObject) -> Result<T>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let invalid = |e| MiniKVDBError::invalid_object(key.0.clone(), e);
+        let obj = self.migrations.upgrade(key, obj).map_err(invalid)?;
+        T::try_from(obj).map_err(|e| invalid(e.into()))
     }
 }
 
@@ -68,42 +704,617 @@ impl MiniKVDB {
         key: impl Into<Key>,
         value: impl Into<KVDBObject>,
     ) -> Result<Option<KVDBObject>> {
-        Ok(self.map.write()?.set(SetCommand(key.into(), value.into())))
+        let (key, value) = (key.into(), value.into());
+        let mut store = self.map.shard(&key).write()?;
+        #[cfg(feature = "schema")]
+        store.validate(&key, &value)?;
+        Ok(store.set(SetCommand(key, value)))
     }
 
+    /// Field of the object as stored, pending migrations are not applied.
     pub fn hash_get(
         &self,
         key: impl Into<Key>,
         field: impl Into<Key>,
     ) -> Result<Option<KVDBValue>> {
-        Ok(self.map.read()?.get(GetCommand(key.into(), field.into())))
+        let key = key.into();
+        Ok(self
+            .map
+            .shard(&key)
+            .read()?
+            .get(GetCommand(key, field.into())))
+    }
+
+    pub fn hash_get_as<T>(&self, key: impl Into<Key>, field: impl Into<Key>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let (key, field) = (key.into(), field.into());
+        self.hash_get(key.clone(), field.clone())?
+            .map(|v| {
+                self.coercion.convert(v).map_err(|e| {
+                    MiniKVDBError::invalid_value(key.0, MiniKVDBError::field(field.0, e))
+                })
+            })
+            .transpose()
+    }
+
+    pub fn hash_get_path(
+        &self,
+        key: impl Into<Key>,
+        path: impl TryInto<KVDBPath, Error = MiniKVDBError>,
+    ) -> Result<Option<KVDBValue>> {
+        let (key, path) = (key.into(), path.try_into()?);
+        Ok(self
+            .map
+            .shard(&key)
+            .read()?
+            .get_path(GetPathCommand(key, path)))
+    }
+
+    pub fn hash_set_path(
+        &self,
+        key: impl Into<Key>,
+        path: impl TryInto<KVDBPath, Error = MiniKVDBError>,
+        value: impl Into<KVDBValue>,
+    ) -> Result<Option<KVDBValue>> {
+        let (key, path) = (key.into(), path.try_into()?);
+        self.map
+            .shard(&key)
+            .write()?
+            .set_path(SetPathCommand(key, path, value.into()))
     }
 
+    /// Object as stored, pending migrations are not applied.
     pub fn hash_get_all(&self, key: impl Into<Key>) -> Result<Option<KVDBObject>> {
-        Ok(self.map.read()?.get_all(GetAllCommand(key.into())))
+        let key = key.into();
+        Ok(self.map.shard(&key).read()?.get_all(GetAllCommand(key)))
     }
 
     pub fn hash_contains_key(&self, key: impl Into<Key>) -> Result<bool> {
+        let key = key.into();
+        Ok(self
+            .map
+            .shard(&key)
+            .read()?
+            .contains_key(ContainsKeyCommand(key)))
+    }
+
+    /// Sorted keys of the map store starting with the prefix.
+    pub fn hash_keys(&self, prefix: impl Into<Key>) -> Result<Vec<Key>> {
+        let shards = self.map.read_all()?;
+        Ok(scan(
+            shards.iter().flat_map(|s| s.data.keys()),
+            &prefix.into(),
+        ))
+    }
+
+    pub fn hash_count(&self, prefix: impl Into<Key>) -> Result<usize> {
+        let prefix = prefix.into();
         Ok(self
             .map
+            .read_all()?
+            .iter()
+            .map(|s| s.count(CountCommand(prefix.clone())))
+            .sum())
+    }
+
+    pub fn hash_get_object<T: TryFrom<KVDBObject>>(&self, key: impl Into<Key>) -> Result<Option<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let key = key.into();
+        self.map
+            .shard(&key)
             .read()?
-            .contains_key(ContainsKeyCommand(key.into())))
+            .get_object(GetObjectCommand(key))
     }
 
-    pub fn hash_get_object<T: TryFrom<KVDBObject>>(
+    /// Stores any serializable struct or map as an object.
+    pub fn hash_set_serde<T: serde::Serialize + ?Sized>(
+        &self,
+        key: impl Into<Key>,
+        value: &T,
+    ) -> Result<Option<KVDBObject>> {
+        self.hash_set(key, kvdb_serde::to_object(value)?)
+    }
+
+    pub fn hash_get_serde<T: serde::de::DeserializeOwned>(
         &self,
         key: impl Into<Key>,
     ) -> Result<Option<T>> {
-        self.map.read()?.get_object(GetObjectCommand(key.into()))
+        self.hash_get_object::<KVDBObject>(key)?
+            .map(kvdb_serde::from_object)
+            .transpose()
+    }
+
+    /// Registers the migration upgrading objects under the prefix from version `from`.
+    /// Only entity reads such as `hash_get_object` upgrade objects, raw reads such as
+    /// `hash_get_all` or `hash_query` return them as stored until `hash_migrate` runs.
+    pub fn hash_register_migration(
+        &self,
+        prefix: impl Into<Key>,
+        from: u32,
+        migration: impl Fn(KVDBObject) -> Result<KVDBObject> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let cmd = RegisterMigrationCommand(prefix.into(), from, migration.into());
+        for mut shard in self.map.write_all()? {
+            shard.register_migration(cmd.clone());
+        }
+        Ok(())
+    }
+
+    /// Migrates every object under the prefix, one object per lock. Objects that fail to
+    /// migrate are reported and left unchanged.
+    pub fn hash_migrate(&self, mut job: MigrationJob) -> Result<MigrationReport> {
+        let mut keys: Vec<Key> = self
+            .map
+            .read_all()?
+            .iter()
+            .flat_map(|s| s.keys(&job.prefix))
+            .collect();
+        keys.sort();
+        let total = keys.len();
+        let mut report = MigrationReport::default();
+        for key in keys {
+            let shard = self.map.shard(&key);
+            let res = if job.dry_run {
+                shard.read()?.pending_migration(MigrateCommand(key.clone()))
+            } else {
+                shard.write()?.migrate(MigrateCommand(key.clone()))
+            };
+            report.scanned += 1;
+            match res {
+                Ok(Some(_)) => report.migrated.push(key.clone()),
+                Ok(None) => {}
+                Err(e) => report.failed.push((key.clone(), e)),
+            }
+            if let Some(progress) = &mut job.progress {
+                progress(&MigrationProgress {
+                    key,
+                    processed: report.scanned,
+                    total,
+                });
+            }
+        }
+        Ok(report)
     }
 
     pub fn hash_delete(&self, key: impl Into<Key>) -> Result<Option<KVDBObject>> {
-        Ok(self.map.write()?.delete(DeleteCommand(key.into())))
+        let key = key.into();
+        Ok(self.map.shard(&key).write()?.delete(DeleteCommand(key)))
+    }
+
+    pub fn hash_create_index(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        kind: MapIndexKind,
+    ) -> Result<()> {
+        let definition = MapIndexDefinition {
+            prefix: prefix.into(),
+            field: field.into(),
+            kind,
+        };
+        for mut shard in self.map.write_all()? {
+            shard.create_index(CreateIndexCommand(definition.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn hash_drop_index(&self, prefix: impl Into<Key>, field: impl Into<Key>) -> Result<bool> {
+        let (prefix, field) = (prefix.into(), field.into());
+        let mut dropped = false;
+        for mut shard in self.map.write_all()? {
+            dropped |= shard.drop_index(DropIndexCommand(prefix.clone(), field.clone()));
+        }
+        Ok(dropped)
+    }
+
+    /// Keeps previous states of the objects under the prefix in memory, bounded by the
+    /// retention. Enabling a prefix again replaces its retention.
+    #[cfg(feature = "history")]
+    pub fn hash_enable_history(
+        &self,
+        prefix: impl Into<Key>,
+        retention: kvdb_history::HistoryRetention,
+    ) -> Result<()> {
+        let prefix = prefix.into();
+        for mut shard in self.map.write_all()? {
+            shard.enable_history(map_command::EnableHistoryCommand(prefix.clone(), retention));
+        }
+        Ok(())
+    }
+
+    /// Drops the versions kept for the prefix, returns whether history was enabled.
+    #[cfg(feature = "history")]
+    pub fn hash_disable_history(&self, prefix: impl Into<Key>) -> Result<bool> {
+        let prefix = prefix.into();
+        let mut disabled = false;
+        for mut shard in self.map.write_all()? {
+            disabled |= shard.disable_history(map_command::DisableHistoryCommand(prefix.clone()));
+        }
+        Ok(disabled)
+    }
+
+    /// Versions of the object, oldest first and ending with the current one.
+    #[cfg(feature = "history")]
+    pub fn hash_versions(
+        &self,
+        key: impl Into<Key>,
+    ) -> Result<Vec<kvdb_history::Version<KVDBObject>>> {
+        let key = key.into();
+        Ok(self
+            .map
+            .shard(&key)
+            .read()?
+            .versions(map_command::VersionsCommand(key)))
+    }
+
+    #[cfg(feature = "history")]
+    pub fn hash_get_all_as_of(
+        &self,
+        key: impl Into<Key>,
+        at: SystemTime,
+    ) -> Result<Option<KVDBObject>> {
+        let key = key.into();
+        self.map
+            .shard(&key)
+            .read()?
+            .get_all_as_of(map_command::GetAsOfCommand(key, at))
+    }
+
+    #[cfg(feature = "history")]
+    pub fn hash_restore(&self, key: impl Into<Key>, at: SystemTime) -> Result<Option<KVDBObject>> {
+        let key = key.into();
+        self.map
+            .shard(&key)
+            .write()?
+            .restore(map_command::RestoreCommand(key, at))
+    }
+
+    pub fn hash_find_keys(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        value: impl Into<KVDBValue>,
+    ) -> Result<Vec<Key>> {
+        let cmd = FindCommand(prefix.into(), field.into(), value.into());
+        Ok(find(&self.map.read_all()?, cmd))
+    }
+
+    pub fn hash_find_objects<T: TryFrom<KVDBObject>>(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        value: impl Into<KVDBValue>,
+    ) -> Result<Vec<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let shards = self.map.read_all()?;
+        let keys = find(
+            &shards,
+            FindCommand(prefix.into(), field.into(), value.into()),
+        );
+        hash_objects(&shards, keys)
+    }
+
+    /// Keys ordered by the field value. Numbers of every width compare by value, other
+    /// values only match bounds of their own type.
+    pub fn hash_find_range_keys<V: Into<KVDBValue> + Clone>(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        range: impl RangeBounds<V>,
+    ) -> Result<Vec<Key>> {
+        let cmd = FindRangeCommand::new(prefix, field, range);
+        Ok(find_range(&self.map.read_all()?, cmd))
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn hash_set_schema(&self, schema: map_schema::MapSchema) -> Result<()> {
+        for mut shard in self.map.write_all()? {
+            shard.set_schema(map_command::SetSchemaCommand(schema.clone()));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn hash_drop_schema(&self, prefix: impl Into<Key>) -> Result<bool> {
+        let prefix = prefix.into();
+        let mut dropped = false;
+        for mut shard in self.map.write_all()? {
+            dropped |= shard.drop_schema(map_command::DropSchemaCommand(prefix.clone()));
+        }
+        Ok(dropped)
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn hash_schemas(&self) -> Result<Vec<map_schema::MapSchema>> {
+        Ok(self.map.shards()[0].read()?.schemas())
+    }
+
+    #[cfg(feature = "search")]
+    pub fn hash_create_search_index<K: Into<Key>>(
+        &self,
+        prefix: impl Into<Key>,
+        fields: impl IntoIterator<Item = K>,
+    ) -> Result<()> {
+        let definition = map_search::SearchIndexDefinition {
+            prefix: prefix.into(),
+            fields: fields.into_iter().map(Into::into).collect(),
+        };
+        for mut shard in self.map.write_all()? {
+            shard.create_search_index(map_command::CreateSearchIndexCommand(definition.clone()));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "search")]
+    pub fn hash_drop_search_index(&self, prefix: impl Into<Key>) -> Result<bool> {
+        let prefix = prefix.into();
+        let mut dropped = false;
+        for mut shard in self.map.write_all()? {
+            dropped |= shard.drop_search_index(map_command::DropSearchIndexCommand(prefix.clone()));
+        }
+        Ok(dropped)
+    }
+
+    /// Scores are computed from the statistics of all shards, so they do not depend on
+    /// the shard count.
+    #[cfg(feature = "search")]
+    pub fn hash_search(
+        &self,
+        prefix: impl Into<Key>,
+        query: impl Into<map_search::SearchQuery>,
+    ) -> Result<Vec<map_search::SearchHit>> {
+        search(
+            &self.map.read_all()?,
+            map_command::SearchCommand(prefix.into(), query.into()),
+        )
+    }
+
+    #[cfg(feature = "search")]
+    pub fn hash_search_objects<T: TryFrom<KVDBObject>>(
+        &self,
+        prefix: impl Into<Key>,
+        query: impl Into<map_search::SearchQuery>,
+    ) -> Result<Vec<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let shards = self.map.read_all()?;
+        let hits = search(
+            &shards,
+            map_command::SearchCommand(prefix.into(), query.into()),
+        )?;
+        hash_objects(&shards, hits.into_iter().map(|h| h.key).collect())
+    }
+
+    /// Objects as stored, `hash_query_objects` applies pending migrations.
+    pub fn hash_query(&self, query: MapQuery) -> Result<Vec<(Key, KVDBObject)>> {
+        Ok(run_query(&self.map.read_all()?, query))
+    }
+
+    pub fn hash_aggregate(&self, aggregation: Aggregation) -> Result<Vec<KVDBObject>> {
+        let shards = self.map.read_all()?;
+        Ok(aggregation.run(shards.iter().flat_map(|s| s.data.iter())))
+    }
+
+    pub fn hash_query_objects<T: TryFrom<KVDBObject>>(&self, query: MapQuery) -> Result<Vec<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let shards = self.map.read_all()?;
+        run_query(&shards, query)
+            .into_iter()
+            .map(|(k, obj)| shards[self.map.index(&k)].entity(&k, obj))
+            .collect()
+    }
+
+    pub fn hash_find_range_objects<T: TryFrom<KVDBObject>, V: Into<KVDBValue> + Clone>(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        range: impl RangeBounds<V>,
+    ) -> Result<Vec<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let shards = self.map.read_all()?;
+        let keys = find_range(&shards, FindRangeCommand::new(prefix, field, range));
+        hash_objects(&shards, keys)
     }
 }
 
+#[cfg(feature = "persistent")]
+impl MiniKVDBSnapshot {
+    pub fn hash_get(&self, key: impl Into<Key>, field: impl Into<Key>) -> Option<KVDBValue> {
+        let key = key.into();
+        shard(&self.map, &key).get(GetCommand(key, field.into()))
+    }
+
+    pub fn hash_get_as<T>(&self, key: impl Into<Key>, field: impl Into<Key>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        let (key, field) = (key.into(), field.into());
+        self.hash_get(key.clone(), field.clone())
+            .map(|v| {
+                self.coercion.convert(v).map_err(|e| {
+                    MiniKVDBError::invalid_value(key.0, MiniKVDBError::field(field.0, e))
+                })
+            })
+            .transpose()
+    }
+
+    pub fn hash_get_path(
+        &self,
+        key: impl Into<Key>,
+        path: impl TryInto<KVDBPath, Error = MiniKVDBError>,
+    ) -> Result<Option<KVDBValue>> {
+        let (key, path) = (key.into(), path.try_into()?);
+        Ok(shard(&self.map, &key).get_path(GetPathCommand(key, path)))
+    }
+
+    pub fn hash_get_all(&self, key: impl Into<Key>) -> Option<KVDBObject> {
+        let key = key.into();
+        shard(&self.map, &key).get_all(GetAllCommand(key))
+    }
+
+    pub fn hash_contains_key(&self, key: impl Into<Key>) -> bool {
+        let key = key.into();
+        shard(&self.map, &key).contains_key(ContainsKeyCommand(key))
+    }
+
+    pub fn hash_count(&self, prefix: impl Into<Key>) -> usize {
+        let prefix = prefix.into();
+        self.map
+            .iter()
+            .map(|s| s.count(CountCommand(prefix.clone())))
+            .sum()
+    }
+
+    pub fn hash_get_object<T: TryFrom<KVDBObject>>(&self, key: impl Into<Key>) -> Result<Option<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        let key = key.into();
+        shard(&self.map, &key).get_object(GetObjectCommand(key))
+    }
+
+    /// Sorted keys of the map store starting with the prefix.
+    pub fn hash_keys(&self, prefix: impl Into<Key>) -> Vec<Key> {
+        scan(self.map.iter().flat_map(|s| s.data.keys()), &prefix.into())
+    }
+
+    pub fn hash_find_keys(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        value: impl Into<KVDBValue>,
+    ) -> Vec<Key> {
+        find(
+            &self.map,
+            FindCommand(prefix.into(), field.into(), value.into()),
+        )
+    }
+
+    pub fn hash_find_range_keys<V: Into<KVDBValue> + Clone>(
+        &self,
+        prefix: impl Into<Key>,
+        field: impl Into<Key>,
+        range: impl RangeBounds<V>,
+    ) -> Vec<Key> {
+        find_range(&self.map, FindRangeCommand::new(prefix, field, range))
+    }
+
+    #[cfg(feature = "search")]
+    pub fn hash_search(
+        &self,
+        prefix: impl Into<Key>,
+        query: impl Into<map_search::SearchQuery>,
+    ) -> Result<Vec<map_search::SearchHit>> {
+        search(
+            &self.map,
+            map_command::SearchCommand(prefix.into(), query.into()),
+        )
+    }
+
+    pub fn hash_query(&self, query: MapQuery) -> Vec<(Key, KVDBObject)> {
+        run_query(&self.map, query)
+    }
+
+    pub fn hash_query_objects<T: TryFrom<KVDBObject>>(&self, query: MapQuery) -> Result<Vec<T>>
+    where
+        MiniKVDBError: From<T::Error>,
+    {
+        run_query(&self.map, query)
+            .into_iter()
+            .map(|(k, obj)| shard(&self.map, &k).entity(&k, obj))
+            .collect()
+    }
+
+    pub fn hash_aggregate(&self, aggregation: Aggregation) -> Vec<KVDBObject> {
+        aggregation.run(self.map.iter().flat_map(|s| s.data.iter()))
+    }
+}
+
+fn run_query<S: Deref<Target = MapStore>>(shards: &[S], query: MapQuery) -> Vec<(Key, KVDBObject)> {
+    let found = shards.iter().flat_map(|s| s.matching(&query)).collect();
+    query.finish(found)
+}
+
+/// Converts the objects stored under the keys, skipping missing ones.
+fn hash_objects<S, T>(shards: &[S], keys: Vec<Key>) -> Result<Vec<T>>
+where
+    S: Deref<Target = MapStore>,
+    T: TryFrom<KVDBObject>,
+    MiniKVDBError: From<T::Error>,
+{
+    keys.into_iter()
+        .filter_map(|k| {
+            let shard = &shards[shard_index(&k, shards.len())];
+            shard.data.get(&k).cloned().map(|obj| shard.entity(&k, obj))
+        })
+        .collect()
+}
+
+fn find<S: Deref<Target = MapStore>>(shards: &[S], cmd: FindCommand) -> Vec<Key> {
+    let mut keys: Vec<Key> = shards.iter().flat_map(|s| s.find(cmd.clone())).collect();
+    keys.sort();
+    keys
+}
+
+/// Keys of all shards ordered by the value of the field, then by key.
+fn find_range<S: Deref<Target = MapStore>>(shards: &[S], cmd: FindRangeCommand) -> Vec<Key> {
+    if let [shard] = shards {
+        return shard.find_range(cmd);
+    }
+    let field = cmd.1.clone();
+    let mut found: Vec<(&KVDBValue, Key)> = shards
+        .iter()
+        .flat_map(|s| {
+            s.find_range(cmd.clone()).into_iter().filter_map(|k| {
+                s.data
+                    .get(&k)
+                    .and_then(|obj| obj.get(&field))
+                    .map(|v| (v, k))
+            })
+        })
+        .collect();
+    found.sort_by(|a, b| a.0.range_cmp(b.0).then_with(|| a.1.cmp(&b.1)));
+    found.into_iter().map(|(_, k)| k).collect()
+}
+
+#[cfg(feature = "search")]
+fn search<S: Deref<Target = MapStore>>(
+    shards: &[S],
+    cmd: map_command::SearchCommand,
+) -> Result<Vec<map_search::SearchHit>> {
+    let map_command::SearchCommand(prefix, query) = cmd;
+    let indexes = shards
+        .iter()
+        .map(|s| s.search_index(&prefix))
+        .collect::<Result<Vec<_>>>()?;
+    let stats = indexes
+        .iter()
+        .fold(map_search::CorpusStats::default(), |mut stats, i| {
+            stats.merge(i.stats(&query));
+            stats
+        });
+    let hits = indexes
+        .iter()
+        .flat_map(|i| i.matches(&query, &stats))
+        .collect();
+    Ok(map_search::rank(hits, &query))
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::minikvdb::kvdb_shard::Sharded;
+
     use super::*;
 
     fn test_db() -> MapStore {
@@ -135,12 +1346,12 @@ mod tests {
         ));
         assert!(ret.is_some());
         assert_eq!(
-            *ret.unwrap().get("name".into()).unwrap(),
+            *ret.unwrap().get("name").unwrap(),
             KVDBValue::String("bob".into())
         );
 
         assert_eq!(
-            *db.0.get("a".into()).unwrap().get("name").unwrap(),
+            *db.data.get("a").unwrap().get("name").unwrap(),
             KVDBValue::String("tom".into())
         );
     }
@@ -176,12 +1387,34 @@ mod tests {
 
         assert!(res.is_some());
         let res = res.unwrap();
-        assert_eq!(
-            *res.get("name".into()).unwrap(),
-            KVDBValue::String("tom".into())
-        );
+        assert_eq!(*res.get("name").unwrap(), KVDBValue::String("tom".into()));
+
+        assert_eq!(*res.get("age").unwrap(), KVDBValue::Int(22));
+    }
+
+    #[test]
+    fn counts_objects_by_prefix() {
+        let mut db = seeded_db();
+        let _ = db.set(SetCommand("user:1".into(), KVDBObject::new()));
+        let _ = db.set(SetCommand("user:2".into(), KVDBObject::new()));
+
+        assert_eq!(db.count(CountCommand("user:".into())), 2);
+        assert_eq!(db.count(CountCommand("".into())), 5);
+    }
+
+    #[test]
+    fn distinguishes_null_field_from_missing_field() {
+        let mut db = test_db();
+        let nickname: Option<String> = None;
+        let _ = db.set(SetCommand(
+            "a".into(),
+            [("nickname".into(), nickname.into())].into(),
+        ));
 
-        assert_eq!(*res.get("age".into()).unwrap(), KVDBValue::Int(22.into()));
+        let null = db.get(GetCommand("a".into(), "nickname".into()));
+        let missing = db.get(GetCommand("a".into(), "email".into()));
+        assert_eq!(null, Some(KVDBValue::Null));
+        assert_eq!(missing, None);
     }
 
     #[test]
@@ -198,11 +1431,481 @@ mod tests {
         let res = db.delete(DeleteCommand("b".into()));
         assert!(res.is_some());
         let res = res.unwrap();
+        assert_eq!(*res.get("name").unwrap(), KVDBValue::String("bob".into()));
+        assert_eq!(*res.get("age").unwrap(), KVDBValue::Int(42));
+        assert!(!db.data.contains_key("b"));
+    }
+
+    fn indexed_db(kind: MapIndexKind) -> MapStore {
+        let mut db = seeded_db();
+        db.create_index(CreateIndexCommand(MapIndexDefinition {
+            prefix: "".into(),
+            field: "age".into(),
+            kind,
+        }));
+        db
+    }
+
+    #[test]
+    fn builds_index_from_existing_objects() {
+        let db = indexed_db(MapIndexKind::Hash);
+        let res = db.find(FindCommand("".into(), "age".into(), 42.into()));
+        assert_eq!(res, vec![Key::from("b")]);
+    }
+
+    #[test]
+    fn keeps_index_in_sync_on_set_and_delete() {
+        let mut db = indexed_db(MapIndexKind::Hash);
+        let _ = db.set(SetCommand(
+            "b".into(),
+            [("name".into(), "bob".into()), ("age".into(), 22.into())].into(),
+        ));
+        assert!(db
+            .find(FindCommand("".into(), "age".into(), 42.into()))
+            .is_empty());
         assert_eq!(
-            *res.get("name".into()).unwrap(),
-            KVDBValue::String("bob".into())
+            db.find(FindCommand("".into(), "age".into(), 22.into())),
+            vec![Key::from("a"), Key::from("b")]
+        );
+
+        let _ = db.delete(DeleteCommand("a".into()));
+        assert_eq!(
+            db.find(FindCommand("".into(), "age".into(), 22.into())),
+            vec![Key::from("b")]
+        );
+    }
+
+    #[test]
+    fn finds_range_with_and_without_index() {
+        let scan = seeded_db().find_range(FindRangeCommand::new("", "age", 20..=30));
+        let indexed =
+            indexed_db(MapIndexKind::Ordered).find_range(FindRangeCommand::new("", "age", 20..=30));
+        assert_eq!(scan, vec![Key::from("a")]);
+        assert_eq!(scan, indexed);
+    }
+
+    #[test]
+    fn finds_range_only_among_comparable_values() {
+        let mut scan = test_db();
+        for (key, age) in [
+            ("user:1", KVDBValue::from(25)),
+            ("user:2", "unknown".into()),
+            ("user:3", true.into()),
+            ("user:4", 30.5f32.into()),
+            ("user:5", KVDBValue::Null),
+        ] {
+            let _ = scan.set(SetCommand(key.into(), [("age".into(), age)].into()));
+        }
+        let mut indexed = scan.clone();
+        indexed.create_index(CreateIndexCommand(MapIndexDefinition {
+            prefix: "user:".into(),
+            field: "age".into(),
+            kind: MapIndexKind::Ordered,
+        }));
+
+        for db in [&scan, &indexed] {
+            let range =
+                |from: KVDBValue| db.find_range(FindRangeCommand::new("user:", "age", from..));
+            assert_eq!(
+                range(20.into()),
+                vec![Key::from("user:1"), Key::from("user:4")]
+            );
+            assert_eq!(range(26.0f32.into()), vec![Key::from("user:4")]);
+            #[cfg(feature = "big-types")]
+            assert_eq!(
+                range(KVDBValue::Long(20)),
+                vec![Key::from("user:1"), Key::from("user:4")]
+            );
+            assert_eq!(range("a".into()), vec![Key::from("user:2")]);
+            assert_eq!(
+                db.find_range(FindRangeCommand::new("user:", "age", ..KVDBValue::from(30))),
+                vec![Key::from("user:1")]
+            );
+        }
+    }
+
+    fn people_db() -> MapStore {
+        let mut db = test_db();
+        for (i, (name, age)) in [("tom", 22), ("bob", 42), ("john", 31), ("anna", 27)]
+            .into_iter()
+            .enumerate()
+        {
+            let _ = db.set(SetCommand(
+                format!("user:{i}").into(),
+                [("name".into(), name.into()), ("age".into(), age.into())].into(),
+            ));
+        }
+        let _ = db.set(SetCommand(
+            "admin:0".into(),
+            [("name".into(), "root".into()), ("age".into(), 50.into())].into(),
+        ));
+        db
+    }
+
+    fn names(res: Vec<(Key, KVDBObject)>) -> Vec<KVDBValue> {
+        res.into_iter()
+            .map(|(_, obj)| obj.get("name").cloned().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn queries_with_filter_sort_and_pagination() {
+        let query = MapQuery::new("user:")
+            .filter(Predicate::gt("age", 25))
+            .sort_by("age", map_query::SortOrder::Desc)
+            .offset(1)
+            .limit(1);
+
+        let res = people_db().query(QueryCommand(query));
+        assert_eq!(names(res), crate::values!("john"));
+    }
+
+    #[test]
+    fn query_uses_index_with_same_results_as_scan() {
+        let query = MapQuery::new("user:")
+            .filter(Predicate::ge("age", 27).and(!Predicate::prefix("name", "j")))
+            .sort_by("name", map_query::SortOrder::Asc);
+
+        let scan = people_db().query(QueryCommand(query.clone()));
+
+        let mut db = people_db();
+        db.create_index(CreateIndexCommand(MapIndexDefinition {
+            prefix: "user:".into(),
+            field: "age".into(),
+            kind: MapIndexKind::Ordered,
+        }));
+        let indexed = db.query(QueryCommand(query));
+
+        assert_eq!(names(scan.clone()), crate::values!("anna", "bob"));
+        assert_eq!(scan, indexed);
+    }
+
+    #[test]
+    fn query_projects_fields() {
+        let query = MapQuery::new("admin:").select(["name"]);
+        let res = people_db().query(QueryCommand(query));
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].1.len(), 1);
+        assert!(res[0].1.contains_key("name"));
+    }
+
+    #[cfg(feature = "search")]
+    #[test]
+    fn keeps_search_index_in_sync() {
+        let mut db = people_db();
+        db.create_search_index(map_command::CreateSearchIndexCommand(
+            map_search::SearchIndexDefinition {
+                prefix: "user:".into(),
+                fields: vec!["name".into()],
+            },
+        ));
+
+        let hits = db
+            .search(map_command::SearchCommand("user:".into(), "tom".into()))
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+
+        let _ = db.delete(DeleteCommand(hits[0].key.clone()));
+        let _ = db.set(SetCommand(
+            "user:9".into(),
+            [("name".into(), "Tom Riddle".into())].into(),
+        ));
+        let hits = db
+            .search(map_command::SearchCommand("user:".into(), "tom".into()))
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].key, Key::from("user:9"));
+
+        assert!(db
+            .search(map_command::SearchCommand("admin:".into(), "tom".into()))
+            .is_err());
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn rejects_writes_violating_schema() {
+        use crate::minikvdb::kvdb_type::KVDBType;
+        use map_schema::{FieldSchema, MapSchema};
+
+        let db = MiniKVDB::default();
+        db.hash_set_schema(
+            MapSchema::new("user:")
+                .field(
+                    FieldSchema::new("name")
+                        .required()
+                        .of_type(KVDBType::String),
+                )
+                .field(FieldSchema::new("age").range(Some(0.0), None)),
+        )
+        .unwrap();
+
+        let valid: KVDBObject = [("name".into(), "tom".into()), ("age".into(), 3.into())].into();
+        assert!(db.hash_set("user:1", valid).is_ok());
+        assert!(db.hash_set("admin:1", KVDBObject::new()).is_ok());
+        match db.hash_set(
+            "user:2",
+            [("age".into(), (-1).into())]
+                .into_iter()
+                .collect::<KVDBObject>(),
+        ) {
+            Err(MiniKVDBError::SchemaViolation(key, violations)) => {
+                assert_eq!(key, "user:2");
+                let kinds: Vec<_> = violations
+                    .iter()
+                    .map(|v| (v.field.0.as_str(), &v.kind))
+                    .collect();
+                assert_eq!(
+                    kinds,
+                    vec![
+                        ("name", &map_schema::ViolationKind::Missing),
+                        (
+                            "age",
+                            &map_schema::ViolationKind::OutOfRange {
+                                value: -1.0,
+                                min: Some(0.0),
+                                max: None,
+                            }
+                        ),
+                    ]
+                );
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+        assert!(!db.hash_contains_key("user:2").unwrap());
+
+        assert!(db.hash_set_path("user:1", "name", 1).is_err());
+        assert_eq!(db.hash_get("user:1", "name").unwrap(), Some("tom".into()));
+        assert!(db.hash_drop_schema("user:").unwrap());
+        assert!(db.hash_set_path("user:1", "name", 1).is_ok());
+    }
+
+    #[test]
+    fn migrates_objects_lazily_and_in_batches() {
+        let db = MiniKVDB::default();
+        for i in 0..3 {
+            let obj: KVDBObject = [("name".into(), format!("user {i}").into())].into();
+            db.hash_set(format!("user:{i}"), obj).unwrap();
+        }
+        db.hash_register_migration("user:", 1, |mut obj| {
+            obj.insert("active".into(), true.into());
+            Ok(obj)
+        })
+        .unwrap();
+
+        let obj: KVDBObject = db.hash_get_object("user:0").unwrap().unwrap();
+        assert_eq!(obj["active"], true.into());
+        assert_eq!(map_migrate::version(&obj), 2);
+        assert_eq!(db.hash_get("user:0", "active").unwrap(), None);
+        let stored = db.hash_get_all("user:0").unwrap().unwrap();
+        assert_eq!(map_migrate::version(&stored), 1);
+        let queried = db.hash_query(MapQuery::new("user:0")).unwrap();
+        assert!(!queried[0].1.contains_key("active"));
+
+        let mut progress = vec![];
+        let report = db
+            .hash_migrate(
+                MigrationJob::new("user:")
+                    .dry_run()
+                    .on_progress(|p| progress.push((p.processed, p.total))),
+            )
+            .unwrap();
+        assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(report.migrated.len(), 3);
+        assert_eq!(db.hash_get("user:0", "active").unwrap(), None);
+
+        let report = db.hash_migrate(MigrationJob::new("user:")).unwrap();
+        assert_eq!(report.scanned, 3);
+        assert_eq!(report.migrated.len(), 3);
+        assert_eq!(db.hash_get("user:2", "active").unwrap(), Some(true.into()));
+
+        let report = db.hash_migrate(MigrationJob::new("user:")).unwrap();
+        assert!(report.migrated.is_empty());
+    }
+
+    #[test]
+    fn reports_failed_migrations() {
+        let db = MiniKVDB::default();
+        db.hash_set("user:1", KVDBObject::new()).unwrap();
+        db.hash_register_migration("user:", 1, |_| Err(MiniKVDBError::MissingField("x".into())))
+            .unwrap();
+
+        let report = db.hash_migrate(MigrationJob::new("user:")).unwrap();
+        assert_eq!(report.failed.len(), 1);
+        assert!(db.hash_get_object::<KVDBObject>("user:1").is_err());
+    }
+
+    #[test]
+    fn gets_and_sets_nested_paths() {
+        let mut db = indexed_db(MapIndexKind::Hash);
+        let res = db.set_path(SetPathCommand(
+            "a".into(),
+            "address.city".parse().unwrap(),
+            "Warsaw".into(),
+        ));
+        assert!(res.unwrap().is_none());
+
+        let _ = db.set_path(SetPathCommand(
+            "a".into(),
+            "age".parse().unwrap(),
+            30.into(),
+        ));
+
+        assert_eq!(
+            db.get_path(GetPathCommand("a".into(), "address.city".parse().unwrap())),
+            Some("Warsaw".into())
+        );
+        assert_eq!(
+            db.find(FindCommand("".into(), "age".into(), 30.into())),
+            vec![Key::from("a")]
+        );
+        assert!(db
+            .set_path(SetPathCommand(
+                "a".into(),
+                "name[0]".parse().unwrap(),
+                "x".into()
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn index_survives_serialization_roundtrip() {
+        let db = indexed_db(MapIndexKind::Ordered);
+        let repr: MapStoreRepr = db.into();
+        let db: MapStore = repr.into();
+        assert_eq!(db.indexes().len(), 1);
+        assert_eq!(
+            db.find(FindCommand("".into(), "age".into(), 22.into())),
+            vec![Key::from("a")]
+        );
+    }
+
+    #[test]
+    fn sharded_store_gives_same_results_as_single_shard() {
+        let sharded = |count| {
+            let db = MiniKVDB {
+                map: std::sync::Arc::new(Sharded::from_store(people_db(), count)),
+                ..Default::default()
+            };
+            #[cfg(feature = "search")]
+            db.hash_create_search_index("user:", ["name"]).unwrap();
+            db
+        };
+        let (single, sharded) = (sharded(1), sharded(8));
+        assert_eq!(sharded.map.shard_count(), 8);
+
+        let query = MapQuery::new("user:")
+            .sort_by("age", map_query::SortOrder::Desc)
+            .offset(1)
+            .limit(2);
+        assert_eq!(
+            single.hash_query(query.clone()).unwrap(),
+            sharded.hash_query(query).unwrap()
+        );
+        assert_eq!(
+            single.hash_find_range_keys("user:", "age", 25..).unwrap(),
+            sharded.hash_find_range_keys("user:", "age", 25..).unwrap()
         );
-        assert_eq!(*res.get("age".into()).unwrap(), KVDBValue::Int(42.into()));
-        assert!(db.0.get("b".into()).is_none());
+        assert_eq!(sharded.hash_count("user:").unwrap(), 4);
+        let aggregation = Aggregation::new("")
+            .reduce("total", map_aggregate::Reducer::Count)
+            .reduce("age", map_aggregate::Reducer::Avg("age".into()));
+        assert_eq!(
+            single.hash_aggregate(aggregation.clone()).unwrap(),
+            sharded.hash_aggregate(aggregation).unwrap()
+        );
+        #[cfg(feature = "search")]
+        assert_eq!(
+            single.hash_search("user:", "tom").unwrap(),
+            sharded.hash_search("user:", "tom").unwrap()
+        );
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn restores_previous_objects() {
+        let db = MiniKVDB::default();
+        let obj = |n: i32| KVDBObject::from([("n".into(), n.into())]);
+        db.hash_set("doc:1", obj(1)).unwrap();
+        db.hash_create_index("doc:", "n", MapIndexKind::Hash)
+            .unwrap();
+        db.hash_enable_history("doc:", kvdb_history::HistoryRetention::default())
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let before = SystemTime::now();
+        db.hash_set_path("doc:1", "n", 2).unwrap();
+
+        assert_eq!(
+            db.hash_get_all_as_of("doc:1", before).unwrap(),
+            Some(obj(1))
+        );
+        assert_eq!(db.hash_versions("doc:1").unwrap().len(), 2);
+        assert_eq!(db.hash_restore("doc:1", before).unwrap(), Some(obj(1)));
+        assert_eq!(
+            db.hash_find_keys("doc:", "n", 1).unwrap(),
+            vec!["doc:1".into()]
+        );
+        assert!(db.hash_restore("doc:2", before).unwrap().is_none());
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn resharding_and_rollback_record_no_versions() {
+        use crate::prelude::PoisonPolicy;
+
+        let db = MiniKVDB::with_shards(4);
+        db.set_poison_policy(PoisonPolicy::Rollback);
+        let obj = |n: i32| KVDBObject::from([("n".into(), n.into())]);
+        db.hash_create_index("doc:", "n", MapIndexKind::Hash)
+            .unwrap();
+        db.hash_enable_history("doc:", kvdb_history::HistoryRetention::default())
+            .unwrap();
+        db.hash_set("doc:1", obj(1)).unwrap();
+        let key: Key = "doc:1".into();
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut store = db.map.shard(&key).write().unwrap();
+            store.set(SetCommand(key.clone(), obj(2)));
+            store.delete(DeleteCommand(key.clone()));
+            panic!("interrupted write");
+        }));
+        db.clear_poison();
+
+        assert_eq!(db.hash_versions("doc:1").unwrap().len(), 1);
+        assert_eq!(
+            db.hash_find_keys("doc:", "n", 1).unwrap(),
+            vec![key.clone()]
+        );
+        let merged = db.map.merged().unwrap();
+        assert_eq!(
+            merged
+                .versions(map_command::VersionsCommand(key.clone()))
+                .len(),
+            1
+        );
+        assert_eq!(
+            merged.find(FindCommand("doc:".into(), "n".into(), 1.into())),
+            vec![key]
+        );
+    }
+
+    #[test]
+    fn loads_store_persisted_before_indexes() {
+        let data: HashMap<Key, KVDBObject> =
+            [("a".into(), [("name".into(), "tom".into())].into())].into();
+        let legacy = serde_json::to_string(&data).unwrap();
+
+        let store: MapStore = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(store.data, Objects::from(data));
+        assert!(store.indexes.is_empty());
+
+        let mut store = seeded_db();
+        store.create_index(CreateIndexCommand(MapIndexDefinition {
+            prefix: "".into(),
+            field: "age".into(),
+            kind: MapIndexKind::Ordered,
+        }));
+        let current = serde_json::to_string(&store).unwrap();
+        let loaded: MapStore = serde_json::from_str(&current).unwrap();
+        assert_eq!(loaded.data, store.data);
+        assert_eq!(loaded.indexes.len(), 1);
     }
 }
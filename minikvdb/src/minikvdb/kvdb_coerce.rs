@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MiniKVDBError, Result};
+
+#[cfg(any(feature = "key_value", feature = "list"))]
+use super::kvdb_key::Key;
+use super::{kvdb_value::KVDBValue, MiniKVDB};
+
+/// How typed getters convert stored values into the requested type.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Coercion {
+    /// Only the variant mapped to the requested type is accepted.
+    #[default]
+    Strict,
+    /// Numbers are widened or narrowed when the value fits, `Float` and `Double` convert
+    /// into each other and numeric strings are parsed.
+    Lenient,
+}
+
+impl Coercion {
+    pub fn convert<T>(self, value: KVDBValue) -> Result<T>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        match self {
+            Coercion::Strict => T::try_from(value),
+            Coercion::Lenient => match T::try_from(value.clone()) {
+                Ok(v) => Ok(v),
+                Err(e) => value
+                    .coercions()
+                    .into_iter()
+                    .find_map(|v| T::try_from(v).ok())
+                    .ok_or(e),
+            },
+        }
+    }
+
+    /// Converts the value stored under the key, errors carry the key.
+    #[cfg(any(feature = "key_value", feature = "list"))]
+    pub(crate) fn convert_stored<T>(self, key: &Key, value: Option<KVDBValue>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        value
+            .map(|v| {
+                self.convert(v)
+                    .map_err(|e| MiniKVDBError::invalid_value(key.0.clone(), e))
+            })
+            .transpose()
+    }
+}
+
+impl KVDBValue {
+    /// Alternative representations of the value used by lenient coercion,
+    /// lossless conversions first.
+    pub fn coercions(&self) -> Vec<KVDBValue> {
+        match self {
+            KVDBValue::Int(v) => integers((*v).into()),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(v) => integers((*v).into()),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(v) => integers((*v).into()),
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(v) => integers((*v).into()),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(v) => integers(*v),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(v) => i128::try_from(*v).map(integers).unwrap_or_default(),
+            KVDBValue::Float(v) => floats((*v).into()),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(v) => floats(*v),
+            KVDBValue::String(s) => parse(s.trim()),
+            _ => vec![],
+        }
+    }
+}
+
+fn integers(v: i128) -> Vec<KVDBValue> {
+    let mut out = vec![];
+    if let Ok(v) = i32::try_from(v) {
+        out.push(KVDBValue::Int(v));
+    }
+    #[cfg(feature = "big-types")]
+    {
+        if let Ok(v) = i64::try_from(v) {
+            out.push(KVDBValue::Long(v));
+        }
+        if let Ok(v) = u32::try_from(v) {
+            out.push(KVDBValue::UInt(v));
+        }
+        if let Ok(v) = u64::try_from(v) {
+            out.push(KVDBValue::ULong(v));
+        }
+        out.push(KVDBValue::Int128(v));
+        if let Ok(v) = u128::try_from(v) {
+            out.push(KVDBValue::UInt128(v));
+        }
+    }
+    #[cfg(feature = "decimal")]
+    if let Some(v) = rust_decimal::prelude::FromPrimitive::from_i128(v) {
+        out.push(KVDBValue::Decimal(v));
+    }
+    #[cfg(feature = "big-types")]
+    if v as f64 as i128 == v {
+        out.push(KVDBValue::Double(v as f64));
+    }
+    if v as f32 as i128 == v {
+        out.push(KVDBValue::Float(v as f32));
+    }
+    out
+}
+
+fn floats(v: f64) -> Vec<KVDBValue> {
+    // Narrowing to `f32` must not round or overflow.
+    let float = v as f32;
+    [
+        #[cfg(feature = "big-types")]
+        Some(KVDBValue::Double(v)),
+        (float.is_finite() && float as f64 == v).then_some(KVDBValue::Float(float)),
+        #[cfg(feature = "decimal")]
+        rust_decimal::prelude::FromPrimitive::from_f64(v).map(KVDBValue::Decimal),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn parse(s: &str) -> Vec<KVDBValue> {
+    let mut out = vec![];
+    if let Ok(v) = s.parse::<i128>() {
+        out.extend(integers(v));
+    }
+    #[cfg(feature = "decimal")]
+    if let Ok(v) = s.parse() {
+        out.push(KVDBValue::Decimal(v));
+    }
+    if let Ok(v) = s.parse::<f64>() {
+        out.extend(floats(v));
+    }
+    // Decimal strings are parsed as the nearest `f32` rather than narrowed.
+    if let Ok(v) = s.parse::<f32>() {
+        if v.is_finite() {
+            out.push(KVDBValue::Float(v));
+        }
+    }
+    if let Ok(v) = s.parse() {
+        out.push(KVDBValue::Bool(v));
+    }
+    out
+}
+
+impl MiniKVDB {
+    /// Returns a handle sharing the same stores that converts values using `coercion`.
+    pub fn with_coercion(&self, coercion: Coercion) -> Self {
+        Self {
+            coercion,
+            ..self.clone()
+        }
+    }
+
+    pub fn coercion(&self) -> Coercion {
+        self.coercion
+    }
+
+    #[cfg(any(feature = "key_value", feature = "list"))]
+    pub(crate) fn convert<T>(&self, key: &Key, value: Option<KVDBValue>) -> Result<Option<T>>
+    where
+        T: TryFrom<KVDBValue, Error = MiniKVDBError>,
+    {
+        self.coercion.convert_stored(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_accepts_only_mapped_variant() {
+        let n: Result<i32> = Coercion::Strict.convert("12".into());
+        let f: Result<f32> = Coercion::Strict.convert(1.into());
+        assert!(n.is_err());
+        assert!(f.is_err());
+        assert_eq!(Coercion::Strict.convert::<i32>(1.into()).unwrap(), 1);
+    }
+
+    #[test]
+    fn lenient_parses_numeric_strings() {
+        let n: i32 = Coercion::Lenient.convert(" 12 ".into()).unwrap();
+        let f: f32 = Coercion::Lenient.convert("1.5".into()).unwrap();
+        let b: bool = Coercion::Lenient.convert("true".into()).unwrap();
+        assert_eq!(n, 12);
+        assert_eq!(f, 1.5);
+        assert!(b);
+        assert!(Coercion::Lenient.convert::<i32>("abc".into()).is_err());
+    }
+
+    #[test]
+    fn lenient_converts_numbers() {
+        let f: f32 = Coercion::Lenient.convert(3.into()).unwrap();
+        assert_eq!(f, 3.0);
+        assert!(Coercion::Lenient.convert::<i32>(1.5f32.into()).is_err());
+        assert_eq!(
+            Coercion::Lenient.convert::<f32>(16_777_216.into()).unwrap(),
+            16_777_216.0
+        );
+        assert!(Coercion::Lenient.convert::<f32>(16_777_217.into()).is_err());
+        assert_eq!(Coercion::Lenient.convert::<f32>("0.1".into()).unwrap(), 0.1);
+        assert!(Coercion::Lenient.convert::<f32>("1e300".into()).is_err());
+    }
+
+    #[cfg(feature = "big-types")]
+    #[test]
+    fn lenient_widens_and_narrows_when_value_fits() {
+        let long: i64 = Coercion::Lenient.convert(3.into()).unwrap();
+        let int: i32 = Coercion::Lenient.convert(7i64.into()).unwrap();
+        let double: f64 = Coercion::Lenient.convert(1.5f32.into()).unwrap();
+        let float: f32 = Coercion::Lenient.convert(2.5f64.into()).unwrap();
+        assert_eq!(long, 3);
+        assert_eq!(int, 7);
+        assert_eq!(double, 1.5);
+        assert_eq!(float, 2.5);
+        assert!(Coercion::Lenient.convert::<i32>(i64::MAX.into()).is_err());
+        assert!(Coercion::Lenient.convert::<u32>((-1).into()).is_err());
+        assert!(Coercion::Lenient.convert::<f32>(1e300f64.into()).is_err());
+        assert!(Coercion::Lenient.convert::<f32>(0.1f64.into()).is_err());
+        assert!(Coercion::Lenient.convert::<f64>(i64::MAX.into()).is_err());
+        assert_eq!(
+            Coercion::Lenient
+                .convert::<f64>((1i64 << 53).into())
+                .unwrap(),
+            (1i64 << 53) as f64
+        );
+    }
+}
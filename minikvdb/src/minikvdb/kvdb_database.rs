@@ -0,0 +1,424 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    sync::{Arc, RwLock},
+};
+
+use crate::error::{MiniKVDBError, Result};
+
+#[cfg(feature = "vector")]
+use super::kvdb_lock::{Journaled, StoreLock, StoreWriteGuard};
+use super::MiniKVDB;
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+use super::{
+    kvdb_key::Key,
+    kvdb_shard::{self, Sharded, ShardedStore},
+};
+
+/// Name of the database of `MiniKVDB::default()` and deserialized databases.
+pub const DEFAULT_DATABASE: &str = "0";
+
+/// Stores of one logical database.
+#[derive(Clone)]
+struct Stores {
+    #[cfg(feature = "key_value")]
+    kv: Arc<Sharded<crate::kv_store::KVStore>>,
+    #[cfg(feature = "list")]
+    list: Arc<Sharded<crate::list_store::ListStore, crate::list_store::ListLock>>,
+    #[cfg(feature = "map")]
+    map: Arc<Sharded<crate::map_store::MapStore>>,
+    #[cfg(feature = "vector")]
+    vector: Arc<StoreLock<crate::vector_store::VectorStore>>,
+}
+
+/// Name of the database of a handle and the databases reachable from it, shared by all
+/// handles derived from the same root.
+#[derive(Clone)]
+pub(crate) struct DatabaseRef {
+    name: Arc<str>,
+    registry: Arc<RwLock<HashMap<String, Stores>>>,
+}
+
+impl Default for DatabaseRef {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_DATABASE.into(),
+            registry: Default::default(),
+        }
+    }
+}
+
+impl Debug for DatabaseRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DatabaseRef").field(&self.name).finish()
+    }
+}
+
+/// Number of entries in each store of a database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseStats {
+    pub name: String,
+    #[cfg(feature = "key_value")]
+    pub keys: usize,
+    #[cfg(feature = "list")]
+    pub lists: usize,
+    #[cfg(feature = "map")]
+    pub objects: usize,
+    #[cfg(feature = "vector")]
+    pub vectors: usize,
+}
+
+impl MiniKVDB {
+    fn stores(&self) -> Stores {
+        Stores {
+            #[cfg(feature = "key_value")]
+            kv: self.kv.clone(),
+            #[cfg(feature = "list")]
+            list: self.list.clone(),
+            #[cfg(feature = "map")]
+            map: self.map.clone(),
+            #[cfg(feature = "vector")]
+            vector: self.vector.clone(),
+        }
+    }
+
+    /// Empty stores with the same shard counts.
+    fn empty_stores(&self) -> Stores {
+        Stores {
+            #[cfg(feature = "key_value")]
+            kv: Arc::new(Sharded::new(self.kv.shard_count())),
+            #[cfg(feature = "list")]
+            list: Arc::new(Sharded::new(self.list.shard_count())),
+            #[cfg(feature = "map")]
+            map: Arc::new(Sharded::new(self.map.shard_count())),
+            #[cfg(feature = "vector")]
+            vector: Default::default(),
+        }
+    }
+
+    fn with_stores(&self, name: &str, stores: Stores) -> Self {
+        let Stores {
+            #[cfg(feature = "key_value")]
+            kv,
+            #[cfg(feature = "list")]
+            list,
+            #[cfg(feature = "map")]
+            map,
+            #[cfg(feature = "vector")]
+            vector,
+        } = stores;
+        Self {
+            #[cfg(feature = "key_value")]
+            kv,
+            #[cfg(feature = "list")]
+            list,
+            #[cfg(feature = "map")]
+            map,
+            #[cfg(feature = "vector")]
+            vector,
+            coercion: self.coercion,
+            database: DatabaseRef {
+                name: name.into(),
+                registry: self.database.registry.clone(),
+            },
+        }
+    }
+
+    /// Handle of the logical database with the name, such as `1` or `"tenant-a"`, created
+    /// empty on first use. Databases are isolated from each other and each one is
+    /// persisted on its own.
+    pub fn database(&self, name: impl Display) -> Result<MiniKVDB> {
+        let name = name.to_string();
+        if *self.database.name == name {
+            return Ok(self.clone());
+        }
+        let mut registry = self
+            .database
+            .registry
+            .write()
+            .map_err(|_| MiniKVDBError::RWLockWritePoison)?;
+        registry
+            .entry(self.database.name.to_string())
+            .or_insert_with(|| self.stores());
+        let stores = registry
+            .entry(name.clone())
+            .or_insert_with(|| self.empty_stores())
+            .clone();
+        Ok(self.with_stores(&name, stores))
+    }
+
+    pub fn database_name(&self) -> &str {
+        &self.database.name
+    }
+
+    /// Sorted names of all databases reachable from this handle.
+    pub fn databases(&self) -> Result<Vec<String>> {
+        let registry = self
+            .database
+            .registry
+            .read()
+            .map_err(|_| MiniKVDBError::RWLockReadPoison)?;
+        let mut names: Vec<String> = registry.keys().cloned().collect();
+        if !registry.contains_key(&*self.database.name) {
+            names.push(self.database.name.to_string());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Exchanges the contents of two databases, including indexes, schemas and history.
+    /// Existing handles keep their name and see the other contents afterwards.
+    pub fn swap_databases(&self, a: impl Display, b: impl Display) -> Result<()> {
+        let (a, b) = (self.database(a)?, self.database(b)?);
+        if a.database.name == b.database.name {
+            return Ok(());
+        }
+        #[cfg(feature = "key_value")]
+        let mut kv = a.kv.write_all_pair(&b.kv)?;
+        #[cfg(feature = "list")]
+        let mut list = a.list.write_all_pair(&b.list)?;
+        #[cfg(feature = "map")]
+        let mut map = a.map.write_all_pair(&b.map)?;
+        #[cfg(feature = "vector")]
+        let mut vector = write_pair(&a.vector, &b.vector)?;
+
+        #[cfg(feature = "key_value")]
+        kvdb_shard::swap_shards(&mut kv.0, &mut kv.1);
+        #[cfg(feature = "list")]
+        kvdb_shard::swap_shards(&mut list.0, &mut list.1);
+        #[cfg(feature = "map")]
+        kvdb_shard::swap_shards(&mut map.0, &mut map.1);
+        #[cfg(feature = "vector")]
+        std::mem::swap(&mut *vector.0, &mut *vector.1);
+        Ok(())
+    }
+
+    /// Removes every key of this database. Index, schema and history definitions and
+    /// vector collections are kept.
+    pub fn flush(&self) -> Result<()> {
+        #[cfg(feature = "key_value")]
+        let mut kv = self.kv.write_all()?;
+        #[cfg(feature = "list")]
+        let mut list = self.list.write_all()?;
+        #[cfg(feature = "map")]
+        let mut map = self.map.write_all()?;
+        #[cfg(feature = "vector")]
+        let mut vector = self.vector.write()?;
+
+        #[cfg(feature = "key_value")]
+        kvdb_shard::clear_shards(&mut kv);
+        #[cfg(feature = "list")]
+        kvdb_shard::clear_shards(&mut list);
+        #[cfg(feature = "map")]
+        kvdb_shard::clear_shards(&mut map);
+        #[cfg(feature = "vector")]
+        vector.clear();
+        Ok(())
+    }
+
+    /// Moves the key from every store of this database holding it into the database `to`.
+    /// Nothing is moved and `false` is returned when the key is missing here or already
+    /// used in one of those stores of `to`. Vectors are not moved.
+    #[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+    pub fn move_key(&self, key: impl Into<Key>, to: impl Display) -> Result<bool> {
+        let (key, target) = (key.into(), self.database(to)?);
+        if target.database.name == self.database.name {
+            return Ok(false);
+        }
+        #[cfg(feature = "key_value")]
+        let (mut kv_from, mut kv_to) = self.kv.write_pair(&target.kv, &key)?;
+        #[cfg(feature = "list")]
+        let (mut list_from, mut list_to) = self.list.write_pair(&target.list, &key)?;
+        #[cfg(feature = "map")]
+        let (mut map_from, mut map_to) = self.map.write_pair(&target.map, &key)?;
+
+        let stores = [
+            #[cfg(feature = "key_value")]
+            (kv_from.has_key(&key), kv_to.has_key(&key)),
+            #[cfg(feature = "list")]
+            (list_from.has_key(&key), list_to.has_key(&key)),
+            #[cfg(feature = "map")]
+            (map_from.has_key(&key), map_to.has_key(&key)),
+        ];
+        if !stores.iter().any(|(from, _)| *from) || stores.iter().any(|(from, to)| *from && *to) {
+            return Ok(false);
+        }
+        // The map is moved first as it is the only store that can reject the entry.
+        #[cfg(feature = "map")]
+        map_from.move_entry(&key, &mut map_to)?;
+        #[cfg(feature = "key_value")]
+        kv_from.move_entry(&key, &mut kv_to)?;
+        #[cfg(feature = "list")]
+        list_from.move_entry(&key, &mut list_to)?;
+        Ok(true)
+    }
+
+    pub fn stats(&self) -> Result<DatabaseStats> {
+        Ok(DatabaseStats {
+            name: self.database.name.to_string(),
+            #[cfg(feature = "key_value")]
+            keys: self.kv.key_count()?,
+            #[cfg(feature = "list")]
+            lists: self.list.key_count()?,
+            #[cfg(feature = "map")]
+            objects: self.map.key_count()?,
+            #[cfg(feature = "vector")]
+            vectors: self.vector.read()?.vector_count(),
+        })
+    }
+
+    /// Stats of all databases reachable from this handle, sorted by name.
+    pub fn database_stats(&self) -> Result<Vec<DatabaseStats>> {
+        self.databases()?
+            .into_iter()
+            .map(|name| self.database(name)?.stats())
+            .collect()
+    }
+}
+
+#[cfg(feature = "vector")]
+fn write_pair<'a, T: Clone + Journaled>(
+    a: &'a StoreLock<T>,
+    b: &'a StoreLock<T>,
+) -> Result<(StoreWriteGuard<'a, T>, StoreWriteGuard<'a, T>)> {
+    if kvdb_shard::locks_first(a, b) {
+        let a = a.write()?;
+        Ok((a, b.write()?))
+    } else {
+        let b = b.write()?;
+        Ok((a.write()?, b))
+    }
+}
+
+#[cfg(all(test, feature = "key_value", feature = "list", feature = "map"))]
+mod tests {
+    use crate::{minikvdb::kvdb_value::KVDBObject, values};
+
+    use super::*;
+
+    #[test]
+    fn isolates_databases() {
+        let root = MiniKVDB::with_shards(4);
+        let tenant = root.database("tenant").unwrap();
+        root.set("a", 1).unwrap();
+        tenant.set("a", 2).unwrap();
+
+        assert_eq!(root.get("a").unwrap(), Some(1.into()));
+        assert_eq!(tenant.get("a").unwrap(), Some(2.into()));
+        assert_eq!(
+            tenant.database(DEFAULT_DATABASE).unwrap().get("a").unwrap(),
+            Some(1.into())
+        );
+        assert_eq!(
+            root.database("tenant").unwrap().get("a").unwrap(),
+            Some(2.into())
+        );
+        assert_eq!(tenant.database_name(), "tenant");
+        assert_eq!(root.databases().unwrap(), vec!["0", "tenant"]);
+    }
+
+    #[test]
+    fn swaps_and_flushes_databases() {
+        let root = MiniKVDB::default();
+        let other = root.database(1).unwrap();
+        root.set("a", 1).unwrap();
+        root.push_back("l", values![1]).unwrap();
+        other
+            .hash_set("h", KVDBObject::from([("n".into(), 1.into())]))
+            .unwrap();
+
+        root.swap_databases(0, 1).unwrap();
+        assert_eq!(root.get("a").unwrap(), None);
+        assert!(root.hash_contains_key("h").unwrap());
+        assert_eq!(other.get("a").unwrap(), Some(1.into()));
+        assert_eq!(other.list_len("l").unwrap(), Some(1));
+
+        other.flush().unwrap();
+        assert_eq!(
+            root.database_stats().unwrap(),
+            vec![
+                DatabaseStats {
+                    name: "0".into(),
+                    keys: 0,
+                    lists: 0,
+                    objects: 1,
+                    #[cfg(feature = "vector")]
+                    vectors: 0,
+                },
+                DatabaseStats {
+                    name: "1".into(),
+                    keys: 0,
+                    lists: 0,
+                    objects: 0,
+                    #[cfg(feature = "vector")]
+                    vectors: 0,
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn swaps_databases_with_different_shard_counts() {
+        use crate::prelude::{HistoryRetention, MapIndexKind};
+
+        let root = MiniKVDB::with_shards(4);
+        let single = MiniKVDB::default().stores();
+        root.database
+            .registry
+            .write()
+            .unwrap()
+            .insert("1".into(), single);
+        let other = root.database(1).unwrap();
+        assert_eq!(other.map.shard_count(), 1);
+
+        root.enable_history("a", HistoryRetention::default())
+            .unwrap();
+        root.hash_create_index("h", "n", MapIndexKind::Hash)
+            .unwrap();
+        root.hash_enable_history("h", HistoryRetention::default())
+            .unwrap();
+        for n in 0..4 {
+            root.set(format!("a{n}"), n).unwrap();
+            root.set(format!("a{n}"), n + 1).unwrap();
+            root.hash_set(format!("h{n}"), KVDBObject::from([("n".into(), n.into())]))
+                .unwrap();
+        }
+
+        root.swap_databases(0, 1).unwrap();
+        for n in 0..4 {
+            assert_eq!(other.versions(format!("a{n}")).unwrap().len(), 2);
+            assert_eq!(other.hash_versions(format!("h{n}")).unwrap().len(), 1);
+        }
+        assert_eq!(other.get("a1").unwrap(), Some(2.into()));
+        assert_eq!(
+            other.hash_find_keys("h", "n", 1).unwrap(),
+            vec!["h1".into()]
+        );
+        assert!(root.versions("a1").unwrap().is_empty());
+
+        root.swap_databases(1, 0).unwrap();
+        assert_eq!(root.versions("a1").unwrap().len(), 2);
+        assert_eq!(root.hash_find_keys("h", "n", 1).unwrap(), vec!["h1".into()]);
+        assert!(root.database(1).unwrap().get("a1").unwrap().is_none());
+    }
+
+    #[test]
+    fn moves_keys_between_databases() {
+        let root = MiniKVDB::with_shards(4);
+        root.set("a", 1).unwrap();
+        root.push_back("a", values![1]).unwrap();
+        root.set("b", 1).unwrap();
+        root.database(1).unwrap().set("b", 2).unwrap();
+
+        assert!(root.move_key("a", 1).unwrap());
+        assert!(!root.move_key("a", 1).unwrap());
+        assert!(!root.move_key("b", 1).unwrap());
+
+        let other = root.database(1).unwrap();
+        assert_eq!(other.get("a").unwrap(), Some(1.into()));
+        assert_eq!(other.list_len("a").unwrap(), Some(1));
+        assert_eq!(root.stats().unwrap().keys, 1);
+        assert_eq!(other.get("b").unwrap(), Some(2.into()));
+    }
+}
@@ -0,0 +1,18 @@
+use crate::error::MiniKVDBError;
+
+use super::{kvdb_type::EntityField, kvdb_value::KVDBObject};
+
+/// Field holding the version of objects written by versioned entities. Objects without it
+/// are at version 1.
+pub const VERSION_FIELD: &str = "_version";
+
+/// Types stored as map objects, implemented by `#[derive(KVDBEntity)]`.
+pub trait KVDBEntity: Into<KVDBObject> + TryFrom<KVDBObject, Error = MiniKVDBError> {
+    /// Version written to `VERSION_FIELD`, set with `#[kvdb(version = N)]`.
+    const VERSION: u32 = 1;
+
+    /// Fields of the stored object, used to derive a schema.
+    fn fields() -> Vec<EntityField> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,334 @@
+use std::time::{Duration, SystemTime};
+
+use crate::error::{MiniKVDBError, Result};
+
+use super::{
+    kvdb_key::Key,
+    kvdb_lock::{Journal, Journaled},
+};
+
+/// Bounds of the versions kept for every key under a prefix, unbounded by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryRetention {
+    /// Versions kept per key, including the current one.
+    pub max_versions: Option<usize>,
+    /// Versions replaced longer ago are dropped.
+    pub max_age: Option<Duration>,
+}
+
+impl HistoryRetention {
+    pub fn max_versions(mut self, count: usize) -> Self {
+        self.max_versions = Some(count);
+        self
+    }
+
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+}
+
+/// Value of a key from `timestamp` until the next version, `None` when it was deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version<T> {
+    pub timestamp: SystemTime,
+    pub value: Option<T>,
+}
+
+#[derive(Debug, Clone)]
+struct HistoryRule {
+    prefix: Key,
+    retention: HistoryRetention,
+    since: SystemTime,
+}
+
+/// With the `persistent` feature versions are kept in persistent collections, which
+/// snapshots share without cloning them.
+#[cfg(not(feature = "persistent"))]
+type Versions<T> = std::collections::VecDeque<Version<T>>;
+#[cfg(feature = "persistent")]
+type Versions<T> = imbl::Vector<Version<T>>;
+
+#[cfg(not(feature = "persistent"))]
+type Keys<T> = std::collections::HashMap<Key, KeyHistory<T>>;
+#[cfg(feature = "persistent")]
+type Keys<T> = imbl::HashMap<Key, KeyHistory<T>>;
+
+#[derive(Debug, Clone)]
+struct KeyHistory<T> {
+    /// Time from which the versions are complete.
+    since: SystemTime,
+    versions: Versions<T>,
+}
+
+impl<T: Clone> KeyHistory<T> {
+    /// Versions are only pruned when the key is written.
+    fn prune(&mut self, retention: HistoryRetention, now: SystemTime) {
+        let len = self.versions.len();
+        if let Some(max) = retention.max_versions {
+            let excess = self.versions.len().saturating_sub(max.max(1));
+            for _ in 0..excess {
+                self.versions.pop_front();
+            }
+        }
+        // The version current at the cutoff is kept to answer reads up to the cutoff.
+        if let Some(cutoff) = retention.max_age.and_then(|age| now.checked_sub(age)) {
+            while self.versions.len() > 1 && self.versions[1].timestamp <= cutoff {
+                self.versions.pop_front();
+            }
+        }
+        if self.versions.len() != len {
+            if let Some(first) = self.versions.front() {
+                self.since = first.timestamp;
+            }
+        }
+    }
+}
+
+/// Timestamped versions of the keys under the prefixes with history enabled. Versions
+/// are kept in memory only and not persisted with the store.
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    rules: Vec<HistoryRule>,
+    keys: Keys<T>,
+    /// Versions of the keys recorded by the write in progress, before it.
+    journal: Journal<(Key, Option<KeyHistory<T>>)>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            keys: Keys::new(),
+            journal: Journal::default(),
+        }
+    }
+}
+
+impl<T: Clone> Journaled for History<T> {
+    fn set_journaling(&mut self, enabled: bool) {
+        self.journal.set_enabled(enabled);
+    }
+
+    fn rollback(&mut self) {
+        for (key, history) in self.journal.take() {
+            match history {
+                Some(history) => self.keys.insert(key, history),
+                None => self.keys.remove(&key),
+            };
+        }
+    }
+}
+
+impl<T: Clone> History<T> {
+    /// Same prefixes and retentions without any versions.
+    pub fn empty_like(&self) -> Self {
+        Self {
+            rules: self.rules.clone(),
+            keys: Keys::new(),
+            journal: Journal::default(),
+        }
+    }
+
+    /// Versions of the keys of `other`, which has the same rules, are moved in.
+    pub fn merge(&mut self, other: Self) {
+        self.keys.extend(other.keys);
+    }
+
+    /// Distributes the versions over `count` histories with the same rules.
+    pub fn split(self, count: usize, shard: impl Fn(&Key) -> usize) -> Vec<Self> {
+        let mut histories = vec![self.empty_like(); count];
+        for (key, history) in self.keys {
+            histories[shard(&key)].keys.insert(key, history);
+        }
+        histories
+    }
+
+    /// Rule with the longest prefix covering the key.
+    fn rule(&self, key: &Key) -> Option<&HistoryRule> {
+        self.rules
+            .iter()
+            .filter(|r| key.0.starts_with(&r.prefix.0))
+            .max_by_key(|r| r.prefix.0.len())
+    }
+
+    pub fn is_tracked(&self, key: &Key) -> bool {
+        self.rule(key).is_some()
+    }
+
+    /// Starts tracking the keys under the prefix, the current entries become their first
+    /// version. Enabling a tracked prefix again only replaces its retention.
+    pub fn enable<'a>(
+        &mut self,
+        prefix: Key,
+        retention: HistoryRetention,
+        entries: impl Iterator<Item = (&'a Key, &'a T)>,
+    ) where
+        T: 'a,
+    {
+        let now = SystemTime::now();
+        match self.rules.iter_mut().find(|r| r.prefix == prefix) {
+            Some(rule) => rule.retention = retention,
+            None => self.rules.push(HistoryRule {
+                prefix: prefix.clone(),
+                retention,
+                since: now,
+            }),
+        }
+        for (key, value) in entries.filter(|(k, _)| k.0.starts_with(&prefix.0)) {
+            self.keys.entry(key.clone()).or_insert_with(|| KeyHistory {
+                since: now,
+                versions: [Version {
+                    timestamp: now,
+                    value: Some(value.clone()),
+                }]
+                .into_iter()
+                .collect(),
+            });
+        }
+        let keys: Vec<Key> = self
+            .keys
+            .keys()
+            .filter(|k| k.0.starts_with(&prefix.0))
+            .cloned()
+            .collect();
+        for key in keys {
+            if let Some(retention) = self.rule(&key).map(|r| r.retention) {
+                if let Some(history) = self.keys.get_mut(&key) {
+                    history.prune(retention, now);
+                }
+            }
+        }
+    }
+
+    /// Drops the versions of keys no longer tracked, returns whether the prefix was tracked.
+    pub fn disable(&mut self, prefix: &Key) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|r| r.prefix != *prefix);
+        let rules = &self.rules;
+        self.keys
+            .retain(|k, _| rules.iter().any(|r| k.0.starts_with(&r.prefix.0)));
+        len != self.rules.len()
+    }
+
+    /// Records the value the key holds from now on, ignored when the key is not tracked.
+    pub fn record(&mut self, key: &Key, value: Option<&T>) {
+        let Some(rule) = self.rule(key) else {
+            return;
+        };
+        let (since, retention) = (rule.since, rule.retention);
+        let keys = &self.keys;
+        self.journal
+            .record(|| (key.clone(), keys.get(key).cloned()));
+        let now = SystemTime::now();
+        let history = self.keys.entry(key.clone()).or_insert_with(|| KeyHistory {
+            since,
+            versions: Versions::new(),
+        });
+        history.versions.push_back(Version {
+            timestamp: now,
+            value: value.cloned(),
+        });
+        history.prune(retention, now);
+    }
+
+    /// Versions of the key, oldest first.
+    pub fn versions(&self, key: &Key) -> Vec<Version<T>> {
+        self.keys
+            .get(key)
+            .map(|h| h.versions.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Value of the key at the time, `None` when it did not exist. Fails with `NoHistory`
+    /// when the key is not tracked or its versions do not reach back that far.
+    pub fn as_of(&self, key: &Key, at: SystemTime) -> Result<Option<T>> {
+        let history = self.keys.get(key);
+        let since = match history {
+            Some(history) => Some(history.since),
+            None => self.rule(key).map(|r| r.since),
+        };
+        if since.is_none_or(|since| at < since) {
+            return Err(MiniKVDBError::NoHistory(key.0.clone()));
+        }
+        Ok(history
+            .and_then(|h| h.versions.iter().rev().find(|v| v.timestamp <= at))
+            .and_then(|v| v.value.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> Key {
+        k.into()
+    }
+
+    #[test]
+    fn reads_value_as_of_time() {
+        let mut history = History::default();
+        let existing = [(key("user:1"), 1), (key("other"), 0)];
+        history.enable(
+            key("user:"),
+            HistoryRetention::default(),
+            existing.iter().map(|(k, v)| (k, v)),
+        );
+        let enabled = SystemTime::now();
+        std::thread::sleep(Duration::from_millis(2));
+
+        history.record(&key("user:1"), Some(&2));
+        history.record(&key("user:2"), Some(&5));
+        history.record(&key("other"), Some(&1));
+        let written = SystemTime::now();
+        std::thread::sleep(Duration::from_millis(2));
+        history.record(&key("user:1"), None);
+
+        assert_eq!(history.as_of(&key("user:1"), enabled).unwrap(), Some(1));
+        assert_eq!(history.as_of(&key("user:1"), written).unwrap(), Some(2));
+        assert_eq!(
+            history.as_of(&key("user:1"), SystemTime::now()).unwrap(),
+            None
+        );
+        assert_eq!(history.as_of(&key("user:2"), enabled).unwrap(), None);
+        assert!(history.as_of(&key("other"), written).is_err());
+        assert!(history
+            .as_of(&key("user:1"), enabled - Duration::from_secs(1))
+            .is_err());
+        assert_eq!(history.versions(&key("user:1")).len(), 3);
+    }
+
+    #[test]
+    fn prunes_versions_by_count_and_age() {
+        let mut history = History::default();
+        history.enable(
+            key("a"),
+            HistoryRetention::default().max_versions(2),
+            std::iter::empty(),
+        );
+        for i in 0..5 {
+            history.record(&key("a"), Some(&i));
+        }
+        let values: Vec<_> = history
+            .versions(&key("a"))
+            .into_iter()
+            .map(|v| v.value)
+            .collect();
+        assert_eq!(values, vec![Some(3), Some(4)]);
+        let first = history.versions(&key("a"))[0].timestamp;
+        assert!(history
+            .as_of(&key("a"), first - Duration::from_nanos(1))
+            .is_err());
+
+        history.enable(
+            key("a"),
+            HistoryRetention::default().max_age(Duration::ZERO),
+            std::iter::empty(),
+        );
+        history.record(&key("a"), Some(&5));
+        assert_eq!(history.versions(&key("a")).len(), 1);
+
+        assert!(history.disable(&key("a")));
+        assert!(history.versions(&key("a")).is_empty());
+    }
+}
@@ -0,0 +1,716 @@
+use std::{
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{MiniKVDBError, Result};
+
+#[cfg(any(
+    feature = "key_value",
+    feature = "list",
+    feature = "map",
+    feature = "vector"
+))]
+use super::MiniKVDB;
+
+/// What happens when a store is accessed after a thread panicked while writing to it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoisonPolicy {
+    /// Every access fails with `RWLockReadPoison` or `RWLockWritePoison` until the poison
+    /// is cleared with `MiniKVDB::clear_poison`.
+    #[default]
+    Fail,
+    /// The poison is cleared and the store is used as the panicking thread left it.
+    Recover,
+    /// The keys changed by the interrupted write are restored from an undo record of
+    /// their previous values, kept while writing. Index, schema and history definitions
+    /// changed by the write are not restored.
+    Rollback,
+}
+
+impl PoisonPolicy {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => PoisonPolicy::Recover,
+            2 => PoisonPolicy::Rollback,
+            _ => PoisonPolicy::Fail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StoreKind {
+    #[cfg(feature = "key_value")]
+    KeyValue,
+    #[cfg(feature = "list")]
+    List,
+    #[cfg(feature = "map")]
+    Map,
+    #[cfg(feature = "vector")]
+    Vector,
+}
+
+/// Store keeping an undo record of its changes while journaling, which
+/// `PoisonPolicy::Rollback` replays to undo an interrupted write.
+pub trait Journaled {
+    /// Starts recording changes, or stops and drops the record.
+    fn set_journaling(&mut self, enabled: bool);
+
+    /// Undoes the recorded changes, newest first, and stops recording.
+    fn rollback(&mut self);
+}
+
+/// Undo record of a store, such as the previous values of the keys a write changed.
+#[derive(Debug, Clone)]
+pub struct Journal<E>(Option<Vec<E>>);
+
+impl<E> Default for Journal<E> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<E> Journal<E> {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.0 = enabled.then(Vec::new);
+    }
+
+    /// The entry is only built while recording.
+    pub fn record(&mut self, entry: impl FnOnce() -> E) {
+        if let Some(entries) = self.0.as_mut() {
+            entries.push(entry());
+        }
+    }
+
+    /// Recorded entries, newest first. Recording stops.
+    pub fn take(&mut self) -> std::iter::Rev<std::vec::IntoIter<E>> {
+        self.0.take().unwrap_or_default().into_iter().rev()
+    }
+}
+
+#[derive(Default)]
+struct Slot<T> {
+    /// Shared with snapshots, writes copy the store while a snapshot holds it.
+    value: Arc<T>,
+    /// Whether the store records an undo journal for the write in progress.
+    journaling: bool,
+}
+
+/// Read-write lock around a store applying the configured `PoisonPolicy`.
+#[derive(Default)]
+pub struct StoreLock<T> {
+    inner: RwLock<Slot<T>>,
+    policy: AtomicU8,
+}
+
+impl<T> StoreLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: RwLock::new(Slot {
+                value: Arc::new(value),
+                journaling: false,
+            }),
+            policy: AtomicU8::new(PoisonPolicy::Fail as u8),
+        }
+    }
+
+    pub fn policy(&self) -> PoisonPolicy {
+        PoisonPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+
+    pub fn set_policy(&self, policy: PoisonPolicy) {
+        self.policy.store(policy as u8, Ordering::Relaxed);
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+}
+
+impl<T: Clone + Journaled> StoreLock<T> {
+    /// Clears the poison, rolling back the interrupted write when an undo record was kept.
+    /// Returns whether the store was poisoned.
+    pub fn clear_poison(&self) -> bool {
+        let mut slot = match self.inner.write() {
+            Ok(_) => return false,
+            Err(e) => e.into_inner(),
+        };
+        if std::mem::take(&mut slot.journaling) {
+            Arc::make_mut(&mut slot.value).rollback();
+        }
+        self.inner.clear_poison();
+        true
+    }
+
+    pub fn read(&self) -> Result<StoreReadGuard<'_, T>> {
+        if self.is_poisoned() && self.policy() != PoisonPolicy::Fail {
+            self.clear_poison();
+        }
+        self.inner
+            .read()
+            .map(StoreReadGuard)
+            .map_err(|_| MiniKVDBError::RWLockReadPoison)
+    }
+
+    pub fn pin(&self) -> Result<StorePin<'_, T>> {
+        self.read().map(|guard| StorePin(guard.0))
+    }
+
+    pub fn write(&self) -> Result<StoreWriteGuard<'_, T>> {
+        if self.is_poisoned() && self.policy() != PoisonPolicy::Fail {
+            self.clear_poison();
+        }
+        let slot = self
+            .inner
+            .write()
+            .map_err(|_| MiniKVDBError::RWLockWritePoison)?;
+        Ok(StoreWriteGuard {
+            slot,
+            rollback: self.policy() == PoisonPolicy::Rollback,
+        })
+    }
+}
+
+/// Shared and exclusive access to a store, implemented by `StoreLock` and `SnapshotLock`.
+pub trait LockBackend<T> {
+    type ReadGuard<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+    /// Blocks writers while held, the shared store can outlive it.
+    type Pin<'a>: Deref<Target = Arc<T>>
+    where
+        Self: 'a;
+
+    fn new(value: T) -> Self;
+    fn read(&self) -> Result<Self::ReadGuard<'_>>;
+    fn write(&self) -> Result<Self::WriteGuard<'_>>;
+    fn pin(&self) -> Result<Self::Pin<'_>>;
+    fn set_policy(&self, policy: PoisonPolicy);
+    fn is_poisoned(&self) -> bool;
+    fn clear_poison(&self) -> bool;
+}
+
+impl<T: Clone + Journaled> LockBackend<T> for StoreLock<T> {
+    type ReadGuard<'a>
+        = StoreReadGuard<'a, T>
+    where
+        T: 'a;
+    type WriteGuard<'a>
+        = StoreWriteGuard<'a, T>
+    where
+        T: 'a;
+    type Pin<'a>
+        = StorePin<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        StoreLock::new(value)
+    }
+
+    fn read(&self) -> Result<Self::ReadGuard<'_>> {
+        StoreLock::read(self)
+    }
+
+    fn write(&self) -> Result<Self::WriteGuard<'_>> {
+        StoreLock::write(self)
+    }
+
+    fn pin(&self) -> Result<Self::Pin<'_>> {
+        StoreLock::pin(self)
+    }
+
+    fn set_policy(&self, policy: PoisonPolicy) {
+        StoreLock::set_policy(self, policy)
+    }
+
+    fn is_poisoned(&self) -> bool {
+        StoreLock::is_poisoned(self)
+    }
+
+    fn clear_poison(&self) -> bool {
+        StoreLock::clear_poison(self)
+    }
+}
+
+impl<T: Debug> Debug for StoreLock<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.inner.try_read() {
+            Ok(slot) => f.debug_tuple("StoreLock").field(&slot.value).finish(),
+            Err(_) => f.write_str("StoreLock(<locked>)"),
+        }
+    }
+}
+
+impl<T: Serialize + Clone + Journaled> Serialize for StoreLock<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.read()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for StoreLock<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+pub struct StoreReadGuard<'a, T>(RwLockReadGuard<'a, Slot<T>>);
+
+impl<T> Deref for StoreReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0.value
+    }
+}
+
+pub struct StorePin<'a, T>(RwLockReadGuard<'a, Slot<T>>);
+
+impl<T> Deref for StorePin<'_, T> {
+    type Target = Arc<T>;
+    fn deref(&self) -> &Arc<T> {
+        &self.0.value
+    }
+}
+
+pub struct StoreWriteGuard<'a, T: Clone + Journaled> {
+    slot: RwLockWriteGuard<'a, Slot<T>>,
+    /// Whether the store journals its changes once it is written to.
+    rollback: bool,
+}
+
+impl<T: Clone + Journaled> Deref for StoreWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.slot.value
+    }
+}
+
+impl<T: Clone + Journaled> DerefMut for StoreWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        let slot = &mut *self.slot;
+        let value = Arc::make_mut(&mut slot.value);
+        if self.rollback && !slot.journaling {
+            slot.journaling = true;
+            value.set_journaling(true);
+        }
+        value
+    }
+}
+
+impl<T: Clone + Journaled> Drop for StoreWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // A panicking writer leaves the undo record for the next access.
+        if self.slot.journaling && !std::thread::panicking() {
+            self.slot.journaling = false;
+            Arc::make_mut(&mut self.slot.value).set_journaling(false);
+        }
+    }
+}
+
+/// Store published as immutable snapshots: reads never lock and see a consistent
+/// point-in-time view, writers are serialized and work on a copy published when the
+/// guard is dropped. Meant for stores built from persistent data structures, which
+/// are cheap to copy.
+///
+/// A panicking writer never publishes its copy, so `Recover` and `Rollback` behave the
+/// same and reads keep working even under `Fail`.
+#[cfg(feature = "persistent")]
+pub struct SnapshotLock<T> {
+    current: arc_swap::ArcSwap<T>,
+    writer: std::sync::Mutex<()>,
+    policy: AtomicU8,
+}
+
+#[cfg(feature = "persistent")]
+impl<T> SnapshotLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: arc_swap::ArcSwap::from_pointee(value),
+            writer: std::sync::Mutex::new(()),
+            policy: AtomicU8::new(PoisonPolicy::Fail as u8),
+        }
+    }
+
+    pub fn policy(&self) -> PoisonPolicy {
+        PoisonPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+
+    pub fn set_policy(&self, policy: PoisonPolicy) {
+        self.policy.store(policy as u8, Ordering::Relaxed);
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.writer.is_poisoned()
+    }
+
+    pub fn clear_poison(&self) -> bool {
+        let poisoned = self.is_poisoned();
+        self.writer.clear_poison();
+        poisoned
+    }
+
+    pub fn read(&self) -> Result<StoreSnapshot<T>> {
+        Ok(StoreSnapshot(self.current.load_full()))
+    }
+
+    /// Waits for the write in progress. A poisoned writer lock does not matter, as an
+    /// interrupted write is never published.
+    pub fn pin(&self) -> Result<SnapshotPin<'_, T>> {
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(SnapshotPin {
+            value: self.current.load_full(),
+            _writer: writer,
+        })
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T: Clone> SnapshotLock<T> {
+    pub fn write(&self) -> Result<SnapshotWriteGuard<'_, T>> {
+        if self.is_poisoned() && self.policy() != PoisonPolicy::Fail {
+            self.clear_poison();
+        }
+        let writer = self
+            .writer
+            .lock()
+            .map_err(|_| MiniKVDBError::RWLockWritePoison)?;
+        Ok(SnapshotWriteGuard {
+            lock: self,
+            value: Some(T::clone(&self.current.load())),
+            _writer: writer,
+        })
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T: Clone> LockBackend<T> for SnapshotLock<T> {
+    type ReadGuard<'a>
+        = StoreSnapshot<T>
+    where
+        T: 'a;
+    type WriteGuard<'a>
+        = SnapshotWriteGuard<'a, T>
+    where
+        T: 'a;
+    type Pin<'a>
+        = SnapshotPin<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        SnapshotLock::new(value)
+    }
+
+    fn read(&self) -> Result<Self::ReadGuard<'_>> {
+        SnapshotLock::read(self)
+    }
+
+    fn write(&self) -> Result<Self::WriteGuard<'_>> {
+        SnapshotLock::write(self)
+    }
+
+    fn pin(&self) -> Result<Self::Pin<'_>> {
+        SnapshotLock::pin(self)
+    }
+
+    fn set_policy(&self, policy: PoisonPolicy) {
+        SnapshotLock::set_policy(self, policy)
+    }
+
+    fn is_poisoned(&self) -> bool {
+        SnapshotLock::is_poisoned(self)
+    }
+
+    fn clear_poison(&self) -> bool {
+        SnapshotLock::clear_poison(self)
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T: Debug> Debug for SnapshotLock<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SnapshotLock")
+            .field(&self.current.load())
+            .finish()
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T: Default> Default for SnapshotLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// Point-in-time view of a `SnapshotLock` store, unaffected by later writes.
+#[cfg(feature = "persistent")]
+pub struct StoreSnapshot<T>(Arc<T>);
+
+#[cfg(feature = "persistent")]
+impl<T> Deref for StoreSnapshot<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(feature = "persistent")]
+pub struct SnapshotPin<'a, T> {
+    value: Arc<T>,
+    _writer: std::sync::MutexGuard<'a, ()>,
+}
+
+#[cfg(feature = "persistent")]
+impl<T> Deref for SnapshotPin<'_, T> {
+    type Target = Arc<T>;
+    fn deref(&self) -> &Arc<T> {
+        &self.value
+    }
+}
+
+#[cfg(feature = "persistent")]
+pub struct SnapshotWriteGuard<'a, T> {
+    lock: &'a SnapshotLock<T>,
+    value: Option<T>,
+    _writer: std::sync::MutexGuard<'a, ()>,
+}
+
+#[cfg(feature = "persistent")]
+impl<T> Deref for SnapshotWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.value.as_ref().expect("value is taken only on drop")
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T> DerefMut for SnapshotWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().expect("value is taken only on drop")
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T> Drop for SnapshotWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Published while the writer lock is still held, a panicking writer discards its copy.
+        if let Some(value) = self.value.take().filter(|_| !std::thread::panicking()) {
+            self.lock.current.store(Arc::new(value));
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "key_value",
+    feature = "list",
+    feature = "map",
+    feature = "vector"
+))]
+impl MiniKVDB {
+    /// Sets the poison policy of every store, shared by all handles.
+    pub fn set_poison_policy(&self, policy: PoisonPolicy) {
+        #[cfg(feature = "key_value")]
+        self.kv.set_policy(policy);
+        #[cfg(feature = "list")]
+        self.list.set_policy(policy);
+        #[cfg(feature = "map")]
+        self.map.set_policy(policy);
+        #[cfg(feature = "vector")]
+        self.vector.set_policy(policy);
+    }
+
+    pub fn poisoned_stores(&self) -> Vec<StoreKind> {
+        [
+            #[cfg(feature = "key_value")]
+            (StoreKind::KeyValue, self.kv.is_poisoned()),
+            #[cfg(feature = "list")]
+            (StoreKind::List, self.list.is_poisoned()),
+            #[cfg(feature = "map")]
+            (StoreKind::Map, self.map.is_poisoned()),
+            #[cfg(feature = "vector")]
+            (StoreKind::Vector, self.vector.is_poisoned()),
+        ]
+        .into_iter()
+        .filter_map(|(kind, poisoned)| poisoned.then_some(kind))
+        .collect()
+    }
+
+    /// Clears the poison of every store regardless of the policy and returns the stores
+    /// that were poisoned. Interrupted writes are rolled back when an undo record was kept.
+    pub fn clear_poison(&self) -> Vec<StoreKind> {
+        let mut cleared = vec![];
+        #[cfg(feature = "key_value")]
+        if self.kv.clear_poison() {
+            cleared.push(StoreKind::KeyValue);
+        }
+        #[cfg(feature = "list")]
+        if self.list.clear_poison() {
+            cleared.push(StoreKind::List);
+        }
+        #[cfg(feature = "map")]
+        if self.map.clear_poison() {
+            cleared.push(StoreKind::Map);
+        }
+        #[cfg(feature = "vector")]
+        if self.vector.clear_poison() {
+            cleared.push(StoreKind::Vector);
+        }
+        cleared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Values that are only appended, so the journal records the previous lengths.
+    #[derive(Debug, Default, Clone)]
+    struct Values {
+        values: Vec<i32>,
+        journal: Journal<usize>,
+    }
+
+    impl Values {
+        fn new(values: &[i32]) -> Self {
+            Self {
+                values: values.to_vec(),
+                journal: Journal::default(),
+            }
+        }
+
+        fn push(&mut self, value: i32) {
+            let len = self.values.len();
+            self.journal.record(|| len);
+            self.values.push(value);
+        }
+    }
+
+    impl Journaled for Values {
+        fn set_journaling(&mut self, enabled: bool) {
+            self.journal.set_enabled(enabled);
+        }
+
+        fn rollback(&mut self) {
+            for len in self.journal.take() {
+                self.values.truncate(len);
+            }
+        }
+    }
+
+    fn poison(lock: &Arc<StoreLock<Values>>) {
+        let lock = lock.clone();
+        let _ = std::thread::spawn(move || {
+            let mut v = lock.write().unwrap();
+            v.push(2);
+            v.push(3);
+            panic!("interrupted write");
+        })
+        .join();
+    }
+
+    #[test]
+    fn fails_until_poison_is_cleared() {
+        let lock = Arc::new(StoreLock::new(Values::new(&[1])));
+        poison(&lock);
+
+        assert!(lock.is_poisoned());
+        assert!(matches!(lock.read(), Err(MiniKVDBError::RWLockReadPoison)));
+        assert!(matches!(
+            lock.write(),
+            Err(MiniKVDBError::RWLockWritePoison)
+        ));
+        assert!(lock.clear_poison());
+        assert_eq!(lock.read().unwrap().values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn recovers_with_interrupted_write() {
+        let lock = Arc::new(StoreLock::new(Values::new(&[1])));
+        lock.set_policy(PoisonPolicy::Recover);
+        poison(&lock);
+
+        assert_eq!(lock.read().unwrap().values, vec![1, 2, 3]);
+        assert!(!lock.is_poisoned());
+    }
+
+    #[test]
+    fn rolls_back_interrupted_write() {
+        let lock = Arc::new(StoreLock::new(Values::new(&[1])));
+        lock.set_policy(PoisonPolicy::Rollback);
+        lock.write().unwrap().push(4);
+        poison(&lock);
+
+        lock.write().unwrap().push(5);
+        assert_eq!(lock.read().unwrap().values, vec![1, 4, 5]);
+    }
+
+    #[test]
+    fn keeps_no_undo_record_after_write() {
+        let lock = StoreLock::new(Values::new(&[]));
+        lock.set_policy(PoisonPolicy::Rollback);
+        lock.write().unwrap().push(1);
+
+        assert!(lock.read().unwrap().journal.clone().take().next().is_none());
+    }
+
+    #[cfg(feature = "persistent")]
+    #[test]
+    fn snapshots_are_unaffected_by_writes() {
+        let lock = Arc::new(SnapshotLock::new(vec![1]));
+        let snapshot = lock.read().unwrap();
+        lock.write().unwrap().push(2);
+        assert_eq!(*snapshot, vec![1]);
+        assert_eq!(*lock.read().unwrap(), vec![1, 2]);
+
+        let writer = lock.clone();
+        let _ = std::thread::spawn(move || {
+            writer.write().unwrap().push(3);
+            let mut v = writer.write().unwrap();
+            v.push(4);
+            panic!("interrupted write");
+        })
+        .join();
+        assert!(lock.is_poisoned());
+        assert_eq!(*lock.read().unwrap(), vec![1, 2, 3]);
+        assert!(lock.write().is_err());
+        assert!(lock.clear_poison());
+        lock.write().unwrap().push(5);
+        assert_eq!(*lock.read().unwrap(), vec![1, 2, 3, 5]);
+    }
+
+    #[cfg(feature = "key_value")]
+    #[test]
+    fn inspects_and_clears_poisoned_stores() {
+        use crate::kv_store::kv_command::{DeleteCommand, SetCommand};
+
+        let db = MiniKVDB::default();
+        db.set_poison_policy(PoisonPolicy::Rollback);
+        db.set("a", 1).unwrap();
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut store = db.kv.shards()[0].write().unwrap();
+            store.set(SetCommand("a".into(), 2.into()));
+            store.set(SetCommand("b".into(), 3.into()));
+            store.delete(DeleteCommand("a".into()));
+            panic!("interrupted write");
+        }));
+
+        assert_eq!(db.poisoned_stores(), vec![StoreKind::KeyValue]);
+        assert_eq!(db.clear_poison(), vec![StoreKind::KeyValue]);
+        assert!(db.poisoned_stores().is_empty());
+        assert_eq!(db.get("a").unwrap(), Some(1.into()));
+        assert_eq!(db.get("b").unwrap(), None);
+    }
+}
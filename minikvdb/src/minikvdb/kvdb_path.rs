@@ -0,0 +1,240 @@
+use std::str::FromStr;
+
+use crate::error::{MiniKVDBError, Result};
+
+use super::{
+    kvdb_key::Key,
+    kvdb_type::KVDBType,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(Key),
+    Index(usize),
+}
+
+impl PathSegment {
+    /// Error for a value the segment cannot step into.
+    fn wrong_type(&self, value: &KVDBValue) -> MiniKVDBError {
+        let expected = match self {
+            PathSegment::Field(_) => KVDBType::Map,
+            PathSegment::Index(_) => KVDBType::List,
+        };
+        MiniKVDBError::wrong_type(expected, value)
+    }
+}
+
+/// Path into nested values of an object, e.g. `address.city` or `tags[2]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KVDBPath(pub Vec<PathSegment>);
+
+impl FromStr for KVDBPath {
+    type Err = MiniKVDBError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || MiniKVDBError::InvalidPath(s.to_owned());
+        let mut segments = vec![];
+        for part in s.split('.') {
+            let (field, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+            if field.is_empty() || field.contains(']') {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Field(field.into()));
+            while !rest.is_empty() {
+                let end = rest.find(']').ok_or_else(invalid)?;
+                let index = rest[1..end].parse().map_err(|_| invalid())?;
+                segments.push(PathSegment::Index(index));
+                rest = &rest[end + 1..];
+                if !rest.is_empty() && !rest.starts_with('[') {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(Self(segments))
+    }
+}
+
+impl TryFrom<&str> for KVDBPath {
+    type Error = MiniKVDBError;
+
+    fn try_from(value: &str) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl KVDBPath {
+    pub fn get<'a>(&self, obj: &'a KVDBObject) -> Option<&'a KVDBValue> {
+        let mut segments = self.0.iter();
+        let Some(PathSegment::Field(first)) = segments.next() else {
+            return None;
+        };
+        let mut current = obj.get(first)?;
+        for segment in segments {
+            current = match (segment, current) {
+                (PathSegment::Field(f), KVDBValue::Map(m)) => m.get(f)?,
+                (PathSegment::Index(i), KVDBValue::List(l)) => l.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Sets the value at the path, creating missing maps and lists along the way. List
+    /// indexes must exist, or be equal to the list length to append. Returns the replaced value.
+    pub fn set(&self, obj: &mut KVDBObject, value: KVDBValue) -> Result<Option<KVDBValue>> {
+        let invalid = || MiniKVDBError::InvalidPath(self.to_string());
+        let Some((PathSegment::Field(first), rest)) = self.0.split_first() else {
+            return Err(invalid());
+        };
+        let Some((last, _)) = rest.split_last() else {
+            return Ok(obj.insert(first.clone(), value));
+        };
+
+        let empty = |next: &PathSegment| match next {
+            PathSegment::Field(_) => KVDBValue::Map(Default::default()),
+            PathSegment::Index(_) => KVDBValue::List(vec![]),
+        };
+
+        let mut current = obj.entry(first.clone()).or_insert_with(|| empty(&rest[0]));
+        for (segment, next) in rest.iter().zip(rest.iter().skip(1)) {
+            current = match (segment, current) {
+                (PathSegment::Field(f), KVDBValue::Map(m)) => {
+                    m.entry(f.clone()).or_insert_with(|| empty(next))
+                }
+                (PathSegment::Index(i), KVDBValue::List(l)) => {
+                    if *i == l.len() {
+                        l.push(empty(next));
+                    }
+                    l.get_mut(*i).ok_or(MiniKVDBError::IndexOutOfBounds(*i))?
+                }
+                (segment, v) => return Err(segment.wrong_type(v)),
+            };
+        }
+
+        match (last, current) {
+            (PathSegment::Field(f), KVDBValue::Map(m)) => Ok(m.insert(f.clone(), value)),
+            (PathSegment::Index(i), KVDBValue::List(l)) if *i == l.len() => {
+                l.push(value);
+                Ok(None)
+            }
+            (PathSegment::Index(i), KVDBValue::List(l)) => {
+                let slot = l.get_mut(*i).ok_or(MiniKVDBError::IndexOutOfBounds(*i))?;
+                Ok(Some(std::mem::replace(slot, value)))
+            }
+            (segment, v) => Err(segment.wrong_type(v)),
+        }
+    }
+}
+
+impl std::fmt::Display for KVDBPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Field(k) if i == 0 => write!(f, "{}", k.0)?,
+                PathSegment::Field(k) => write!(f, ".{}", k.0)?,
+                PathSegment::Index(idx) => write!(f, "[{idx}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn person() -> KVDBObject {
+        let address: KVDBObject = [("city".into(), "Warsaw".into())].into();
+        [
+            ("name".into(), "tom".into()),
+            ("address".into(), address.into()),
+            (
+                "tags".into(),
+                vec!["a".to_owned(), "b".to_owned(), "c".to_owned()].into(),
+            ),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn parses_paths() {
+        let path: KVDBPath = "a.b[2][0].c".parse().unwrap();
+        assert_eq!(
+            path.0,
+            vec![
+                PathSegment::Field("a".into()),
+                PathSegment::Field("b".into()),
+                PathSegment::Index(2),
+                PathSegment::Index(0),
+                PathSegment::Field("c".into()),
+            ]
+        );
+        assert_eq!(path.to_string(), "a.b[2][0].c");
+        assert!("a..b".parse::<KVDBPath>().is_err());
+        assert!("a[x]".parse::<KVDBPath>().is_err());
+        assert!("a[1]b".parse::<KVDBPath>().is_err());
+        assert!("a]".parse::<KVDBPath>().is_err());
+        assert!("a.b]c[0]".parse::<KVDBPath>().is_err());
+    }
+
+    #[test]
+    fn gets_nested_values() {
+        let obj = person();
+        let city = KVDBPath::try_from("address.city").unwrap().get(&obj);
+        let tag = KVDBPath::try_from("tags[2]").unwrap().get(&obj);
+        let missing = KVDBPath::try_from("tags[3]").unwrap().get(&obj);
+
+        assert_eq!(city, Some(&"Warsaw".into()));
+        assert_eq!(tag, Some(&"c".into()));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn sets_nested_values() {
+        let mut obj = person();
+        let old = KVDBPath::try_from("address.city")
+            .unwrap()
+            .set(&mut obj, "Berlin".into())
+            .unwrap();
+        assert_eq!(old, Some("Warsaw".into()));
+
+        KVDBPath::try_from("tags[3]")
+            .unwrap()
+            .set(&mut obj, "d".into())
+            .unwrap();
+        KVDBPath::try_from("meta.source.name")
+            .unwrap()
+            .set(&mut obj, "import".into())
+            .unwrap();
+
+        KVDBPath::try_from("links[0].url")
+            .unwrap()
+            .set(&mut obj, "http://a".into())
+            .unwrap();
+
+        let tags: Vec<String> = obj["tags"].clone().try_into().unwrap();
+        assert_eq!(tags, vec!["a", "b", "c", "d"]);
+
+        let meta: HashMap<String, KVDBValue> = obj["meta"].clone().try_into().unwrap();
+        assert_eq!(
+            meta["source"],
+            KVDBValue::Map([("name".into(), "import".into())].into())
+        );
+        assert_eq!(
+            KVDBPath::try_from("links[0].url").unwrap().get(&obj),
+            Some(&"http://a".into())
+        );
+
+        assert!(KVDBPath::try_from("tags[9]")
+            .unwrap()
+            .set(&mut obj, "x".into())
+            .is_err());
+        assert!(KVDBPath::try_from("name.first")
+            .unwrap()
+            .set(&mut obj, "x".into())
+            .is_err());
+    }
+}
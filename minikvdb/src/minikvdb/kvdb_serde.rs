@@ -0,0 +1,927 @@
+use std::fmt::Display;
+
+use serde::{
+    de::{
+        self, value::MapDeserializer, value::SeqDeserializer, DeserializeOwned, EnumAccess,
+        IntoDeserializer, VariantAccess, Visitor,
+    },
+    ser::{self, Impossible},
+    Deserializer, Serialize,
+};
+
+use crate::error::{MiniKVDBError, Result};
+
+use super::{
+    kvdb_key::Key,
+    kvdb_type::KVDBType,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+/// Error raised while converting between serde types and `KVDBValue`.
+#[derive(Debug, Clone)]
+pub struct SerdeError(String);
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl From<SerdeError> for MiniKVDBError {
+    fn from(value: SerdeError) -> Self {
+        MiniKVDBError::Serde(value.0)
+    }
+}
+
+type SerdeResult<T> = std::result::Result<T, SerdeError>;
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<KVDBValue> {
+    Ok(value.serialize(ValueSerializer)?)
+}
+
+/// Serializes a struct or map into an object.
+pub fn to_object<T: Serialize + ?Sized>(value: &T) -> Result<KVDBObject> {
+    match to_value(value)? {
+        KVDBValue::Map(obj) => Ok(obj),
+        v => Err(MiniKVDBError::wrong_type(KVDBType::Map, &v)),
+    }
+}
+
+pub fn from_value<T: DeserializeOwned>(value: KVDBValue) -> Result<T> {
+    Ok(T::deserialize(value)?)
+}
+
+pub fn from_object<T: DeserializeOwned>(obj: KVDBObject) -> Result<T> {
+    from_value(KVDBValue::Map(obj))
+}
+
+/// Serializer producing `KVDBValue`s. Enums are externally tagged like in serde_json.
+pub struct ValueSerializer;
+
+impl ValueSerializer {
+    fn int(v: i128) -> SerdeResult<KVDBValue> {
+        if let Ok(v) = i32::try_from(v) {
+            return Ok(KVDBValue::Int(v));
+        }
+        #[cfg(feature = "big-types")]
+        {
+            if let Ok(v) = i64::try_from(v) {
+                return Ok(KVDBValue::Long(v));
+            }
+            Ok(KVDBValue::Int128(v))
+        }
+        #[cfg(not(feature = "big-types"))]
+        Err(ser::Error::custom(format!("integer {v} out of range")))
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, v: bool) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> SerdeResult<KVDBValue> {
+        Self::int(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> SerdeResult<KVDBValue> {
+        Self::int(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> SerdeResult<KVDBValue> {
+        Self::int(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> SerdeResult<KVDBValue> {
+        Self::int(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> SerdeResult<KVDBValue> {
+        match i128::try_from(v) {
+            Ok(v) => Self::int(v),
+            #[cfg(feature = "big-types")]
+            Err(_) => Ok(KVDBValue::UInt128(v)),
+            #[cfg(not(feature = "big-types"))]
+            Err(_) => Err(ser::Error::custom(format!("integer {v} out of range"))),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Float(v))
+    }
+
+    #[cfg(feature = "big-types")]
+    fn serialize_f64(self, v: f64) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Double(v))
+    }
+
+    /// Without `big-types` only doubles that are exact as `f32` can be stored.
+    #[cfg(not(feature = "big-types"))]
+    fn serialize_f64(self, v: f64) -> SerdeResult<KVDBValue> {
+        let narrowed = v as f32;
+        if v.is_nan() || narrowed as f64 == v {
+            Ok(KVDBValue::Float(narrowed))
+        } else {
+            Err(ser::Error::custom(format!("float {v} loses precision")))
+        }
+    }
+
+    fn serialize_char(self, v: char) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Bytes(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> SerdeResult<KVDBValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> SerdeResult<KVDBValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> SerdeResult<KVDBValue> {
+        Ok(KVDBValue::Map(
+            [(variant.into(), value.serialize(self)?)].into(),
+        ))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> SerdeResult<SerializeList> {
+        Ok(SerializeList {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> SerdeResult<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> SerdeResult<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> SerdeResult<SerializeList> {
+        Ok(SerializeList {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> SerdeResult<SerializeObject> {
+        Ok(SerializeObject::default())
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> SerdeResult<SerializeObject> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> SerdeResult<SerializeObject> {
+        Ok(SerializeObject {
+            variant: Some(variant),
+            ..Default::default()
+        })
+    }
+}
+
+/// Wraps the value in a single entry object when serializing an enum variant.
+fn tagged(variant: Option<&'static str>, value: KVDBValue) -> KVDBValue {
+    match variant {
+        Some(variant) => KVDBValue::Map([(variant.into(), value)].into()),
+        None => value,
+    }
+}
+
+pub struct SerializeList {
+    variant: Option<&'static str>,
+    items: Vec<KVDBValue>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        Ok(tagged(self.variant, KVDBValue::List(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+#[derive(Default)]
+pub struct SerializeObject {
+    variant: Option<&'static str>,
+    obj: KVDBObject,
+    next_key: Option<Key>,
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> SerdeResult<()> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| ser::Error::custom("map value without a key"))?;
+        self.obj.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        Ok(tagged(self.variant, KVDBValue::Map(self.obj)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> SerdeResult<()> {
+        self.obj
+            .insert(key.into(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = KVDBValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> SerdeResult<()> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> SerdeResult<KVDBValue> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Object keys are strings; numbers, chars and unit variants are converted to strings.
+struct KeySerializer;
+
+impl KeySerializer {
+    fn key(v: impl Display) -> SerdeResult<Key> {
+        Ok(Key(v.to_string()))
+    }
+
+    fn unsupported() -> SerdeResult<Key> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Key;
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<Key, SerdeError>;
+    type SerializeTuple = Impossible<Key, SerdeError>;
+    type SerializeTupleStruct = Impossible<Key, SerdeError>;
+    type SerializeTupleVariant = Impossible<Key, SerdeError>;
+    type SerializeMap = Impossible<Key, SerdeError>;
+    type SerializeStruct = Impossible<Key, SerdeError>;
+    type SerializeStructVariant = Impossible<Key, SerdeError>;
+
+    fn serialize_bool(self, v: bool) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_f64(self, _v: f64) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_char(self, v: char) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_str(self, v: &str) -> SerdeResult<Key> {
+        Self::key(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_none(self) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> SerdeResult<Key> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> SerdeResult<Key> {
+        Self::key(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> SerdeResult<Key> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> SerdeResult<Key> {
+        Self::unsupported()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> SerdeResult<Self::SerializeSeq> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> SerdeResult<Self::SerializeTuple> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeTupleStruct> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeTupleVariant> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> SerdeResult<Self::SerializeMap> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeStruct> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeStructVariant> {
+        Err(ser::Error::custom("object keys must be strings or numbers"))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for KVDBValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for KVDBValue {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            KVDBValue::Null => visitor.visit_unit(),
+            KVDBValue::Int(v) => visitor.visit_i32(v),
+            KVDBValue::Float(v) => visitor.visit_f32(v),
+            KVDBValue::Bool(v) => visitor.visit_bool(v),
+            KVDBValue::String(v) => visitor.visit_string(v),
+            KVDBValue::Bytes(v) => visitor.visit_byte_buf(v),
+            KVDBValue::List(v) => {
+                let mut seq = SeqDeserializer::new(v.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            KVDBValue::Map(v) => {
+                let mut map =
+                    MapDeserializer::new(v.into_iter().map(|(k, v)| (KeyDeserializer(k.0), v)));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTimeUtc(v) => visitor.visit_string(v.to_rfc3339()),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(v) => visitor.visit_i64(v),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(v) => visitor.visit_f64(v),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(v) => visitor.visit_u32(v),
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(v) => visitor.visit_u64(v),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(v) => visitor.visit_i128(v),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(v) => visitor.visit_u128(v),
+            #[cfg(feature = "chrono")]
+            KVDBValue::Date(v) => visitor.visit_string(v.to_string()),
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTime(v) => {
+                visitor.visit_string(v.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+            }
+            #[cfg(feature = "uuid")]
+            KVDBValue::Uuid(v) => visitor.visit_string(v.to_string()),
+            #[cfg(feature = "decimal")]
+            KVDBValue::Decimal(v) => visitor.visit_string(v.to_string()),
+            #[cfg(feature = "duration")]
+            KVDBValue::Duration(v) => {
+                let fields = [
+                    ("secs", KVDBValue::String(v.as_secs().to_string())),
+                    ("nanos", KVDBValue::Int(v.subsec_nanos() as i32)),
+                ];
+                visitor.visit_map(MapDeserializer::new(fields.into_iter()))
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            KVDBValue::Null => visitor.visit_none(),
+            v => visitor.visit_some(v),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        match self {
+            KVDBValue::String(variant) => visitor.visit_enum(VariantDeserializer {
+                variant,
+                value: None,
+            }),
+            KVDBValue::Map(obj) if obj.len() == 1 => {
+                let (variant, value) = obj.into_iter().next().unwrap();
+                visitor.visit_enum(VariantDeserializer {
+                    variant: variant.0,
+                    value: Some(value),
+                })
+            }
+            _ => Err(de::Error::custom(
+                "expected a string or a single entry map for an enum",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// Object keys are strings, numeric and bool keys are parsed back when requested.
+struct KeyDeserializer(String);
+
+impl<'de> IntoDeserializer<'de, SerdeError> for KeyDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => visitor.visit_string(self.0),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        KVDBValue::String(self.0).deserialize_enum(name, variants, visitor)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+    }
+
+    serde::forward_to_deserialize_any! {
+        f32 f64 char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct VariantDeserializer {
+    variant: String,
+    value: Option<KVDBValue>,
+}
+
+impl<'de> EnumAccess<'de> for VariantDeserializer {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> SerdeResult<(V::Value, Self)> {
+        let variant = seed.deserialize(self.variant.as_str().into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> SerdeResult<()> {
+        match self.value {
+            None | Some(KVDBValue::Null) => Ok(()),
+            Some(_) => Err(de::Error::custom("expected a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> SerdeResult<T::Value> {
+        match self.value {
+            Some(v) => seed.deserialize(v),
+            None => Err(de::Error::custom("expected a newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> SerdeResult<V::Value> {
+        match self.value {
+            Some(v @ KVDBValue::List(_)) => v.deserialize_any(visitor),
+            _ => Err(de::Error::custom("expected a tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        match self.value {
+            Some(v @ KVDBValue::Map(_)) => v.deserialize_any(visitor),
+            _ => Err(de::Error::custom("expected a struct variant")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Status {
+        Active,
+        Banned { reason: String },
+        Moved(String),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u8,
+        nickname: Option<String>,
+        tags: Vec<String>,
+        address: Address,
+        status: Status,
+        history: Vec<Status>,
+        scores: BTreeMap<u32, f32>,
+        point: (i32, i32),
+    }
+
+    fn user() -> User {
+        User {
+            name: "tom".into(),
+            age: 22,
+            nickname: None,
+            tags: vec!["a".into(), "b".into()],
+            address: Address {
+                city: "Warsaw".into(),
+            },
+            status: Status::Banned {
+                reason: "spam".into(),
+            },
+            history: vec![Status::Active, Status::Moved("x".into())],
+            scores: [(1, 0.5)].into(),
+            point: (1, -1),
+        }
+    }
+
+    #[test]
+    fn serializes_structs_into_objects() {
+        let obj = to_object(&user()).unwrap();
+
+        assert_eq!(obj["name"], "tom".into());
+        assert_eq!(obj["age"], 22.into());
+        assert_eq!(obj["nickname"], KVDBValue::Null);
+        assert_eq!(obj["tags"], vec!["a".to_owned(), "b".to_owned()].into());
+        assert_eq!(
+            obj["address"],
+            KVDBValue::Map([("city".into(), "Warsaw".into())].into())
+        );
+        assert_eq!(
+            obj["history"],
+            KVDBValue::List(vec![
+                "Active".into(),
+                KVDBValue::Map([("Moved".into(), "x".into())].into())
+            ])
+        );
+        assert_eq!(
+            obj["scores"],
+            KVDBValue::Map([("1".into(), 0.5f32.into())].into())
+        );
+    }
+
+    #[test]
+    fn round_trips_serde_types() {
+        let obj = to_object(&user()).unwrap();
+        let back: User = from_object(obj).unwrap();
+        assert_eq!(back, user());
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        let obj: KVDBObject = [("city".into(), 1.into())].into();
+        assert!(from_object::<Address>(obj).is_err());
+        assert!(to_object(&1).is_err());
+    }
+
+    #[cfg(not(feature = "big-types"))]
+    #[test]
+    fn rejects_doubles_losing_precision() {
+        assert_eq!(0.5f64.serialize(ValueSerializer).unwrap(), 0.5f32.into());
+        assert!(f64::INFINITY.serialize(ValueSerializer).is_ok());
+        assert!(f64::NAN.serialize(ValueSerializer).is_ok());
+        assert!(0.1f64.serialize(ValueSerializer).is_err());
+        assert!(1e40f64.serialize(ValueSerializer).is_err());
+    }
+
+    #[cfg(feature = "map")]
+    #[test]
+    fn stores_serde_types_in_hashes() {
+        let db = crate::minikvdb::MiniKVDB::default();
+        db.hash_set_serde("user:1", &user()).unwrap();
+
+        assert_eq!(db.hash_get("user:1", "age").unwrap(), Some(22.into()));
+        assert_eq!(db.hash_get_serde::<User>("user:1").unwrap(), Some(user()));
+        assert_eq!(db.hash_get_serde::<User>("user:2").unwrap(), None);
+        assert!(db.hash_get_serde::<Address>("user:1").is_err());
+    }
+}
@@ -0,0 +1,402 @@
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+use std::sync::Arc;
+use std::{marker::PhantomData, ops::DerefMut};
+
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Result;
+
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+use super::MiniKVDB;
+use super::{
+    kvdb_key::Key,
+    kvdb_lock::{LockBackend, PoisonPolicy, StoreLock},
+    KVDBStore,
+};
+
+/// Store whose entries can be distributed over shards and merged back.
+pub trait ShardedStore: KVDBStore + Default + Clone {
+    /// Empty store with the same configuration, such as indexes or schemas.
+    fn empty_like(&self) -> Self {
+        Self::default()
+    }
+
+    /// Moves every entry into `shards[shard(key)]`, along with its history.
+    fn split_into(self, shards: &mut [Self], shard: impl Fn(&Key) -> usize);
+
+    /// Moves every entry of `other` into the store, along with its history.
+    fn merge(&mut self, other: Self);
+
+    fn key_count(&self) -> usize;
+
+    fn has_key(&self, key: &Key) -> bool;
+
+    /// Moves the entry of the key into `other`, replacing the entry there.
+    fn move_entry(&mut self, key: &Key, other: &mut Self) -> Result<()>;
+
+    /// Serializes the shards as the single store holding all of their entries.
+    fn serialize_shards<S: Serializer>(
+        shards: &[&Self],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>;
+}
+
+/// Entries of the maps of every shard, serialized as one map.
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+pub(crate) struct ShardEntries<'a, M>(pub Vec<&'a M>);
+
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+impl<'a, M, K, V> Serialize for ShardEntries<'a, M>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    <&'a M as IntoIterator>::IntoIter: ExactSizeIterator,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let len = self.0.iter().map(|m| m.into_iter().len()).sum();
+        let mut map = serializer.serialize_map(Some(len))?;
+        for (k, v) in self.0.iter().flat_map(|m| *m) {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+/// Store split by key hash into shards, each behind its own lock. Operations on several
+/// shards lock them in ascending shard order.
+#[derive(Debug)]
+pub struct Sharded<T, L = StoreLock<T>> {
+    shards: Vec<L>,
+    _store: PhantomData<fn() -> T>,
+}
+
+impl<T: ShardedStore, L: LockBackend<T>> Sharded<T, L> {
+    /// At least one shard is created.
+    pub fn new(count: usize) -> Self {
+        Self::from_store(T::default(), count)
+    }
+
+    pub fn from_store(store: T, count: usize) -> Self {
+        let count = count.max(1);
+        let mut shards = vec![store.empty_like(); count];
+        store.split_into(&mut shards, |k| shard_index(k, count));
+        Self {
+            shards: shards.into_iter().map(L::new).collect(),
+            _store: PhantomData,
+        }
+    }
+
+    /// Copy of all shards merged into one store.
+    pub fn merged(&self) -> Result<T> {
+        let guards = self.read_all()?;
+        let mut merged = guards[0].empty_like();
+        for guard in guards.iter() {
+            merged.merge((**guard).clone());
+        }
+        Ok(merged)
+    }
+
+    pub fn read_all(&self) -> Result<Vec<L::ReadGuard<'_>>> {
+        self.shards.iter().map(L::read).collect()
+    }
+
+    pub fn write_all(&self) -> Result<Vec<L::WriteGuard<'_>>> {
+        self.shards.iter().map(L::write).collect()
+    }
+
+    /// Blocks writers of every shard until the pins are dropped.
+    pub fn pin_all(&self) -> Result<Vec<L::Pin<'_>>> {
+        self.shards.iter().map(L::pin).collect()
+    }
+
+    /// Write guards of the shard holding the key in this store and in `other`, locked in
+    /// a fixed order so that moves in opposite directions never deadlock. The stores have
+    /// to be different.
+    pub fn write_pair<'a>(
+        &'a self,
+        other: &'a Self,
+        key: &Key,
+    ) -> Result<(L::WriteGuard<'a>, L::WriteGuard<'a>)> {
+        let (a, b) = (self.shard(key), other.shard(key));
+        if locks_first(self, other) {
+            let a = a.write()?;
+            Ok((a, b.write()?))
+        } else {
+            let b = b.write()?;
+            Ok((a.write()?, b))
+        }
+    }
+
+    /// Write guards of all shards of this store and of `other`, see `write_pair`.
+    #[allow(clippy::type_complexity)]
+    pub fn write_all_pair<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> Result<(Vec<L::WriteGuard<'a>>, Vec<L::WriteGuard<'a>>)> {
+        if locks_first(self, other) {
+            let a = self.write_all()?;
+            Ok((a, other.write_all()?))
+        } else {
+            let b = other.write_all()?;
+            Ok((self.write_all()?, b))
+        }
+    }
+
+    pub fn key_count(&self) -> Result<usize> {
+        Ok(self.read_all()?.iter().map(|s| s.key_count()).sum())
+    }
+
+    /// Locks the shards holding the keys in ascending shard order, so that concurrent
+    /// multi-key operations never deadlock.
+    pub fn write_keys<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a Key>,
+    ) -> Result<ShardGuards<'_, T, L>> {
+        let mut indexes: Vec<usize> = keys.into_iter().map(|k| self.index(k)).collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+        let guards = indexes
+            .into_iter()
+            .map(|i| self.shards[i].write().map(|g| (i, g)))
+            .collect::<Result<_>>()?;
+        Ok(ShardGuards {
+            count: self.shards.len(),
+            guards,
+        })
+    }
+}
+
+impl<T: ShardedStore, L: LockBackend<T>> Sharded<T, L> {
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn index(&self, key: &Key) -> usize {
+        shard_index(key, self.shards.len())
+    }
+
+    pub fn shard(&self, key: &Key) -> &L {
+        &self.shards[self.index(key)]
+    }
+
+    pub fn shards(&self) -> &[L] {
+        &self.shards
+    }
+
+    pub fn set_policy(&self, policy: PoisonPolicy) {
+        self.shards.iter().for_each(|s| s.set_policy(policy));
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.shards.iter().any(L::is_poisoned)
+    }
+
+    /// Returns whether any shard was poisoned.
+    pub fn clear_poison(&self) -> bool {
+        let mut cleared = false;
+        for shard in self.shards.iter() {
+            cleared |= shard.clear_poison();
+        }
+        cleared
+    }
+}
+
+impl<T: ShardedStore, L: LockBackend<T>> Default for Sharded<T, L> {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Stores are persisted as one store holding the entries of every shard and loaded into
+/// a single shard, so the shard count can change between runs, see
+/// `MiniKVDB::with_shards_from`.
+impl<T: ShardedStore, L: LockBackend<T>> Serialize for Sharded<T, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let guards = self.read_all().map_err(serde::ser::Error::custom)?;
+        let shards: Vec<&T> = guards.iter().map(|g| &**g).collect();
+        T::serialize_shards(&shards, serializer)
+    }
+}
+
+impl<'de, T, L> Deserialize<'de> for Sharded<T, L>
+where
+    T: ShardedStore + Deserialize<'de>,
+    L: LockBackend<T>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        T::deserialize(deserializer).map(|store| Self::from_store(store, 1))
+    }
+}
+
+/// Write guards of the shards locked by `Sharded::write_keys`.
+pub struct ShardGuards<'a, T, L: LockBackend<T> + 'a> {
+    count: usize,
+    guards: Vec<(usize, L::WriteGuard<'a>)>,
+}
+
+impl<T, L: LockBackend<T>> ShardGuards<'_, T, L> {
+    /// Shard holding the key, which has to be one of the locked keys.
+    pub fn store(&mut self, key: &Key) -> &mut T {
+        let index = shard_index(key, self.count);
+        let (_, guard) = self
+            .guards
+            .iter_mut()
+            .find(|(i, _)| *i == index)
+            .expect("key was not locked");
+        guard
+    }
+}
+
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+impl MiniKVDB {
+    /// Database whose key-value, list and map stores are split into `count` shards.
+    /// `MiniKVDB::default()` uses a single shard.
+    pub fn with_shards(count: usize) -> Self {
+        Self {
+            #[cfg(feature = "key_value")]
+            kv: Arc::new(Sharded::new(count)),
+            #[cfg(feature = "list")]
+            list: Arc::new(Sharded::new(count)),
+            #[cfg(feature = "map")]
+            map: Arc::new(Sharded::new(count)),
+            ..Default::default()
+        }
+    }
+
+    /// Copy of the database with its stores split into `count` shards, such as a
+    /// database loaded from disk, which always has a single shard. The copy is a new
+    /// standalone database without the other databases reachable from `db`, and its
+    /// stores use the default poison policy.
+    pub fn with_shards_from(db: &MiniKVDB, count: usize) -> Result<Self> {
+        Ok(Self {
+            #[cfg(feature = "key_value")]
+            kv: Arc::new(Sharded::from_store(db.kv.merged()?, count)),
+            #[cfg(feature = "list")]
+            list: Arc::new(Sharded::from_store(db.list.merged()?, count)),
+            #[cfg(feature = "map")]
+            map: Arc::new(Sharded::from_store(db.map.merged()?, count)),
+            #[cfg(feature = "vector")]
+            vector: Arc::new(StoreLock::new((*db.vector.read()?).clone())),
+            coercion: db.coercion,
+            ..Default::default()
+        })
+    }
+}
+
+/// Exchanges the entries and configuration of two locked stores.
+pub fn swap_shards<T: ShardedStore>(
+    a: &mut [impl DerefMut<Target = T>],
+    b: &mut [impl DerefMut<Target = T>],
+) {
+    if a.len() == b.len() {
+        for (a, b) in a.iter_mut().zip(b.iter_mut()) {
+            std::mem::swap(&mut **a, &mut **b);
+        }
+    } else {
+        let (merged_a, merged_b) = (take_merged(a), take_merged(b));
+        refill(a, merged_b);
+        refill(b, merged_a);
+    }
+}
+
+/// Removes every entry of a locked store, keeping its configuration.
+pub fn clear_shards<T: ShardedStore>(shards: &mut [impl DerefMut<Target = T>]) {
+    for shard in shards.iter_mut() {
+        let empty = shard.empty_like();
+        **shard = empty;
+    }
+}
+
+fn take_merged<T: ShardedStore>(shards: &mut [impl DerefMut<Target = T>]) -> T {
+    let mut merged = shards[0].empty_like();
+    for shard in shards.iter_mut() {
+        let empty = shard.empty_like();
+        merged.merge(std::mem::replace(&mut **shard, empty));
+    }
+    merged
+}
+
+fn refill<T: ShardedStore>(shards: &mut [impl DerefMut<Target = T>], store: T) {
+    let count = shards.len();
+    let mut split = vec![store.empty_like(); count];
+    store.split_into(&mut split, |k| shard_index(k, count));
+    for (shard, store) in shards.iter_mut().zip(split) {
+        **shard = store;
+    }
+}
+
+/// Whether `a` is locked before `b` when both are needed.
+pub(crate) fn locks_first<T>(a: &T, b: &T) -> bool {
+    (a as *const T) < (b as *const T)
+}
+
+/// FNV-1a hash of the key, stable across runs and platforms.
+pub(crate) fn shard_index(key: &Key, count: usize) -> usize {
+    if count == 1 {
+        return 0;
+    }
+    let hash = key.0.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash % count as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distributes_keys_deterministically() {
+        let keys: Vec<Key> = (0..100).map(|i| format!("user:{i}").into()).collect();
+        let counts = keys.iter().fold([0; 8], |mut counts, k| {
+            counts[shard_index(k, 8)] += 1;
+            counts
+        });
+        assert!(counts.iter().all(|c| *c > 0));
+        assert_eq!(
+            shard_index(&"user:1".into(), 8),
+            shard_index(&"user:1".into(), 8)
+        );
+        assert!(keys.iter().all(|k| shard_index(k, 1) == 0));
+    }
+
+    #[cfg(feature = "key_value")]
+    #[test]
+    fn reshards_loaded_database() {
+        let db = MiniKVDB::with_shards(4);
+        for i in 0..20 {
+            db.set(format!("key:{i}"), i).unwrap();
+        }
+        let json = serde_json::to_string(&db).unwrap();
+        let loaded: MiniKVDB = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.kv.shard_count(), 1);
+
+        let resharded = MiniKVDB::with_shards_from(&loaded, 4).unwrap();
+        assert_eq!(resharded.kv.shard_count(), 4);
+        assert_eq!(resharded.kv.key_count().unwrap(), 20);
+        assert_eq!(resharded.get("key:7").unwrap(), Some(7.into()));
+    }
+
+    #[cfg(feature = "map")]
+    #[test]
+    fn serializes_shards_as_one_store() {
+        use crate::prelude::{KVDBObject, MapIndexKind};
+
+        let (sharded, single) = (MiniKVDB::with_shards(4), MiniKVDB::default());
+        for db in [&sharded, &single] {
+            db.hash_create_index("user:", "age", MapIndexKind::Ordered)
+                .unwrap();
+            for i in 0..10 {
+                let obj = KVDBObject::from([("age".into(), i.into())]);
+                db.hash_set(format!("user:{i}"), obj).unwrap();
+            }
+        }
+        assert_eq!(
+            serde_json::to_value(&sharded).unwrap(),
+            serde_json::to_value(&single).unwrap()
+        );
+    }
+}
@@ -0,0 +1,168 @@
+#[cfg(feature = "persistent")]
+use std::sync::Arc;
+
+#[cfg(feature = "persistent")]
+use crate::error::Result;
+
+#[cfg(any(feature = "map", feature = "persistent"))]
+use super::kvdb_key::Key;
+#[cfg(feature = "persistent")]
+use super::{kvdb_coerce::Coercion, kvdb_shard::shard_index, MiniKVDB};
+
+/// Read-only view of the key-value, list and map stores at one instant, returned by
+/// `MiniKVDB::snapshot`. Reads never lock and are unaffected by later writes. Requires
+/// the `persistent` feature.
+#[cfg(feature = "persistent")]
+#[derive(Debug, Clone)]
+pub struct MiniKVDBSnapshot {
+    #[cfg(feature = "key_value")]
+    pub(crate) kv: Vec<Arc<crate::kv_store::KVStore>>,
+    #[cfg(feature = "list")]
+    pub(crate) list: Vec<Arc<crate::list_store::ListStore>>,
+    #[cfg(feature = "map")]
+    pub(crate) map: Vec<Arc<crate::map_store::MapStore>>,
+    pub(crate) coercion: Coercion,
+}
+
+/// Shard of a snapshotted store holding the key.
+#[cfg(feature = "persistent")]
+pub(crate) fn shard<'a, T>(shards: &'a [Arc<T>], key: &Key) -> &'a T {
+    &shards[shard_index(key, shards.len())]
+}
+
+/// Sorted keys starting with the prefix.
+#[cfg(any(feature = "map", feature = "persistent"))]
+pub(crate) fn scan<'a>(keys: impl Iterator<Item = &'a Key>, prefix: &Key) -> Vec<Key> {
+    let mut keys: Vec<Key> = keys
+        .filter(|k| k.0.starts_with(&prefix.0))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys
+}
+
+#[cfg(feature = "persistent")]
+impl MiniKVDB {
+    /// Consistent read-only view of the key-value, list and map stores, the vector store
+    /// is not included. Writers wait only while every shard is pinned, the stores are
+    /// shared with the snapshot instead of copied. Entries, map indexes, search indexes
+    /// and history are persistent collections, so the first write to a shard still
+    /// shared with a snapshot copies only the parts of it the write changes.
+    pub fn snapshot(&self) -> Result<MiniKVDBSnapshot> {
+        #[cfg(feature = "key_value")]
+        let kv = self.kv.pin_all()?;
+        #[cfg(feature = "list")]
+        let list = self.list.pin_all()?;
+        #[cfg(feature = "map")]
+        let map = self.map.pin_all()?;
+        Ok(MiniKVDBSnapshot {
+            #[cfg(feature = "key_value")]
+            kv: kv.iter().map(|pin| Arc::clone(pin)).collect(),
+            #[cfg(feature = "list")]
+            list: list.iter().map(|pin| Arc::clone(pin)).collect(),
+            #[cfg(feature = "map")]
+            map: map.iter().map(|pin| Arc::clone(pin)).collect(),
+            coercion: self.coercion,
+        })
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl MiniKVDBSnapshot {
+    pub fn coercion(&self) -> Coercion {
+        self.coercion
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "persistent",
+    feature = "key_value",
+    feature = "list",
+    feature = "map"
+))]
+mod tests {
+    use crate::{minikvdb::kvdb_value::KVDBObject, values};
+
+    use super::*;
+
+    #[test]
+    fn reads_state_at_time_of_snapshot() {
+        let db = MiniKVDB::with_shards(4);
+        db.set("user:1", "tom").unwrap();
+        db.set("user:2", "bob").unwrap();
+        db.push_back("queue", values![1, 2]).unwrap();
+        db.hash_set("item:1", KVDBObject::from([("n".into(), 1.into())]))
+            .unwrap();
+
+        let snapshot = db.snapshot().unwrap();
+        db.set("user:1", "ann").unwrap();
+        db.set("user:3", "joe").unwrap();
+        db.push_back("queue", values![3]).unwrap();
+        db.hash_delete("item:1").unwrap();
+
+        assert_eq!(snapshot.get("user:1"), Some("tom".into()));
+        assert_eq!(
+            snapshot.keys("user:"),
+            vec!["user:1".into(), "user:2".into()]
+        );
+        assert_eq!(snapshot.list_range(("queue",)), Some(values![1, 2]));
+        assert_eq!(snapshot.hash_get("item:1", "n"), Some(1.into()));
+        assert_eq!(snapshot.hash_count("item:"), 1);
+
+        assert_eq!(db.get("user:1").unwrap(), Some("ann".into()));
+        assert_eq!(db.list_len("queue").unwrap(), Some(3));
+        assert!(!db.hash_contains_key("item:1").unwrap());
+    }
+
+    #[test]
+    fn reads_indexes_at_time_of_snapshot() {
+        use crate::prelude::MapIndexKind;
+
+        let db = MiniKVDB::with_shards(2);
+        let item = |n: i32| KVDBObject::from([("n".into(), n.into())]);
+        db.hash_create_index("item:", "n", MapIndexKind::Ordered)
+            .unwrap();
+        for n in 0..4 {
+            db.hash_set(format!("item:{n}"), item(n)).unwrap();
+        }
+
+        let snapshot = db.snapshot().unwrap();
+        db.hash_set("item:0", item(9)).unwrap();
+        db.hash_delete("item:3").unwrap();
+
+        assert_eq!(
+            snapshot.hash_find_keys("item:", "n", 0),
+            vec!["item:0".into()]
+        );
+        assert_eq!(
+            snapshot.hash_find_range_keys("item:", "n", 2..),
+            vec!["item:2".into(), "item:3".into()]
+        );
+        assert_eq!(
+            db.hash_find_range_keys("item:", "n", 2..).unwrap(),
+            vec!["item:2".into(), "item:0".into()]
+        );
+    }
+
+    #[test]
+    fn never_sees_a_multi_shard_write_half_done() {
+        let db = MiniKVDB::with_shards(8);
+        db.push_back("a", values![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+        std::thread::scope(|s| {
+            let writer = db.clone();
+            s.spawn(move || {
+                for i in 0..500 {
+                    let (from, to) = if i % 16 < 8 { ("a", "b") } else { ("b", "a") };
+                    writer.list_move((from, to)).unwrap();
+                }
+            });
+            for _ in 0..200 {
+                let snapshot = db.snapshot().unwrap();
+                let len = snapshot.list_len("a").unwrap_or(0) + snapshot.list_len("b").unwrap_or(0);
+                assert_eq!(len, 8);
+            }
+        });
+    }
+}
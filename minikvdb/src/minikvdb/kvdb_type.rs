@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, marker::PhantomData};
+
+use super::{
+    kvdb_key::Key,
+    kvdb_value::{KVDBObject, KVDBValue},
+};
+
+/// Variant of a `KVDBValue` without its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KVDBType {
+    Null,
+    Int,
+    Float,
+    Bool,
+    String,
+    Bytes,
+    List,
+    Map,
+    #[cfg(feature = "chrono")]
+    DateTimeUtc,
+    #[cfg(feature = "big-types")]
+    Long,
+    #[cfg(feature = "big-types")]
+    Double,
+    #[cfg(feature = "big-types")]
+    UInt,
+    #[cfg(feature = "big-types")]
+    ULong,
+    #[cfg(feature = "big-types")]
+    Int128,
+    #[cfg(feature = "big-types")]
+    UInt128,
+    #[cfg(feature = "chrono")]
+    Date,
+    #[cfg(feature = "chrono")]
+    DateTime,
+    #[cfg(feature = "uuid")]
+    Uuid,
+    #[cfg(feature = "decimal")]
+    Decimal,
+    #[cfg(feature = "duration")]
+    Duration,
+}
+
+impl Display for KVDBType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl KVDBValue {
+    pub fn kvdb_type(&self) -> KVDBType {
+        match self {
+            KVDBValue::Null => KVDBType::Null,
+            KVDBValue::Int(_) => KVDBType::Int,
+            KVDBValue::Float(_) => KVDBType::Float,
+            KVDBValue::Bool(_) => KVDBType::Bool,
+            KVDBValue::String(_) => KVDBType::String,
+            KVDBValue::Bytes(_) => KVDBType::Bytes,
+            KVDBValue::List(_) => KVDBType::List,
+            KVDBValue::Map(_) => KVDBType::Map,
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTimeUtc(_) => KVDBType::DateTimeUtc,
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(_) => KVDBType::Long,
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(_) => KVDBType::Double,
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(_) => KVDBType::UInt,
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(_) => KVDBType::ULong,
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(_) => KVDBType::Int128,
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(_) => KVDBType::UInt128,
+            #[cfg(feature = "chrono")]
+            KVDBValue::Date(_) => KVDBType::Date,
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTime(_) => KVDBType::DateTime,
+            #[cfg(feature = "uuid")]
+            KVDBValue::Uuid(_) => KVDBType::Uuid,
+            #[cfg(feature = "decimal")]
+            KVDBValue::Decimal(_) => KVDBType::Decimal,
+            #[cfg(feature = "duration")]
+            KVDBValue::Duration(_) => KVDBType::Duration,
+        }
+    }
+}
+
+/// Rust types stored in entity fields, used to describe entities as schemas.
+pub trait KVDBFieldType {
+    /// Type of the stored value, `None` when any value is accepted.
+    fn kvdb_type() -> Option<KVDBType>;
+
+    /// Whether the field may be missing or null.
+    fn optional() -> bool {
+        false
+    }
+}
+
+impl<T: KVDBFieldType> KVDBFieldType for Option<T> {
+    fn kvdb_type() -> Option<KVDBType> {
+        T::kvdb_type()
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl KVDBFieldType for KVDBValue {
+    fn kvdb_type() -> Option<KVDBType> {
+        None
+    }
+}
+
+impl<T: KVDBFieldType> KVDBFieldType for Vec<T> {
+    fn kvdb_type() -> Option<KVDBType> {
+        Some(KVDBType::List)
+    }
+}
+
+impl KVDBFieldType for KVDBObject {
+    fn kvdb_type() -> Option<KVDBType> {
+        Some(KVDBType::Map)
+    }
+}
+
+impl<T: KVDBFieldType> KVDBFieldType for HashMap<String, T> {
+    fn kvdb_type() -> Option<KVDBType> {
+        Some(KVDBType::Map)
+    }
+}
+
+/// Describes field types in derived `KVDBEntity::fields`. Method resolution prefers
+/// `KnownFieldType` and falls back to `AnyFieldType` for types without `KVDBFieldType`.
+#[doc(hidden)]
+pub struct FieldTypeProbe<T>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait KnownFieldType {
+    fn field_type(&self) -> (Option<KVDBType>, bool);
+}
+
+impl<T: KVDBFieldType> KnownFieldType for FieldTypeProbe<T> {
+    fn field_type(&self) -> (Option<KVDBType>, bool) {
+        (T::kvdb_type(), T::optional())
+    }
+}
+
+#[doc(hidden)]
+pub trait AnyFieldType {
+    fn field_type(&self) -> (Option<KVDBType>, bool) {
+        (None, false)
+    }
+}
+
+impl<T> AnyFieldType for &FieldTypeProbe<T> {}
+
+/// A field of an entity as stored in its object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityField {
+    pub name: Key,
+    pub kvdb_type: Option<KVDBType>,
+    pub required: bool,
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display};
 
 use super::kvdb_key::Key;
 
@@ -18,7 +18,10 @@ macro_rules! kvdb_map {
                 if let $crate::prelude::KVDBValue::$typ(ref v) = value {
                     Ok(v.to_owned())
                 } else {
-                    Err($crate::error::MiniKVDBError::WrongFieldType)
+                    Err($crate::error::MiniKVDBError::wrong_type(
+                        $crate::minikvdb::kvdb_type::KVDBType::$typ,
+                        &value,
+                    ))
                 }
             }
         }
@@ -29,16 +32,27 @@ macro_rules! kvdb_map {
                 if let $crate::prelude::KVDBValue::$typ(ref v) = value {
                     Ok(v.to_owned())
                 } else {
-                    Err($crate::error::MiniKVDBError::WrongFieldType)
+                    Err($crate::error::MiniKVDBError::wrong_type(
+                        $crate::minikvdb::kvdb_type::KVDBType::$typ,
+                        &value,
+                    ))
                 }
             }
         }
+
+        impl $crate::minikvdb::kvdb_type::KVDBFieldType for $v {
+            fn kvdb_type() -> Option<$crate::minikvdb::kvdb_type::KVDBType> {
+                Some($crate::minikvdb::kvdb_type::KVDBType::$typ)
+            }
+        }
     };
 }
 
 pub type KVDBObject = std::collections::HashMap<Key, KVDBValue>;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+/// New variants are appended, so that the variant indices used by non-self-describing
+/// formats keep decoding stored values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum KVDBValue {
     Int(i32),
     Float(f32),
@@ -50,21 +64,294 @@ pub enum KVDBValue {
     Long(i64),
     #[cfg(feature = "big-types")]
     Double(f64),
+    Bytes(#[serde(with = "serde_bytes")] Vec<u8>),
+    List(Vec<KVDBValue>),
+    Map(KVDBObject),
+    Null,
+    #[cfg(feature = "big-types")]
+    UInt(u32),
+    #[cfg(feature = "big-types")]
+    ULong(u64),
+    #[cfg(feature = "big-types")]
+    Int128(i128),
+    #[cfg(feature = "big-types")]
+    UInt128(u128),
+    #[cfg(feature = "chrono")]
+    Date(::chrono::NaiveDate),
+    #[cfg(feature = "chrono")]
+    DateTime(::chrono::NaiveDateTime),
+    #[cfg(feature = "uuid")]
+    Uuid(::uuid::Uuid),
+    #[cfg(feature = "decimal")]
+    Decimal(::rust_decimal::Decimal),
+    #[cfg(feature = "duration")]
+    Duration(std::time::Duration),
 }
 
 impl Display for KVDBValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            KVDBValue::Null => write!(f, "null"),
             KVDBValue::Int(v) => write!(f, "{v}"),
             KVDBValue::Float(v) => write!(f, "{v}"),
             KVDBValue::Bool(v) => write!(f, "{v}"),
             KVDBValue::String(v) => write!(f, "{v}"),
+            KVDBValue::Bytes(v) => write!(f, "b\"{}\"", v.escape_ascii()),
+            KVDBValue::List(v) => {
+                write!(f, "[")?;
+                for (i, v) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{v}")?;
+                }
+                write!(f, "]")
+            }
+            KVDBValue::Map(v) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in Self::sorted_entries(v).into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {v}", k.0)?;
+                }
+                write!(f, "}}")
+            }
             #[cfg(feature = "chrono")]
             KVDBValue::DateTimeUtc(v) => write!(f, "{v}"),
             #[cfg(feature = "big-types")]
             KVDBValue::Long(v) => write!(f, "{v}"),
             #[cfg(feature = "big-types")]
             KVDBValue::Double(v) => write!(f, "{v}"),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(v) => write!(f, "{v}"),
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(v) => write!(f, "{v}"),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(v) => write!(f, "{v}"),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(v) => write!(f, "{v}"),
+            #[cfg(feature = "chrono")]
+            KVDBValue::Date(v) => write!(f, "{v}"),
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTime(v) => write!(f, "{v}"),
+            #[cfg(feature = "uuid")]
+            KVDBValue::Uuid(v) => write!(f, "{v}"),
+            #[cfg(feature = "decimal")]
+            KVDBValue::Decimal(v) => write!(f, "{v}"),
+            #[cfg(feature = "duration")]
+            KVDBValue::Duration(v) => write!(f, "{v:?}"),
+        }
+    }
+}
+
+impl KVDBValue {
+    fn rank(&self) -> u8 {
+        match self {
+            KVDBValue::Null => 0,
+            KVDBValue::Int(_) => 1,
+            KVDBValue::Float(_) => 2,
+            KVDBValue::Bool(_) => 3,
+            KVDBValue::String(_) => 4,
+            KVDBValue::Bytes(_) => 5,
+            KVDBValue::List(_) => 6,
+            KVDBValue::Map(_) => 7,
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTimeUtc(_) => 8,
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(_) => 9,
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(_) => 10,
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(_) => 11,
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(_) => 12,
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(_) => 13,
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(_) => 14,
+            #[cfg(feature = "chrono")]
+            KVDBValue::Date(_) => 15,
+            #[cfg(feature = "chrono")]
+            KVDBValue::DateTime(_) => 16,
+            #[cfg(feature = "uuid")]
+            KVDBValue::Uuid(_) => 17,
+            #[cfg(feature = "decimal")]
+            KVDBValue::Decimal(_) => 18,
+            #[cfg(feature = "duration")]
+            KVDBValue::Duration(_) => 19,
+        }
+    }
+
+    /// Map entries sorted by key, so that maps can be compared and hashed.
+    pub(crate) fn sorted_entries(map: &KVDBObject) -> Vec<(&Key, &KVDBValue)> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    /// Total ordering over values. Values of different variants are ordered by variant,
+    /// with `Null` first, floating point values are ordered using `total_cmp`.
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (KVDBValue::Int(a), KVDBValue::Int(b)) => a.cmp(b),
+            (KVDBValue::Float(a), KVDBValue::Float(b)) => a.total_cmp(b),
+            (KVDBValue::Bool(a), KVDBValue::Bool(b)) => a.cmp(b),
+            (KVDBValue::String(a), KVDBValue::String(b)) => a.cmp(b),
+            (KVDBValue::Bytes(a), KVDBValue::Bytes(b)) => a.cmp(b),
+            (KVDBValue::List(a), KVDBValue::List(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (KVDBValue::Map(a), KVDBValue::Map(b)) => {
+                let (a, b) = (Self::sorted_entries(a), Self::sorted_entries(b));
+                a.iter()
+                    .zip(b.iter())
+                    .map(|((ak, av), (bk, bv))| ak.cmp(bk).then_with(|| av.total_cmp(bv)))
+                    .find(|o| o.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len()))
+            }
+            #[cfg(feature = "chrono")]
+            (KVDBValue::DateTimeUtc(a), KVDBValue::DateTimeUtc(b)) => a.cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::Long(a), KVDBValue::Long(b)) => a.cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::Double(a), KVDBValue::Double(b)) => a.total_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::UInt(a), KVDBValue::UInt(b)) => a.cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::ULong(a), KVDBValue::ULong(b)) => a.cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::Int128(a), KVDBValue::Int128(b)) => a.cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::UInt128(a), KVDBValue::UInt128(b)) => a.cmp(b),
+            #[cfg(feature = "chrono")]
+            (KVDBValue::Date(a), KVDBValue::Date(b)) => a.cmp(b),
+            #[cfg(feature = "chrono")]
+            (KVDBValue::DateTime(a), KVDBValue::DateTime(b)) => a.cmp(b),
+            #[cfg(feature = "uuid")]
+            (KVDBValue::Uuid(a), KVDBValue::Uuid(b)) => a.cmp(b),
+            #[cfg(feature = "decimal")]
+            (KVDBValue::Decimal(a), KVDBValue::Decimal(b)) => a.cmp(b),
+            #[cfg(feature = "duration")]
+            (KVDBValue::Duration(a), KVDBValue::Duration(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+
+    /// Integer and floating point values of every width.
+    fn numeric(&self) -> Option<Numeric> {
+        match self {
+            KVDBValue::Int(v) => Some(Numeric::Int(*v as i128)),
+            KVDBValue::Float(v) => Some(Numeric::Float(*v as f64)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Long(v) => Some(Numeric::Int(*v as i128)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Double(v) => Some(Numeric::Float(*v)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt(v) => Some(Numeric::Int(*v as i128)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::ULong(v) => Some(Numeric::Int(*v as i128)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::Int128(v) => Some(Numeric::Int(*v)),
+            #[cfg(feature = "big-types")]
+            KVDBValue::UInt128(v) => {
+                Some(i128::try_from(*v).map_or(Numeric::Float(*v as f64), Numeric::Int))
+            }
+            _ => None,
+        }
+    }
+
+    /// Compares values of the same kind: numbers of every width by value and other values
+    /// only with the same variant. `None` when the values cannot be compared.
+    pub fn kind_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.numeric(), other.numeric()) {
+            (Some(a), Some(b)) => Some(a.cmp(b)),
+            (None, None) if self.rank() == other.rank() => Some(self.total_cmp(other)),
+            _ => None,
+        }
+    }
+
+    /// Total ordering that keeps numbers of every width together, ordered by value and
+    /// then by variant, so that range lookups over ordered indexes cover all of them.
+    #[cfg(feature = "map")]
+    pub(crate) fn range_cmp(&self, other: &Self) -> Ordering {
+        let rank = |v: &Self| match v.numeric() {
+            Some(_) => KVDBValue::Int(0).rank(),
+            None => v.rank(),
+        };
+        match (self.numeric(), other.numeric()) {
+            (Some(a), Some(b)) => a.cmp(b).then_with(|| self.rank().cmp(&other.rank())),
+            (None, None) => self.total_cmp(other),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Numeric {
+    Int(i128),
+    Float(f64),
+}
+
+impl Numeric {
+    fn cmp(self, other: Self) -> Ordering {
+        match (self, other) {
+            (Numeric::Int(a), Numeric::Int(b)) => a.cmp(&b),
+            (Numeric::Float(a), Numeric::Float(b)) => a.total_cmp(&b),
+            (Numeric::Int(a), Numeric::Float(b)) => Self::int_float(a, b),
+            (Numeric::Float(a), Numeric::Int(b)) => Self::int_float(b, a).reverse(),
+        }
+    }
+
+    /// Exact comparison, `NaN` is greater than every integer.
+    fn int_float(i: i128, f: f64) -> Ordering {
+        match (i as f64).partial_cmp(&f) {
+            Some(Ordering::Equal) => i.cmp(&(f as i128)),
+            Some(ord) => ord,
+            None => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for KVDBValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (KVDBValue::Int(a), KVDBValue::Int(b)) => a.partial_cmp(b),
+            (KVDBValue::Float(a), KVDBValue::Float(b)) => a.partial_cmp(b),
+            (KVDBValue::Bool(a), KVDBValue::Bool(b)) => a.partial_cmp(b),
+            (KVDBValue::String(a), KVDBValue::String(b)) => a.partial_cmp(b),
+            (KVDBValue::Bytes(a), KVDBValue::Bytes(b)) => a.partial_cmp(b),
+            (KVDBValue::List(a), KVDBValue::List(b)) => a.partial_cmp(b),
+            // Maps have no natural order, they can only be equal.
+            (KVDBValue::Map(a), KVDBValue::Map(b)) => (a == b).then_some(Ordering::Equal),
+            #[cfg(feature = "chrono")]
+            (KVDBValue::DateTimeUtc(a), KVDBValue::DateTimeUtc(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::Long(a), KVDBValue::Long(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::Double(a), KVDBValue::Double(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::UInt(a), KVDBValue::UInt(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::ULong(a), KVDBValue::ULong(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::Int128(a), KVDBValue::Int128(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-types")]
+            (KVDBValue::UInt128(a), KVDBValue::UInt128(b)) => a.partial_cmp(b),
+            #[cfg(feature = "chrono")]
+            (KVDBValue::Date(a), KVDBValue::Date(b)) => a.partial_cmp(b),
+            #[cfg(feature = "chrono")]
+            (KVDBValue::DateTime(a), KVDBValue::DateTime(b)) => a.partial_cmp(b),
+            #[cfg(feature = "uuid")]
+            (KVDBValue::Uuid(a), KVDBValue::Uuid(b)) => a.partial_cmp(b),
+            #[cfg(feature = "decimal")]
+            (KVDBValue::Decimal(a), KVDBValue::Decimal(b)) => a.partial_cmp(b),
+            #[cfg(feature = "duration")]
+            (KVDBValue::Duration(a), KVDBValue::Duration(b)) => a.partial_cmp(b),
+            (a, b) => a.rank().partial_cmp(&b.rank()),
         }
     }
 }
@@ -73,6 +360,7 @@ kvdb_map!(i32, Int);
 kvdb_map!(f32, Float);
 kvdb_map!(bool, Bool);
 kvdb_map!(String, String);
+kvdb_map!(Vec<u8>, Bytes);
 
 impl From<&str> for KVDBValue {
     fn from(value: &str) -> Self {
@@ -80,15 +368,204 @@ impl From<&str> for KVDBValue {
     }
 }
 
+impl From<&[u8]> for KVDBValue {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.to_owned())
+    }
+}
+
+impl<T: Into<KVDBValue>> From<Vec<T>> for KVDBValue {
+    fn from(value: Vec<T>) -> Self {
+        Self::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T> TryFrom<KVDBValue> for Vec<T>
+where
+    T: TryFrom<KVDBValue>,
+    crate::error::MiniKVDBError: From<T::Error>,
+{
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: KVDBValue) -> Result<Self, Self::Error> {
+        if let KVDBValue::List(v) = value {
+            v.into_iter().map(|v| Ok(T::try_from(v)?)).collect()
+        } else {
+            Err(crate::error::MiniKVDBError::wrong_type(
+                crate::minikvdb::kvdb_type::KVDBType::List,
+                &value,
+            ))
+        }
+    }
+}
+
+impl<'a, T> TryFrom<&'a KVDBValue> for Vec<T>
+where
+    T: TryFrom<&'a KVDBValue>,
+    crate::error::MiniKVDBError: From<T::Error>,
+{
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: &'a KVDBValue) -> Result<Self, Self::Error> {
+        if let KVDBValue::List(v) = value {
+            v.iter().map(|v| Ok(T::try_from(v)?)).collect()
+        } else {
+            Err(crate::error::MiniKVDBError::wrong_type(
+                crate::minikvdb::kvdb_type::KVDBType::List,
+                value,
+            ))
+        }
+    }
+}
+
+impl<T: Into<KVDBValue>> From<std::collections::HashMap<String, T>> for KVDBValue {
+    fn from(value: std::collections::HashMap<String, T>) -> Self {
+        Self::Map(
+            value
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<T> TryFrom<KVDBValue> for std::collections::HashMap<String, T>
+where
+    T: TryFrom<KVDBValue>,
+    crate::error::MiniKVDBError: From<T::Error>,
+{
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: KVDBValue) -> Result<Self, Self::Error> {
+        if let KVDBValue::Map(v) = value {
+            v.into_iter()
+                .map(|(k, v)| Ok((k.0, T::try_from(v)?)))
+                .collect()
+        } else {
+            Err(crate::error::MiniKVDBError::wrong_type(
+                crate::minikvdb::kvdb_type::KVDBType::Map,
+                &value,
+            ))
+        }
+    }
+}
+
+impl<'a, T> TryFrom<&'a KVDBValue> for std::collections::HashMap<String, T>
+where
+    T: TryFrom<&'a KVDBValue>,
+    crate::error::MiniKVDBError: From<T::Error>,
+{
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: &'a KVDBValue) -> Result<Self, Self::Error> {
+        if let KVDBValue::Map(v) = value {
+            v.iter()
+                .map(|(k, v)| Ok((k.0.clone(), T::try_from(v)?)))
+                .collect()
+        } else {
+            Err(crate::error::MiniKVDBError::wrong_type(
+                crate::minikvdb::kvdb_type::KVDBType::Map,
+                value,
+            ))
+        }
+    }
+}
+
+impl<T: Into<KVDBValue>> From<Option<T>> for KVDBValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T> TryFrom<KVDBValue> for Option<T>
+where
+    T: TryFrom<KVDBValue, Error = crate::error::MiniKVDBError>,
+{
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: KVDBValue) -> Result<Self, Self::Error> {
+        match value {
+            KVDBValue::Null => Ok(None),
+            v => v.try_into().map(Some),
+        }
+    }
+}
+
+impl<T> TryFrom<&KVDBValue> for Option<T>
+where
+    T: for<'a> TryFrom<&'a KVDBValue, Error = crate::error::MiniKVDBError>,
+{
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: &KVDBValue) -> Result<Self, Self::Error> {
+        match value {
+            KVDBValue::Null => Ok(None),
+            v => v.try_into().map(Some),
+        }
+    }
+}
+
+impl KVDBValue {
+    pub fn is_null(&self) -> bool {
+        matches!(self, KVDBValue::Null)
+    }
+}
+
+impl From<KVDBObject> for KVDBValue {
+    fn from(value: KVDBObject) -> Self {
+        Self::Map(value)
+    }
+}
+
+impl TryFrom<KVDBValue> for KVDBObject {
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: KVDBValue) -> Result<Self, Self::Error> {
+        if let KVDBValue::Map(v) = value {
+            Ok(v)
+        } else {
+            Err(crate::error::MiniKVDBError::wrong_type(
+                crate::minikvdb::kvdb_type::KVDBType::Map,
+                &value,
+            ))
+        }
+    }
+}
+
+impl TryFrom<&KVDBValue> for KVDBObject {
+    type Error = crate::error::MiniKVDBError;
+    fn try_from(value: &KVDBValue) -> Result<Self, Self::Error> {
+        if let KVDBValue::Map(v) = value {
+            Ok(v.clone())
+        } else {
+            Err(crate::error::MiniKVDBError::wrong_type(
+                crate::minikvdb::kvdb_type::KVDBType::Map,
+                value,
+            ))
+        }
+    }
+}
+
 #[cfg(feature = "chrono")]
 kvdb_map!(chrono::DateTime<chrono::Utc>, DateTimeUtc);
 
+#[cfg(feature = "chrono")]
+kvdb_map!(chrono::NaiveDate, Date);
+#[cfg(feature = "chrono")]
+kvdb_map!(chrono::NaiveDateTime, DateTime);
+
 #[cfg(feature = "big-types")]
 mod big_types {
     kvdb_map!(i64, Long);
     kvdb_map!(f64, Double);
+    kvdb_map!(u32, UInt);
+    kvdb_map!(u64, ULong);
+    kvdb_map!(i128, Int128);
+    kvdb_map!(u128, UInt128);
 }
 
+#[cfg(feature = "uuid")]
+kvdb_map!(uuid::Uuid, Uuid);
+
+#[cfg(feature = "decimal")]
+kvdb_map!(rust_decimal::Decimal, Decimal);
+
+#[cfg(feature = "duration")]
+kvdb_map!(std::time::Duration, Duration);
+
 #[macro_export]
 macro_rules! values {
     ( $( $v:expr ),* ) => {
@@ -97,3 +574,37 @@ macro_rules! values {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_options_through_null() {
+        let nickname: Option<String> = None;
+        let value: KVDBValue = nickname.into();
+        assert!(value.is_null());
+        assert_eq!(value.to_string(), "null");
+
+        let nickname: Option<String> = value.try_into().unwrap();
+        assert_eq!(nickname, None);
+        let age: Option<i32> = KVDBValue::Int(3).try_into().unwrap();
+        assert_eq!(age, Some(3));
+        let age: Result<Option<i32>, _> = (&KVDBValue::from("x")).try_into();
+        assert!(age.is_err());
+
+        assert!(KVDBValue::Null.total_cmp(&KVDBValue::Int(i32::MIN)).is_lt());
+    }
+
+    #[test]
+    fn converts_nested_collections() {
+        let grid = vec![vec![1, 2], vec![3]];
+        let value: KVDBValue = grid.clone().into();
+        assert_eq!(Vec::<Vec<i32>>::try_from(&value).unwrap(), grid);
+        assert_eq!(Vec::<Vec<i32>>::try_from(value).unwrap(), grid);
+
+        let value = KVDBValue::List(crate::values!(1, "x"));
+        assert!(Vec::<i32>::try_from(&value).is_err());
+        assert_eq!(Vec::<KVDBValue>::try_from(value).unwrap().len(), 2);
+    }
+}
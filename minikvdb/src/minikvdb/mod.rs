@@ -1,18 +1,51 @@
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
+#[cfg(any(
+    feature = "key_value",
+    feature = "list",
+    feature = "map",
+    feature = "vector"
+))]
+use std::sync::Arc;
 
+#[cfg(feature = "vector")]
+use kvdb_lock::StoreLock;
+#[cfg(any(feature = "key_value", feature = "list", feature = "map"))]
+use kvdb_shard::Sharded;
+
+pub mod kvdb_coerce;
+pub mod kvdb_database;
+pub mod kvdb_entity;
+#[cfg(feature = "history")]
+pub mod kvdb_history;
 pub mod kvdb_key;
+pub mod kvdb_lock;
+pub mod kvdb_path;
+pub mod kvdb_serde;
+pub mod kvdb_shard;
+pub mod kvdb_snapshot;
+pub mod kvdb_type;
 pub mod kvdb_value;
 pub mod namespaced_key;
 
+/// In-memory database, clones of a handle share its stores.
+///
+/// A serialized database holds the entries of its stores but not their shard counts, so
+/// its stores are loaded into a single shard. Split them again after loading with
+/// `MiniKVDB::with_shards_from(&loaded, count)`.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MiniKVDB {
     #[cfg(feature = "key_value")]
-    pub(crate) kv: Arc<RwLock<crate::kv_store::KVStore>>,
+    pub(crate) kv: Arc<Sharded<crate::kv_store::KVStore>>,
     #[cfg(feature = "list")]
-    pub(crate) list: Arc<RwLock<crate::list_store::ListStore>>,
+    pub(crate) list: Arc<Sharded<crate::list_store::ListStore, crate::list_store::ListLock>>,
     #[cfg(feature = "map")]
-    pub(crate) map: Arc<RwLock<crate::map_store::MapStore>>,
+    pub(crate) map: Arc<Sharded<crate::map_store::MapStore>>,
+    #[cfg(feature = "vector")]
+    pub(crate) vector: Arc<StoreLock<crate::vector_store::VectorStore>>,
+    #[serde(skip)]
+    pub(crate) coercion: kvdb_coerce::Coercion,
+    #[serde(skip)]
+    pub(crate) database: kvdb_database::DatabaseRef,
 }
 
 pub trait KVDBStore {}
@@ -20,6 +20,12 @@ impl NamespacedKey {
     }
 }
 
+impl From<NamespacedKey> for Key {
+    fn from(value: NamespacedKey) -> Self {
+        value.compose()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,10 +1,36 @@
 #[cfg(feature = "key_value")]
-pub use crate::kv_store::{kv_command, KVStore};
+pub use crate::kv_store::{kv_command, kv_typed::TypedKey, KVStore};
 #[cfg(feature = "list")]
-pub use crate::list_store::{list_command, ListStore};
+pub use crate::list_store::{list_command, list_typed::TypedList, ListSlice, ListStore};
+#[cfg(feature = "schema")]
+pub use crate::map_store::map_schema::{FieldSchema, MapSchema, Violation, ViolationKind};
+#[cfg(feature = "search")]
+pub use crate::map_store::map_search::{SearchHit, SearchQuery};
 #[cfg(feature = "map")]
-pub use crate::map_store::{map_command, MapStore};
+pub use crate::map_store::{
+    map_aggregate::{Aggregation, Reducer},
+    map_command,
+    map_index::MapIndexKind,
+    map_migrate::{MigrationJob, MigrationReport},
+    map_query::{MapQuery, Predicate, SortOrder},
+    map_typed::TypedHash,
+    MapStore,
+};
 
+#[cfg(feature = "vector")]
+pub use crate::vector_store::{
+    vector_command::VectorQuery, VectorCollectionConfig, VectorIndexKind, VectorMetric, VectorStore,
+};
+
+pub use crate::minikvdb::kvdb_coerce::Coercion;
+pub use crate::minikvdb::kvdb_database::{DatabaseStats, DEFAULT_DATABASE};
+pub use crate::minikvdb::kvdb_entity::KVDBEntity;
+#[cfg(feature = "history")]
+pub use crate::minikvdb::kvdb_history::{HistoryRetention, Version};
+pub use crate::minikvdb::kvdb_lock::{PoisonPolicy, StoreKind};
+#[cfg(feature = "persistent")]
+pub use crate::minikvdb::kvdb_snapshot::MiniKVDBSnapshot;
+pub use crate::minikvdb::kvdb_type::{EntityField, KVDBFieldType, KVDBType};
 pub use crate::minikvdb::kvdb_value::*;
 pub use crate::minikvdb::MiniKVDB;
 pub use crate::values;
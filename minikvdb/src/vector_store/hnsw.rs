@@ -0,0 +1,330 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{hash_map::DefaultHasher, BinaryHeap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use crate::minikvdb::kvdb_key::Key;
+
+use super::{VectorEntry, VectorMetric};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate(f32, Key);
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .total_cmp(&other.0)
+            .then_with(|| self.1.cmp(&other.1))
+    }
+}
+
+/// Keeps the `max` links closest to `base`.
+fn keep_closest(
+    links: &mut Vec<Key>,
+    base: &[f32],
+    max: usize,
+    entries: &HashMap<Key, VectorEntry>,
+    metric: VectorMetric,
+) {
+    if links.len() <= max {
+        return;
+    }
+    links.sort_by(|a, b| {
+        metric
+            .distance(base, &entries[a].vector)
+            .total_cmp(&metric.distance(base, &entries[b].vector))
+    });
+    links.truncate(max);
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    // Neighbours on every layer from 0 up to the node level.
+    neighbours: Vec<Vec<Key>>,
+}
+
+/// Hierarchical navigable small world graph over vectors stored in a collection.
+#[derive(Debug, Clone)]
+pub struct Hnsw {
+    m: usize,
+    ef_construction: usize,
+    nodes: HashMap<Key, Node>,
+    entry: Option<Key>,
+}
+
+impl Hnsw {
+    pub fn new(m: usize, ef_construction: usize) -> Self {
+        Self {
+            m: m.max(2),
+            ef_construction: ef_construction.max(1),
+            nodes: HashMap::new(),
+            entry: None,
+        }
+    }
+
+    fn max_neighbours(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.m * 2
+        } else {
+            self.m
+        }
+    }
+
+    fn top_level(&self) -> usize {
+        self.entry
+            .as_ref()
+            .map_or(0, |e| self.nodes[e].neighbours.len() - 1)
+    }
+
+    /// Levels are derived from the key hash, so rebuilding the graph is deterministic.
+    fn level_for(&self, key: &Key) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let uniform = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+        let ml = 1.0 / (self.m as f64).ln();
+        (-(1.0 - uniform).ln() * ml).floor() as usize
+    }
+
+    fn search_layer(
+        &self,
+        distance: &impl Fn(&Key) -> f32,
+        entry_points: Vec<Candidate>,
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<Key> = entry_points.iter().map(|c| c.1.clone()).collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> =
+            entry_points.iter().cloned().map(Reverse).collect();
+        let mut found: BinaryHeap<Candidate> = entry_points.into_iter().collect();
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|f| current.0 > f.0) {
+                break;
+            }
+            let Some(neighbours) = self.nodes[&current.1].neighbours.get(layer) else {
+                continue;
+            };
+            for n in neighbours {
+                if !visited.insert(n.clone()) {
+                    continue;
+                }
+                let c = Candidate(distance(n), n.clone());
+                if found.len() < ef || found.peek().is_some_and(|f| c.0 < f.0) {
+                    candidates.push(Reverse(c.clone()));
+                    found.push(c);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    pub fn insert(&mut self, key: &Key, entries: &HashMap<Key, VectorEntry>, metric: VectorMetric) {
+        self.remove(key, entries, metric);
+
+        let query = &entries[key].vector;
+        let distance = |k: &Key| metric.distance(query, &entries[k].vector);
+        let level = self.level_for(key);
+
+        let Some(entry) = self.entry.clone() else {
+            self.nodes.insert(
+                key.clone(),
+                Node {
+                    neighbours: vec![vec![]; level + 1],
+                },
+            );
+            self.entry = Some(key.clone());
+            return;
+        };
+
+        let top = self.top_level();
+        let mut eps = vec![Candidate(distance(&entry), entry)];
+        for layer in (level + 1..=top).rev() {
+            eps = self.search_layer(&distance, eps, 1, layer);
+        }
+
+        let mut neighbours = vec![vec![]; level + 1];
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&distance, eps, self.ef_construction, layer);
+            neighbours[layer] = found
+                .iter()
+                .take(self.max_neighbours(layer))
+                .map(|c| c.1.clone())
+                .collect();
+            eps = found;
+        }
+
+        for (layer, keys) in neighbours.iter().enumerate() {
+            let max = self.max_neighbours(layer);
+            for n in keys {
+                let node = self.nodes.get_mut(n).expect("Neighbour is in the graph");
+                node.neighbours[layer].push(key.clone());
+                keep_closest(
+                    &mut node.neighbours[layer],
+                    &entries[n].vector,
+                    max,
+                    entries,
+                    metric,
+                );
+            }
+        }
+
+        self.nodes.insert(key.clone(), Node { neighbours });
+        if level > top {
+            self.entry = Some(key.clone());
+        }
+    }
+
+    /// Nodes linking to the removed one are linked to its neighbours instead, so that
+    /// the graph stays navigable.
+    pub fn remove(&mut self, key: &Key, entries: &HashMap<Key, VectorEntry>, metric: VectorMetric) {
+        let Some(removed) = self.nodes.remove(key) else {
+            return;
+        };
+        let m = self.m;
+        for (k, node) in self.nodes.iter_mut() {
+            for (layer, links) in node.neighbours.iter_mut().enumerate() {
+                let Some(pos) = links.iter().position(|n| n == key) else {
+                    continue;
+                };
+                links.remove(pos);
+                for n in removed.neighbours.get(layer).into_iter().flatten() {
+                    if n != k && !links.contains(n) {
+                        links.push(n.clone());
+                    }
+                }
+                let max = if layer == 0 { m * 2 } else { m };
+                keep_closest(links, &entries[k].vector, max, entries, metric);
+            }
+        }
+        if self.entry.as_ref() == Some(key) {
+            self.entry = self
+                .nodes
+                .iter()
+                .max_by(|(ak, a), (bk, b)| {
+                    a.neighbours
+                        .len()
+                        .cmp(&b.neighbours.len())
+                        .then_with(|| bk.cmp(ak))
+                })
+                .map(|(k, _)| k.clone());
+        }
+    }
+
+    /// Returns up to `ef` approximate nearest keys, closest first.
+    pub fn search(
+        &self,
+        query: &[f32],
+        ef: usize,
+        entries: &HashMap<Key, VectorEntry>,
+        metric: VectorMetric,
+    ) -> Vec<(Key, f32)> {
+        let Some(entry) = self.entry.clone() else {
+            return vec![];
+        };
+        let distance = |k: &Key| metric.distance(query, &entries[k].vector);
+
+        let mut eps = vec![Candidate(distance(&entry), entry)];
+        for layer in (1..=self.top_level()).rev() {
+            eps = self.search_layer(&distance, eps, 1, layer);
+        }
+        self.search_layer(&distance, eps, ef.max(1), 0)
+            .into_iter()
+            .map(|Candidate(d, k)| (k, d))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: usize) -> HashMap<Key, VectorEntry> {
+        (0..n)
+            .map(|i| {
+                let angle = i as f32 * 0.1;
+                (
+                    Key::from(i),
+                    VectorEntry {
+                        vector: vec![angle.cos() * (1.0 + i as f32), angle.sin()],
+                        metadata: Default::default(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Number of the 5 nearest keys the index finds.
+    fn recall(hnsw: &Hnsw, entries: &HashMap<Key, VectorEntry>, query: &[f32]) -> usize {
+        let mut exact: Vec<(Key, f32)> = entries
+            .iter()
+            .map(|(k, e)| (k.clone(), VectorMetric::L2.distance(query, &e.vector)))
+            .collect();
+        exact.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let found = hnsw.search(query, 32, entries, VectorMetric::L2);
+        let top: Vec<Key> = found.into_iter().take(5).map(|(k, _)| k).collect();
+        exact
+            .iter()
+            .take(5)
+            .filter(|(k, _)| top.contains(k))
+            .count()
+    }
+
+    #[test]
+    fn finds_same_neighbours_as_exhaustive_search() {
+        let entries = entries(200);
+        let mut hnsw = Hnsw::new(8, 64);
+        for i in 0..200 {
+            hnsw.insert(&Key::from(i), &entries, VectorMetric::L2);
+        }
+
+        assert!(recall(&hnsw, &entries, &[10.0, 0.3]) >= 4);
+    }
+
+    #[test]
+    fn removes_nodes_including_entry_point() {
+        let entries = entries(20);
+        let mut hnsw = Hnsw::new(4, 16);
+        for i in 0..20 {
+            hnsw.insert(&Key::from(i), &entries, VectorMetric::L2);
+        }
+        for i in 0..19 {
+            hnsw.remove(&Key::from(i), &entries, VectorMetric::L2);
+        }
+
+        let found = hnsw.search(&[0.0, 0.0], 10, &entries, VectorMetric::L2);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Key::from(19));
+    }
+
+    #[test]
+    fn keeps_recall_after_removing_half_of_the_nodes() {
+        let mut entries = entries(200);
+        let mut hnsw = Hnsw::new(8, 64);
+        for i in 0..200 {
+            hnsw.insert(&Key::from(i), &entries, VectorMetric::L2);
+        }
+        for i in (0..200).step_by(2) {
+            hnsw.remove(&Key::from(i), &entries, VectorMetric::L2);
+            entries.remove(&Key::from(i));
+        }
+
+        let total: usize = (0..20)
+            .map(|q| recall(&hnsw, &entries, &[q as f32 * 10.0 - 100.0, 0.3]))
+            .sum();
+        assert!(total >= 90, "recall {total} of 100");
+    }
+}
@@ -0,0 +1,523 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    error::{MiniKVDBError, Result},
+    minikvdb::{
+        kvdb_key::Key,
+        kvdb_lock::{Journal, Journaled},
+        kvdb_value::KVDBObject,
+        KVDBStore, MiniKVDB,
+    },
+};
+
+use self::{
+    hnsw::Hnsw,
+    vector_command::{
+        CreateCollectionCommand, DropCollectionCommand, VectorDeleteCommand, VectorGetCommand,
+        VectorQuery, VectorSearchCommand, VectorSetCommand,
+    },
+};
+
+pub mod hnsw;
+pub mod vector_command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VectorMetric {
+    Cosine,
+    L2,
+    Dot,
+}
+
+impl VectorMetric {
+    /// Distance between two vectors, lower is closer. For `Dot` it is the negated product.
+    pub fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let dot = || a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+        match self {
+            VectorMetric::Cosine => {
+                let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norms = norm(a) * norm(b);
+                if norms == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot() / norms
+                }
+            }
+            VectorMetric::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f32>()
+                .sqrt(),
+            VectorMetric::Dot => -dot(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VectorIndexKind {
+    Flat,
+    Hnsw {
+        m: usize,
+        ef_construction: usize,
+        ef_search: usize,
+    },
+}
+
+impl VectorIndexKind {
+    pub fn hnsw() -> Self {
+        Self::Hnsw {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorCollectionConfig {
+    pub dimension: usize,
+    pub metric: VectorMetric,
+    pub index: VectorIndexKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorEntry {
+    pub vector: Vec<f32>,
+    pub metadata: KVDBObject,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorHit {
+    pub key: Key,
+    pub distance: f32,
+    pub metadata: KVDBObject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "VectorCollectionRepr", into = "VectorCollectionRepr")]
+pub struct VectorCollection {
+    config: VectorCollectionConfig,
+    entries: HashMap<Key, VectorEntry>,
+    hnsw: Option<Hnsw>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VectorCollectionRepr {
+    config: VectorCollectionConfig,
+    entries: HashMap<Key, VectorEntry>,
+}
+
+impl From<VectorCollectionRepr> for VectorCollection {
+    fn from(value: VectorCollectionRepr) -> Self {
+        let mut collection = Self::new(value.config);
+        collection.entries = value.entries;
+        if let Some(hnsw) = collection.hnsw.as_mut() {
+            let mut keys: Vec<&Key> = collection.entries.keys().collect();
+            keys.sort();
+            for k in keys {
+                hnsw.insert(k, &collection.entries, collection.config.metric);
+            }
+        }
+        collection
+    }
+}
+
+impl From<VectorCollection> for VectorCollectionRepr {
+    fn from(value: VectorCollection) -> Self {
+        Self {
+            config: value.config,
+            entries: value.entries,
+        }
+    }
+}
+
+impl VectorCollection {
+    pub fn new(config: VectorCollectionConfig) -> Self {
+        let hnsw = match config.index {
+            VectorIndexKind::Flat => None,
+            VectorIndexKind::Hnsw {
+                m, ef_construction, ..
+            } => Some(Hnsw::new(m, ef_construction)),
+        };
+        Self {
+            config,
+            entries: HashMap::new(),
+            hnsw,
+        }
+    }
+
+    pub fn config(&self) -> &VectorCollectionConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn check_dimension(&self, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.config.dimension {
+            return Err(MiniKVDBError::DimensionMismatch(
+                self.config.dimension,
+                vector.len(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, key: Key, entry: VectorEntry) -> Result<Option<VectorEntry>> {
+        self.check_dimension(&entry.vector)?;
+        let old = self.entries.insert(key.clone(), entry);
+        if let Some(hnsw) = self.hnsw.as_mut() {
+            hnsw.insert(&key, &self.entries, self.config.metric);
+        }
+        Ok(old)
+    }
+
+    pub fn get(&self, key: &Key) -> Option<VectorEntry> {
+        self.entries.get(key).cloned()
+    }
+
+    pub fn delete(&mut self, key: &Key) -> Option<VectorEntry> {
+        if let Some(hnsw) = self.hnsw.as_mut() {
+            hnsw.remove(key, &self.entries, self.config.metric);
+        }
+        self.entries.remove(key)
+    }
+
+    pub fn search(&self, query: &VectorQuery) -> Result<Vec<VectorHit>> {
+        self.check_dimension(&query.vector)?;
+        let metric = self.config.metric;
+        let matches = |k: &Key| {
+            query
+                .filter
+                .as_ref()
+                .is_none_or(|f| f.matches(&self.entries[k].metadata))
+        };
+
+        let found: Vec<(Key, f32)> = match (&self.hnsw, self.config.index) {
+            (Some(hnsw), VectorIndexKind::Hnsw { ef_search, .. }) => {
+                // Widen the search until enough neighbours pass the filter.
+                let mut ef = ef_search.max(query.k);
+                loop {
+                    let found: Vec<(Key, f32)> = hnsw
+                        .search(&query.vector, ef, &self.entries, metric)
+                        .into_iter()
+                        .filter(|(k, _)| matches(k))
+                        .collect();
+                    if found.len() >= query.k || ef >= self.entries.len() {
+                        break found;
+                    }
+                    ef *= 2;
+                }
+            }
+            _ => {
+                let mut found: Vec<(Key, f32)> = self
+                    .entries
+                    .iter()
+                    .filter(|(k, _)| matches(k))
+                    .map(|(k, e)| (k.clone(), metric.distance(&query.vector, &e.vector)))
+                    .collect();
+                found.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+                found
+            }
+        };
+
+        Ok(found
+            .into_iter()
+            .take(query.k)
+            .map(|(key, distance)| VectorHit {
+                metadata: self.entries[&key].metadata.clone(),
+                key,
+                distance,
+            })
+            .collect())
+    }
+}
+
+/// Previous state of a collection, or of an entry of a collection.
+#[derive(Debug, Clone)]
+enum VectorUndo {
+    Collection(Key, Option<VectorCollection>),
+    Entry(Key, Key, Option<VectorEntry>),
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorStore(
+    HashMap<Key, VectorCollection>,
+    #[serde(skip)] Journal<VectorUndo>,
+);
+
+impl KVDBStore for VectorStore {}
+
+impl Journaled for VectorStore {
+    fn set_journaling(&mut self, enabled: bool) {
+        self.1.set_enabled(enabled);
+    }
+
+    fn rollback(&mut self) {
+        for undo in self.1.take() {
+            match undo {
+                VectorUndo::Collection(name, Some(collection)) => {
+                    self.0.insert(name, collection);
+                }
+                VectorUndo::Collection(name, None) => {
+                    self.0.remove(&name);
+                }
+                VectorUndo::Entry(name, key, old) => {
+                    if let Some(collection) = self.0.get_mut(&name) {
+                        match old {
+                            // The entry was stored, so it has the collection dimension.
+                            Some(entry) => collection.set(key, entry).ok(),
+                            None => collection.delete(&key).map(Some),
+                        };
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl VectorStore {
+    fn collection(&self, name: &Key) -> Result<&VectorCollection> {
+        self.0
+            .get(name)
+            .ok_or_else(|| MiniKVDBError::MissingCollection(name.0.clone()))
+    }
+
+    fn collection_mut(&mut self, name: &Key) -> Result<&mut VectorCollection> {
+        self.0
+            .get_mut(name)
+            .ok_or_else(|| MiniKVDBError::MissingCollection(name.0.clone()))
+    }
+
+    /// Removes every vector, keeping the collections.
+    pub fn clear(&mut self) {
+        for collection in self.0.values_mut() {
+            *collection = VectorCollection::new(collection.config);
+        }
+    }
+
+    pub fn vector_count(&self) -> usize {
+        self.0.values().map(VectorCollection::len).sum()
+    }
+
+    /// Fails with `CollectionExists` instead of replacing a collection.
+    pub fn create_collection(&mut self, cmd: impl Into<CreateCollectionCommand>) -> Result<()> {
+        let CreateCollectionCommand(name, config) = cmd.into();
+        if self.0.contains_key(&name) {
+            return Err(MiniKVDBError::CollectionExists(name.0));
+        }
+        self.1.record(|| VectorUndo::Collection(name.clone(), None));
+        self.0.insert(name, VectorCollection::new(config));
+        Ok(())
+    }
+
+    pub fn drop_collection(&mut self, cmd: impl Into<DropCollectionCommand>) -> bool {
+        let DropCollectionCommand(name) = cmd.into();
+        let Some(collection) = self.0.remove(&name) else {
+            return false;
+        };
+        self.1
+            .record(|| VectorUndo::Collection(name, Some(collection)));
+        true
+    }
+
+    pub fn set(&mut self, cmd: impl Into<VectorSetCommand>) -> Result<Option<VectorEntry>> {
+        let VectorSetCommand(name, key, vector, metadata) = cmd.into();
+        let old = self
+            .collection_mut(&name)?
+            .set(key.clone(), VectorEntry { vector, metadata })?;
+        self.1.record(|| VectorUndo::Entry(name, key, old.clone()));
+        Ok(old)
+    }
+
+    pub fn get(&self, cmd: impl Into<VectorGetCommand>) -> Result<Option<VectorEntry>> {
+        let VectorGetCommand(name, key) = cmd.into();
+        Ok(self.collection(&name)?.get(&key))
+    }
+
+    pub fn delete(&mut self, cmd: impl Into<VectorDeleteCommand>) -> Result<Option<VectorEntry>> {
+        let VectorDeleteCommand(name, key) = cmd.into();
+        let old = self.collection_mut(&name)?.delete(&key);
+        if old.is_some() {
+            self.1.record(|| VectorUndo::Entry(name, key, old.clone()));
+        }
+        Ok(old)
+    }
+
+    pub fn search(&self, cmd: impl Into<VectorSearchCommand>) -> Result<Vec<VectorHit>> {
+        let VectorSearchCommand(name, query) = cmd.into();
+        self.collection(&name)?.search(&query)
+    }
+}
+
+impl MiniKVDB {
+    pub fn vector_create_collection(
+        &self,
+        name: impl Into<Key>,
+        config: VectorCollectionConfig,
+    ) -> Result<()> {
+        self.vector
+            .write()?
+            .create_collection(CreateCollectionCommand(name.into(), config))
+    }
+
+    pub fn vector_drop_collection(&self, name: impl Into<Key>) -> Result<bool> {
+        Ok(self
+            .vector
+            .write()?
+            .drop_collection(DropCollectionCommand(name.into())))
+    }
+
+    pub fn vector_set(
+        &self,
+        collection: impl Into<Key>,
+        key: impl Into<Key>,
+        vector: impl Into<Vec<f32>>,
+        metadata: impl Into<KVDBObject>,
+    ) -> Result<Option<VectorEntry>> {
+        self.vector.write()?.set(VectorSetCommand(
+            collection.into(),
+            key.into(),
+            vector.into(),
+            metadata.into(),
+        ))
+    }
+
+    pub fn vector_get(
+        &self,
+        collection: impl Into<Key>,
+        key: impl Into<Key>,
+    ) -> Result<Option<VectorEntry>> {
+        self.vector
+            .read()?
+            .get(VectorGetCommand(collection.into(), key.into()))
+    }
+
+    pub fn vector_delete(
+        &self,
+        collection: impl Into<Key>,
+        key: impl Into<Key>,
+    ) -> Result<Option<VectorEntry>> {
+        self.vector
+            .write()?
+            .delete(VectorDeleteCommand(collection.into(), key.into()))
+    }
+
+    pub fn vector_search(
+        &self,
+        collection: impl Into<Key>,
+        query: VectorQuery,
+    ) -> Result<Vec<VectorHit>> {
+        self.vector
+            .read()?
+            .search(VectorSearchCommand(collection.into(), query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map_store::map_query::Predicate;
+
+    use super::*;
+
+    fn test_db(index: VectorIndexKind) -> VectorStore {
+        let mut db = VectorStore::default();
+        db.create_collection(CreateCollectionCommand(
+            "docs".into(),
+            VectorCollectionConfig {
+                dimension: 2,
+                metric: VectorMetric::Cosine,
+                index,
+            },
+        ))
+        .unwrap();
+        for (i, (x, y)) in [(1.0, 0.0), (0.9, 0.1), (0.0, 1.0), (-1.0, 0.0)]
+            .into_iter()
+            .enumerate()
+        {
+            let _ = db.set(VectorSetCommand(
+                "docs".into(),
+                format!("doc:{i}").into(),
+                vec![x, y],
+                [("lang".into(), if i % 2 == 0 { "en" } else { "pl" }.into())].into(),
+            ));
+        }
+        db
+    }
+
+    fn keys(hits: Vec<VectorHit>) -> Vec<Key> {
+        hits.into_iter().map(|h| h.key).collect()
+    }
+
+    #[test]
+    fn computes_distances() {
+        assert_eq!(VectorMetric::L2.distance(&[0.0, 0.0], &[3.0, 4.0]), 5.0);
+        assert_eq!(VectorMetric::Dot.distance(&[1.0, 2.0], &[3.0, 4.0]), -11.0);
+        assert_eq!(VectorMetric::Cosine.distance(&[1.0, 0.0], &[2.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn finds_top_k_with_flat_and_hnsw_index() {
+        for index in [VectorIndexKind::Flat, VectorIndexKind::hnsw()] {
+            let db = test_db(index);
+            let hits = db
+                .search(VectorSearchCommand(
+                    "docs".into(),
+                    VectorQuery::new([1.0, 0.05], 2),
+                ))
+                .unwrap();
+            assert_eq!(keys(hits), vec![Key::from("doc:0"), Key::from("doc:1")]);
+        }
+    }
+
+    #[test]
+    fn filters_by_metadata() {
+        for index in [VectorIndexKind::Flat, VectorIndexKind::hnsw()] {
+            let db = test_db(index);
+            let hits = db
+                .search(VectorSearchCommand(
+                    "docs".into(),
+                    VectorQuery::new([1.0, 0.0], 2).filter(Predicate::eq("lang", "pl")),
+                ))
+                .unwrap();
+            assert_eq!(keys(hits), vec![Key::from("doc:1"), Key::from("doc:3")]);
+        }
+    }
+
+    #[test]
+    fn rejects_vectors_with_wrong_dimension() {
+        let mut db = test_db(VectorIndexKind::Flat);
+        let res = db.set(VectorSetCommand(
+            "docs".into(),
+            "doc:9".into(),
+            vec![1.0],
+            Default::default(),
+        ));
+        assert!(matches!(res, Err(MiniKVDBError::DimensionMismatch(2, 1))));
+    }
+
+    #[test]
+    fn rebuilds_index_after_deserialization() {
+        let db = test_db(VectorIndexKind::hnsw());
+        let repr: VectorCollectionRepr = db.0["docs"].clone().into();
+        let collection: VectorCollection = repr.into();
+        let hits = collection.search(&VectorQuery::new([0.0, 1.0], 1)).unwrap();
+        assert_eq!(keys(hits), vec![Key::from("doc:2")]);
+    }
+
+    #[test]
+    fn keeps_existing_collection() {
+        let mut db = test_db(VectorIndexKind::Flat);
+        let config = db.0["docs"].config;
+        let res = db.create_collection(CreateCollectionCommand("docs".into(), config));
+        assert!(matches!(res, Err(MiniKVDBError::CollectionExists(_))));
+        assert_eq!(db.vector_count(), 4);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    map_store::map_query::Predicate,
+    minikvdb::{kvdb_key::Key, kvdb_value::KVDBObject},
+};
+
+use super::VectorCollectionConfig;
+
+#[derive(Debug, Clone)]
+pub struct CreateCollectionCommand(pub Key, pub VectorCollectionConfig);
+
+#[derive(Debug, Clone)]
+pub struct DropCollectionCommand(pub Key);
+
+#[derive(Debug, Clone)]
+pub struct VectorSetCommand(pub Key, pub Key, pub Vec<f32>, pub KVDBObject);
+
+#[derive(Debug, Clone)]
+pub struct VectorGetCommand(pub Key, pub Key);
+
+#[derive(Debug, Clone)]
+pub struct VectorDeleteCommand(pub Key, pub Key);
+
+#[derive(Debug, Clone)]
+pub struct VectorSearchCommand(pub Key, pub VectorQuery);
+
+#[derive(Debug, Clone)]
+pub struct VectorQuery {
+    pub vector: Vec<f32>,
+    pub k: usize,
+    pub filter: Option<Predicate>,
+}
+
+impl VectorQuery {
+    pub fn new(vector: impl Into<Vec<f32>>, k: usize) -> Self {
+        Self {
+            vector: vector.into(),
+            k,
+            filter: None,
+        }
+    }
+
+    /// Only returns vectors whose metadata matches the predicate.
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.filter = Some(match self.filter {
+            Some(f) => f.and(predicate),
+            None => predicate,
+        });
+        self
+    }
+}
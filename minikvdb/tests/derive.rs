@@ -0,0 +1,519 @@
+#![cfg(feature = "map")]
+
+use minikvdb::{error::MiniKVDBError, prelude::*};
+use minikvdb_macros::KVDBEntity;
+
+mod comma_list {
+    use minikvdb::{error::Result, prelude::KVDBValue};
+
+    pub fn into_value(v: Vec<String>) -> KVDBValue {
+        v.join(",").into()
+    }
+
+    pub fn from_value(v: &KVDBValue) -> Result<Vec<String>> {
+        let s: String = v.try_into()?;
+        Ok(s.split(',').map(str::to_owned).collect())
+    }
+}
+
+fn default_page_size() -> i32 {
+    20
+}
+
+#[derive(Debug, Default, PartialEq, KVDBEntity)]
+#[kvdb(rename_all = "camelCase")]
+struct Settings {
+    dark_mode: bool,
+    #[kvdb(rename = "lang")]
+    language: String,
+    #[kvdb(default = "default_page_size")]
+    page_size: i32,
+    #[kvdb(default)]
+    theme: String,
+    #[kvdb(skip)]
+    dirty: bool,
+    #[kvdb(with = "comma_list")]
+    tags: Vec<String>,
+}
+
+fn keys(obj: &KVDBObject) -> Vec<&str> {
+    let mut keys: Vec<&str> = obj.keys().map(|k| k.0.as_str()).collect();
+    keys.sort();
+    keys
+}
+
+#[test]
+fn round_trips_field_attributes() {
+    let db = MiniKVDB::default();
+    let settings = Settings {
+        dark_mode: true,
+        language: "pl".into(),
+        page_size: 50,
+        theme: "dark".into(),
+        dirty: true,
+        tags: vec!["a".into(), "b".into()],
+    };
+
+    let obj = KVDBObject::from(Settings { ..settings });
+    assert_eq!(
+        keys(&obj),
+        vec!["darkMode", "lang", "pageSize", "tags", "theme"]
+    );
+    assert_eq!(obj["tags"], "a,b".into());
+
+    db.hash_set("settings", obj).unwrap();
+    let loaded: Settings = db.hash_get_object("settings").unwrap().unwrap();
+    assert_eq!(
+        loaded,
+        Settings {
+            dark_mode: true,
+            language: "pl".into(),
+            page_size: 50,
+            theme: "dark".into(),
+            dirty: false,
+            tags: vec!["a".into(), "b".into()],
+        }
+    );
+}
+
+#[test]
+fn reads_defaults_for_missing_fields() {
+    let obj: KVDBObject = [
+        ("darkMode".into(), false.into()),
+        ("lang".into(), "en".into()),
+        ("tags".into(), "x".into()),
+    ]
+    .into();
+
+    let settings = Settings::try_from(obj).unwrap();
+    assert_eq!(settings.page_size, 20);
+    assert_eq!(settings.theme, "");
+
+    let missing: KVDBObject = [("darkMode".into(), false.into())].into();
+    assert!(matches!(
+        Settings::try_from(missing),
+        Err(MiniKVDBError::MissingField(field)) if field == "lang"
+    ));
+}
+
+#[derive(KVDBEntity)]
+#[kvdb(rename_all = "lowercase")]
+struct Lower {
+    page_size: i32,
+}
+
+#[derive(KVDBEntity)]
+#[kvdb(rename_all = "UPPERCASE")]
+struct Upper {
+    page_size: i32,
+}
+
+#[derive(KVDBEntity)]
+#[kvdb(rename_all = "SCREAMING_SNAKE_CASE")]
+struct ScreamingSnake {
+    page_size: i32,
+}
+
+#[derive(KVDBEntity)]
+#[kvdb(rename_all = "kebab-case")]
+struct Kebab {
+    page_size: i32,
+}
+
+#[derive(KVDBEntity)]
+#[kvdb(rename_all = "PascalCase")]
+struct Pascal {
+    page_size: i32,
+}
+
+#[derive(Debug, PartialEq, KVDBEntity)]
+#[kvdb(rename_all = "lowercase")]
+enum LowerRole {
+    ReadOnly,
+}
+
+#[derive(Debug, PartialEq, KVDBEntity)]
+#[kvdb(rename_all = "kebab-case")]
+enum KebabRole {
+    ReadOnly,
+}
+
+#[test]
+fn renames_fields_and_variants() {
+    assert_eq!(keys(&Lower { page_size: 1 }.into()), vec!["page_size"]);
+    assert_eq!(keys(&Upper { page_size: 1 }.into()), vec!["PAGE_SIZE"]);
+    assert_eq!(
+        keys(&ScreamingSnake { page_size: 1 }.into()),
+        vec!["PAGE_SIZE"]
+    );
+    assert_eq!(keys(&Kebab { page_size: 1 }.into()), vec!["page-size"]);
+    assert_eq!(keys(&Pascal { page_size: 1 }.into()), vec!["PageSize"]);
+
+    assert_eq!(KVDBValue::from(LowerRole::ReadOnly), "readonly".into());
+    assert_eq!(KVDBValue::from(KebabRole::ReadOnly), "read-only".into());
+    assert_eq!(
+        LowerRole::try_from(KVDBValue::from("readonly")).unwrap(),
+        LowerRole::ReadOnly
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+#[kvdb(rename_all = "snake_case")]
+enum Shape {
+    Circle { radius: f32 },
+    Rect { width: i32, height: i32 },
+    Empty,
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+#[kvdb(tag = "kind")]
+enum Event {
+    Login { r#type: String },
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct UserId(String);
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct Point(i32, i32);
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct Labeled<T> {
+    label: String,
+    value: T,
+}
+
+#[test]
+fn round_trips_enums_tuples_and_generics() {
+    let db = MiniKVDB::default();
+    for (i, shape) in [
+        Shape::Circle { radius: 1.5 },
+        Shape::Rect {
+            width: 2,
+            height: 3,
+        },
+        Shape::Empty,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        db.hash_set(format!("shape:{i}"), shape.clone()).unwrap();
+        assert_eq!(
+            db.hash_get_object::<Shape>(format!("shape:{i}")).unwrap(),
+            Some(shape)
+        );
+    }
+    assert_eq!(db.hash_get("shape:1", "type").unwrap(), Some("rect".into()));
+
+    let event = KVDBObject::from(Event::Login {
+        r#type: "password".into(),
+    });
+    assert_eq!(event["kind"], "Login".into());
+    assert_eq!(event["type"], "password".into());
+
+    assert_eq!(KVDBValue::from(UserId("u1".into())), "u1".into());
+    assert_eq!(
+        UserId::try_from(KVDBValue::from("u1")).unwrap(),
+        UserId("u1".into())
+    );
+    let point = KVDBValue::from(Point(1, -1));
+    assert_eq!(point, KVDBValue::List(vec![1.into(), (-1).into()]));
+    assert_eq!(Point::try_from(point).unwrap(), Point(1, -1));
+
+    let labeled = Labeled {
+        label: "size".into(),
+        value: Point(2, 3),
+    };
+    db.hash_set("labeled", labeled.clone()).unwrap();
+    assert_eq!(
+        db.hash_get_object::<Labeled<Point>>("labeled").unwrap(),
+        Some(labeled)
+    );
+}
+
+#[test]
+fn rejects_unknown_variants() {
+    let obj: KVDBObject = [("type".into(), "triangle".into())].into();
+    assert!(matches!(
+        Shape::try_from(obj),
+        Err(MiniKVDBError::UnknownVariant(v)) if v == "triangle"
+    ));
+    let obj: KVDBObject = [("radius".into(), 1.0f32.into())].into();
+    assert!(Shape::try_from(obj).is_err());
+}
+
+#[derive(Debug, Clone, Default, PartialEq, KVDBEntity)]
+struct Address {
+    city: String,
+    street: String,
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct Person {
+    name: String,
+    #[kvdb(flatten)]
+    home: Address,
+    #[kvdb(flatten, default)]
+    office: Address,
+    billing: Address,
+    shipping: Option<Address>,
+}
+
+#[test]
+fn round_trips_nested_and_flattened_entities() {
+    let db = MiniKVDB::default();
+    let address = |city: &str| Address {
+        city: city.into(),
+        street: "Main".into(),
+    };
+    let person = Person {
+        name: "tom".into(),
+        home: address("Warsaw"),
+        office: address("Berlin"),
+        billing: address("Paris"),
+        shipping: None,
+    };
+
+    let obj = KVDBObject::from(person.clone());
+    assert_eq!(
+        keys(&obj),
+        vec![
+            "billing",
+            "home.city",
+            "home.street",
+            "name",
+            "office.city",
+            "office.street",
+            "shipping"
+        ]
+    );
+    assert_eq!(obj["shipping"], KVDBValue::Null);
+
+    db.hash_set("person:1", obj).unwrap();
+    assert_eq!(
+        db.hash_get("person:1", "home.city").unwrap(),
+        Some("Warsaw".into())
+    );
+    assert_eq!(
+        db.hash_get_path("person:1", "billing.city").unwrap(),
+        Some("Paris".into())
+    );
+    assert_eq!(
+        db.hash_get_object::<Person>("person:1").unwrap(),
+        Some(person)
+    );
+
+    let mut obj = KVDBObject::from(Person {
+        name: "ann".into(),
+        home: address("Rome"),
+        office: address("Oslo"),
+        billing: address("Lima"),
+        shipping: Some(address("Kyiv")),
+    });
+    obj.retain(|k, _| !k.0.starts_with("office."));
+    let person = Person::try_from(obj).unwrap();
+    assert_eq!(person.office, Address::default());
+    assert_eq!(person.shipping, Some(address("Kyiv")));
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct Contacts {
+    addresses: Vec<Address>,
+    by_name: std::collections::HashMap<String, Address>,
+    scores: Vec<Option<i32>>,
+}
+
+#[test]
+fn round_trips_collections_of_entities() {
+    let db = MiniKVDB::default();
+    let address = |city: &str| Address {
+        city: city.into(),
+        street: "Main".into(),
+    };
+    let contacts = Contacts {
+        addresses: vec![address("Warsaw"), address("Berlin")],
+        by_name: [("tom".to_owned(), address("Paris"))].into(),
+        scores: vec![Some(3), None],
+    };
+
+    db.hash_set("contacts:1", contacts.clone()).unwrap();
+    assert_eq!(
+        db.hash_get_path("contacts:1", "addresses[1].city").unwrap(),
+        Some("Berlin".into())
+    );
+    assert_eq!(
+        db.hash_get_object::<Contacts>("contacts:1").unwrap(),
+        Some(contacts)
+    );
+
+    let fields = Contacts::fields();
+    assert_eq!(fields[0].kvdb_type, Some(KVDBType::List));
+    assert_eq!(fields[1].kvdb_type, Some(KVDBType::Map));
+
+    db.hash_set_path("contacts:1", "addresses[0]", 1).unwrap();
+    match db.hash_get_object::<Contacts>("contacts:1") {
+        Err(MiniKVDBError::InvalidObject { source, .. }) => {
+            assert!(
+                matches!(*source, MiniKVDBError::Field { ref field, .. } if field == "addresses")
+            )
+        }
+        res => panic!("unexpected result {res:?}"),
+    }
+}
+
+#[test]
+fn prefixes_errors_and_fields_of_flattened_entities() {
+    let obj: KVDBObject = [
+        ("name".into(), "tom".into()),
+        ("home.city".into(), "Warsaw".into()),
+        ("billing".into(), KVDBValue::Map(Address::default().into())),
+    ]
+    .into();
+    assert_eq!(
+        Person::try_from(obj).unwrap_err().to_string(),
+        MiniKVDBError::MissingField("home.street".into()).to_string()
+    );
+
+    let fields = Person::fields();
+    let home = fields.iter().find(|f| f.name.0 == "home.city").unwrap();
+    assert!(home.required);
+    let office = fields.iter().find(|f| f.name.0 == "office.city").unwrap();
+    assert!(!office.required);
+}
+
+/// Field type with conversions but without `KVDBFieldType`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cents(i32);
+
+impl From<Cents> for KVDBValue {
+    fn from(v: Cents) -> Self {
+        v.0.into()
+    }
+}
+
+impl TryFrom<&KVDBValue> for Cents {
+    type Error = MiniKVDBError;
+
+    fn try_from(v: &KVDBValue) -> Result<Self, Self::Error> {
+        i32::try_from(v).map(Cents)
+    }
+}
+
+impl TryFrom<KVDBValue> for Cents {
+    type Error = MiniKVDBError;
+
+    fn try_from(v: KVDBValue) -> Result<Self, Self::Error> {
+        Self::try_from(&v)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct Price(Cents);
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+struct Order {
+    total: Cents,
+    price: Price,
+    discount: Option<Cents>,
+    items: i32,
+}
+
+#[test]
+fn describes_custom_field_types_as_any_value() {
+    let db = MiniKVDB::default();
+    let order = Order {
+        total: Cents(1250),
+        price: Price(Cents(1150)),
+        discount: Some(Cents(100)),
+        items: 3,
+    };
+    db.hash_set("order:1", order.clone()).unwrap();
+    assert_eq!(db.hash_get_object::<Order>("order:1").unwrap(), Some(order));
+
+    let fields = Order::fields();
+    let field = |name: &str| fields.iter().find(|f| f.name.0 == name).unwrap();
+    assert_eq!(field("total").kvdb_type, None);
+    assert!(field("total").required);
+    assert_eq!(field("discount").kvdb_type, None);
+    assert!(!field("discount").required);
+    assert_eq!(field("price").kvdb_type, None);
+    assert_eq!(field("items").kvdb_type, Some(KVDBType::Int));
+    assert_eq!(
+        <UserId as KVDBFieldType>::kvdb_type(),
+        Some(KVDBType::String)
+    );
+    assert!(field("items").required);
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+#[kvdb(prefix = "user", id = "id")]
+struct User {
+    id: i32,
+    name: String,
+}
+
+#[test]
+fn repository_lists_only_direct_children() {
+    let db = MiniKVDB::default();
+    let users = UserRepository::new(&db);
+    let user = |id: i32, name: &str| User {
+        id,
+        name: name.into(),
+    };
+
+    users.save(user(1, "tom")).unwrap();
+    users.save(user(2, "ann")).unwrap();
+    db.hash_set("user:1:settings", Settings::default()).unwrap();
+    db.hash_set("users:1", user(3, "bob")).unwrap();
+
+    assert_eq!(UserRepository::key(1).0, "user:1");
+    assert_eq!(users.find_by_id(2).unwrap(), Some(user(2, "ann")));
+    assert_eq!(users.find_by_id(3).unwrap(), None);
+    assert!(users.exists(1).unwrap());
+    assert_eq!(users.count().unwrap(), 2);
+    assert_eq!(
+        users.list_all().unwrap(),
+        vec![user(1, "tom"), user(2, "ann")]
+    );
+
+    assert!(users.delete(1).unwrap());
+    assert!(!users.delete(1).unwrap());
+    assert!(!users.exists(1).unwrap());
+    assert_eq!(users.count().unwrap(), 1);
+    assert_eq!(users.list_all().unwrap(), vec![user(2, "ann")]);
+    assert!(db.hash_contains_key("user:1:settings").unwrap());
+    let keys: Vec<_> = db
+        .hash_keys("user:")
+        .unwrap()
+        .into_iter()
+        .map(|k| k.0)
+        .collect();
+    assert_eq!(keys, vec!["user:1:settings", "user:2"]);
+}
+
+#[derive(Debug, Clone, PartialEq, KVDBEntity)]
+#[kvdb(prefix = "tag", id = "name")]
+struct Tag {
+    name: String,
+}
+
+#[test]
+fn repository_rejects_ids_with_separator() {
+    let db = MiniKVDB::default();
+    let tags = TagRepository::new(&db);
+    let tag = |name: &str| Tag { name: name.into() };
+
+    tags.save(tag("rust")).unwrap();
+    assert!(matches!(
+        tags.save(tag("rust:async")),
+        Err(MiniKVDBError::InvalidId(id)) if id == "rust:async"
+    ));
+    assert!(!db.hash_contains_key("tag:rust:async").unwrap());
+    assert_eq!(tags.count().unwrap(), 1);
+    assert_eq!(tags.list_all().unwrap(), vec![tag("rust")]);
+}
+
+#[test]
+fn rejects_invalid_attributes() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
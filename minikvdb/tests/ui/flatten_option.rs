@@ -0,0 +1,14 @@
+use minikvdb_macros::KVDBEntity;
+
+#[derive(KVDBEntity)]
+struct Address {
+    city: String,
+}
+
+#[derive(KVDBEntity)]
+struct Person {
+    #[kvdb(flatten)]
+    address: Option<Address>,
+}
+
+fn main() {}
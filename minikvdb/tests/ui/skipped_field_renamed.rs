@@ -0,0 +1,9 @@
+use minikvdb_macros::KVDBEntity;
+
+#[derive(KVDBEntity)]
+struct Settings {
+    #[kvdb(skip, rename = "dirty")]
+    dirty: bool,
+}
+
+fn main() {}
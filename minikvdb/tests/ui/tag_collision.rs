@@ -0,0 +1,17 @@
+use minikvdb_macros::KVDBEntity;
+
+#[derive(KVDBEntity)]
+enum Event {
+    Login { r#type: String },
+}
+
+#[derive(KVDBEntity)]
+#[kvdb(tag = "kind")]
+enum Renamed {
+    Login {
+        #[kvdb(rename = "kind")]
+        method: String,
+    },
+}
+
+fn main() {}
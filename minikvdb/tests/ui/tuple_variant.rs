@@ -0,0 +1,8 @@
+use minikvdb_macros::KVDBEntity;
+
+#[derive(KVDBEntity)]
+enum Shape {
+    Circle(f32),
+}
+
+fn main() {}
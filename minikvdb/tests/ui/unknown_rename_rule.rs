@@ -0,0 +1,9 @@
+use minikvdb_macros::KVDBEntity;
+
+#[derive(KVDBEntity)]
+#[kvdb(rename_all = "Title Case")]
+struct Settings {
+    page_size: i32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use minikvdb_macros::KVDBEntity;
+
+#[derive(KVDBEntity)]
+struct Settings {
+    #[kvdb(alias = "size")]
+    page_size: i32,
+}
+
+fn main() {}
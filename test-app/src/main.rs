@@ -7,7 +7,7 @@ fn main() {
 
     let _ = db.set("name", "Tom");
 
-    dbg!(db.increment("visits", 1));
+    let _ = dbg!(db.increment("visits", 1));
 
     let _ = db.push_front(
         "things",
@@ -27,21 +27,21 @@ fn main() {
         ),
     );
 
-    dbg!(db.set("long1", 8i64));
-    dbg!(db.increment("long1", 1));
+    let _ = dbg!(db.set("long1", 8i64));
+    let _ = dbg!(db.increment("long1", 1));
 
-    dbg!(db.list_contains(format!("{}", "things"), true));
-    dbg!(db.list_remove(("things", true)));
-    dbg!(db.list_contains("things", true));
+    let _ = dbg!(db.list_contains("things", true));
+    let _ = dbg!(db.list_remove(("things", true)));
+    let _ = dbg!(db.list_contains("things", true));
 
-    dbg!(db.pop_back("things"));
-    dbg!(db.pop_front("things"));
-    dbg!(db.list_len("things"));
+    let _ = dbg!(db.pop_back("things"));
+    let _ = dbg!(db.pop_front("things"));
+    let _ = dbg!(db.list_len("things"));
 
     // TODO: Would be nice if you could ommit tuple parens.
-    dbg!(db.list_range(("things", 0_usize, 5_usize)));
-    dbg!(db.list_range(("things", 2_usize)));
-    dbg!(db.list_range(("things",)));
+    let _ = dbg!(db.list_range(("things", 0_usize, 5_usize)));
+    let _ = dbg!(db.list_range(("things", 2_usize)));
+    let _ = dbg!(db.list_range(("things",)));
 
     // dbg!(&db);
 
@@ -58,7 +58,7 @@ fn main() {
         },
     );
 
-    dbg!(db.hash_get_object::<Person>("user:2"));
+    let _ = dbg!(db.hash_get_object::<Person>("user:2"));
 
     let _ = db.hash_set(
         NamespacedKey::new("cred").ns(3.141529).compose(),
@@ -67,8 +67,130 @@ fn main() {
             password: "passw0rd".to_owned(),
         },
     );
-    dbg!(db.hash_get_object::<Credentials>("cred:3.141529"));
-    dbg!(db.hash_contains_key("user:2"));
+    let _ = dbg!(db.hash_get_object::<Credentials>("cred:3.141529"));
+    let _ = dbg!(db.hash_contains_key("user:2"));
+
+    let _ = db.hash_set(
+        "user:3",
+        Profile {
+            tags: vec!["admin".to_owned(), "beta".to_owned()],
+            address: Address {
+                city: "Warsaw".to_owned(),
+                street: "Main".to_owned(),
+            },
+            nickname: None,
+            manager: None,
+        },
+    );
+    let _ = dbg!(db.hash_set_path("user:3", "address.city", "Berlin"));
+    let _ = dbg!(db.hash_get_path("user:3", "tags[1]"));
+    let _ = dbg!(db.hash_get_object::<Profile>("user:3"));
+
+    let accounts = CredentialsRepository::new(&db);
+    let _ = accounts.save(Credentials {
+        email: "tom@example.com".to_owned(),
+        password: "secret".to_owned(),
+    });
+    let _ = dbg!(accounts.find_by_id("tom@example.com"));
+    let _ = dbg!(accounts.count());
+
+    let _ = db.hash_set(
+        "settings",
+        [
+            ("darkMode".into(), true.into()),
+            ("lang".into(), "pl".into()),
+            ("tags".into(), "a,b".into()),
+        ]
+        .into_iter()
+        .collect::<KVDBObject>(),
+    );
+    let settings = dbg!(db.hash_get_object::<Settings>("settings"))
+        .expect("settings are readable")
+        .expect("settings were saved");
+    assert!(!settings.dirty);
+
+    let _ = db.hash_set(
+        "shape:1",
+        Shape::Circle {
+            center: Point(0, 0),
+            radius: 2.5,
+        },
+    );
+    let _ = dbg!(db.hash_get_all("shape:1"));
+    let _ = dbg!(db.hash_get_object::<Shape>("shape:1"));
+
+    let _ = db.hash_set(
+        "page:1",
+        Page {
+            item: Role::ReadOnly,
+            owner: UserId("tom".to_owned()),
+        },
+    );
+    let _ = dbg!(db.hash_get_all("page:1"));
+    let _ = dbg!(db.hash_get_object::<Page<Role>>("page:1"));
+
+    let _ = db.hash_set(
+        "customer:1",
+        Customer {
+            name: "Tom".to_owned(),
+            billing: Address {
+                city: "Warsaw".to_owned(),
+                street: "Main".to_owned(),
+            },
+            shipping: Address {
+                city: "Berlin".to_owned(),
+                street: "Side".to_owned(),
+            },
+        },
+    );
+    let _ = dbg!(db.hash_get("customer:1", "billing.city"));
+    let _ = dbg!(db.hash_get_path("customer:1", "shipping.city"));
+    let _ = dbg!(db.hash_get_object::<Customer>("customer:1"));
+
+    let _ = db.hash_set_schema(MapSchema::for_entity::<Customer>("customer:"));
+    let _ = dbg!(db.hash_set(
+        "customer:2",
+        [("name".into(), 1.into())]
+            .into_iter()
+            .collect::<KVDBObject>(),
+    ));
+
+    let _ = db.hash_set(
+        "article:1",
+        [("title".into(), "Old".into())]
+            .into_iter()
+            .collect::<KVDBObject>(),
+    );
+    let _ = db.hash_register_migration("article:", 1, |mut obj| {
+        obj.insert("views".into(), 0.into());
+        Ok(obj)
+    });
+    let _ = dbg!(db.hash_get_object::<Article>("article:1"));
+    let _ = dbg!(db.hash_migrate(
+        MigrationJob::new("article:").on_progress(|p| println!("{}/{}", p.processed, p.total))
+    ));
+    let _ = db.hash_set(
+        "article:2",
+        Article {
+            title: "New".to_owned(),
+            views: 1,
+        },
+    );
+    let _ = dbg!(db.hash_get_all("article:2"));
+
+    let _ = db.hash_set(
+        "article:3",
+        [("title".into(), 3.into())]
+            .into_iter()
+            .collect::<KVDBObject>(),
+    );
+    if let Err(e) = db.hash_get_object::<Article>("article:3") {
+        let mut source: Option<&dyn std::error::Error> = Some(&e);
+        while let Some(e) = source {
+            println!("{e}");
+            source = e.source();
+        }
+    }
 
     let _x = Utc::now();
 
@@ -86,7 +208,98 @@ pub struct Person {
 }
 
 #[derive(Debug, Default, Clone, KVDBEntity)]
+#[kvdb(prefix = "account", id = "email")]
 pub struct Credentials {
     email: String,
     password: String,
 }
+
+#[derive(Debug, Default, Clone, KVDBEntity)]
+pub struct Address {
+    city: String,
+    street: String,
+}
+
+#[derive(Debug, Default, Clone, KVDBEntity)]
+pub struct Profile {
+    tags: Vec<String>,
+    address: Address,
+    nickname: Option<String>,
+    manager: Option<Address>,
+}
+
+#[derive(Debug, Default, Clone, KVDBEntity)]
+#[kvdb(rename_all = "camelCase")]
+pub struct Settings {
+    dark_mode: bool,
+    #[kvdb(rename = "lang")]
+    language: String,
+    #[kvdb(default = "default_page_size")]
+    page_size: i32,
+    #[kvdb(skip)]
+    dirty: bool,
+    #[kvdb(with = "comma_list")]
+    tags: Vec<String>,
+}
+
+fn default_page_size() -> i32 {
+    20
+}
+
+mod comma_list {
+    use minikvdb::{error::Result, prelude::KVDBValue};
+
+    pub fn into_value(v: Vec<String>) -> KVDBValue {
+        v.join(",").into()
+    }
+
+    pub fn from_value(v: &KVDBValue) -> Result<Vec<String>> {
+        let s: String = v.try_into()?;
+        Ok(s.split(',').map(str::to_owned).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, KVDBEntity)]
+#[kvdb(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    ReadOnly,
+}
+
+#[derive(Debug, Clone, KVDBEntity)]
+pub struct UserId(String);
+
+#[derive(Debug, Clone, Copy, KVDBEntity)]
+pub struct Point(i32, i32);
+
+#[derive(Debug, Clone, KVDBEntity)]
+#[kvdb(tag = "kind", rename_all = "lowercase")]
+pub enum Shape {
+    Circle { center: Point, radius: f32 },
+    Square { side: f32 },
+    Empty,
+}
+
+#[derive(Debug, Clone, KVDBEntity)]
+pub struct Page<T>
+where
+    T: Clone,
+{
+    item: T,
+    owner: UserId,
+}
+
+#[derive(Debug, Default, Clone, KVDBEntity)]
+#[kvdb(version = 2)]
+pub struct Article {
+    title: String,
+    views: i32,
+}
+
+#[derive(Debug, Default, Clone, KVDBEntity)]
+pub struct Customer {
+    name: String,
+    #[kvdb(flatten)]
+    billing: Address,
+    shipping: Address,
+}